@@ -0,0 +1,109 @@
+//! The TUI rebuilds its display lines every second, so this has to stay cheap even for big boards.
+
+use bvg_departures::api::departures::DeparturesResponse;
+use bvg_departures::api::BvgClient;
+use bvg_departures::board::{Board, StationBoard};
+use bvg_departures::view::build_display_lines;
+use bvg_departures::view::summary::station_summary;
+use bvg_departures::InputStops;
+use chrono::{Duration, Local, Utc};
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use serde_json::json;
+use std::hint::black_box;
+
+const LINES: &[(&str, &str)] = &[
+    ("U8", "subway"),
+    ("S41", "suburban"),
+    ("M10", "tram"),
+    ("200", "bus"),
+    ("RE1", "regional"),
+];
+
+const DIRECTIONS: &[&str] = &["Hermannstr.", "Wittenau", "Ring", "Warschauer Str.", "Zoo"];
+
+fn stops(count: usize) -> InputStops {
+    let mut config = String::from("stops:\n");
+    for i in 0..count {
+        config.push_str(&format!(
+            "  - id: '9001000{:02}'\n    name: Stop {}\n    directions: [Ring, Zoo]\n",
+            i, i
+        ));
+    }
+    serde_yaml::from_str(&config).expect("valid config")
+}
+
+/// `departures` departures spread over the next hours, some with delays and warnings.
+fn station(stops: &InputStops, index: usize, departures: usize) -> StationBoard {
+    let now = Utc::now();
+    let departures: Vec<_> = (0..departures)
+        .map(|i| {
+            let (line, product) = LINES[i % LINES.len()];
+            let when = now + Duration::seconds(i as i64 * 20 - 120);
+            json!({
+                "tripId": format!("1|{}|0|86|16102026", i),
+                "direction": DIRECTIONS[i % DIRECTIONS.len()],
+                "line": {"name": line, "product": product},
+                "when": when.to_rfc3339(),
+                "plannedWhen": (when - Duration::minutes(1)).to_rfc3339(),
+                "delay": (i % 4) * 60,
+                "remarks": if i % 10 == 0 {
+                    json!([{"type": "warning", "summary": "Aufzug defekt"}])
+                } else {
+                    json!([])
+                },
+            })
+        })
+        .collect();
+    let response: DeparturesResponse =
+        serde_json::from_value(json!({ "departures": departures })).expect("valid response");
+    StationBoard::new(&stops.stops[index], response)
+}
+
+fn board(stations: usize, departures_per_station: usize) -> (InputStops, Board) {
+    let stops = stops(stations);
+    let stations = (0..stations)
+        .map(|i| station(&stops, i, departures_per_station))
+        .collect();
+    let board = Board {
+        stations,
+        fetched_at: Local::now(),
+    };
+    (stops, board)
+}
+
+fn bench_display_lines(c: &mut Criterion) {
+    let mut group = c.benchmark_group("build_display_lines");
+    for (stations, per_station) in [(1, 1_000), (10, 100), (10, 1_000)] {
+        let (stops, board) = board(stations, per_station);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{}x{}", stations, per_station)),
+            &board,
+            |b, board| b.iter(|| build_display_lines(black_box(board), &stops)),
+        );
+    }
+    group.finish();
+}
+
+fn bench_filter(c: &mut Criterion) {
+    let (stops, board) = board(1, 1_000);
+    let station = &board.stations[0];
+    c.bench_function("filter 1000", |b| {
+        b.iter_batched(
+            || station.clone(),
+            |mut station| BvgClient::filter(&stops.stops[0], &mut station),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn bench_summary(c: &mut Criterion) {
+    let (stops, board) = board(1, 1_000);
+    let lines = build_display_lines(&board, &stops);
+    let entries = &lines[0].1;
+    c.bench_function("station_summary 1000", |b| {
+        b.iter(|| station_summary(black_box(entries)))
+    });
+}
+
+criterion_group!(benches, bench_display_lines, bench_filter, bench_summary);
+criterion_main!(benches);
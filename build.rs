@@ -0,0 +1,33 @@
+//! Embeds build information for `bvg-departures --version`.
+
+use std::process::Command;
+
+fn main() {
+    let git_hash = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .and_then(|o| String::from_utf8(o.stdout).ok())
+        .map(|h| h.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let mut features: Vec<String> = std::env::vars()
+        .filter_map(|(key, _)| key.strip_prefix("CARGO_FEATURE_").map(str::to_string))
+        .filter(|f| f != "DEFAULT")
+        .map(|f| f.to_lowercase().replace('_', "-"))
+        .collect();
+    features.sort();
+
+    println!("cargo:rustc-env=BVG_DEPARTURES_GIT_HASH={}", git_hash);
+    println!(
+        "cargo:rustc-env=BVG_DEPARTURES_FEATURES={}",
+        features.join(",")
+    );
+    println!(
+        "cargo:rustc-env=BVG_DEPARTURES_TARGET={}",
+        std::env::var("TARGET").unwrap_or_default()
+    );
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+}
@@ -0,0 +1,189 @@
+//! Spoken announcements of the next departure, e.g. for a departure announcer in the kitchen.
+
+use crate::schedule::Schedule;
+use crate::view::verbose::sentence;
+use crate::view::{DisplayEntry, StationHeader};
+use crate::InputStops;
+use chrono::Local;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tracing::{debug, warn};
+
+/// Name of the environment variable that holds the text to speak.
+pub const TEXT_ENV: &str = "BVG_DEPARTURES_TEXT";
+
+/// Speaks the next departure of the watched lines at an interval, through a command and/or an
+/// HTTP text-to-speech endpoint.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct Announce {
+    /// Shell command that speaks the text from stdin, e.g. `espeak --stdin`. The text is also in
+    /// `$BVG_DEPARTURES_TEXT`, e.g. for `say "$BVG_DEPARTURES_TEXT"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// URL of an HTTP text-to-speech endpoint. It is called with the text as `text` query
+    /// parameter, e.g. `http://localhost:5002/api/tts`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Minutes between announcements
+    #[serde(default = "interval_minutes")]
+    pub interval_minutes: u32,
+    /// Lines to announce. Defaults to `watch_lines`, or all lines if that is empty too.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lines: Vec<String>,
+    /// Only announce in these days and hours, e.g. "Mon-Fri 06:30-08:30". Always if missing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<String>")]
+    pub only: Option<Schedule>,
+}
+
+fn interval_minutes() -> u32 {
+    5
+}
+
+/// Keeps track of when the last announcement was made.
+#[derive(Debug)]
+pub struct Announcer {
+    config: Announce,
+    lines: Vec<String>,
+    http: reqwest::Client,
+    last: Option<Instant>,
+}
+
+impl Announcer {
+    /// None if the config has no `announce` section.
+    pub fn new(stops: &InputStops) -> Option<Self> {
+        let config = stops.announce.clone()?;
+        let lines = if config.lines.is_empty() {
+            stops.watch_lines.clone()
+        } else {
+            config.lines.clone()
+        };
+        Some(Self {
+            config,
+            lines,
+            http: reqwest::Client::new(),
+            last: None,
+        })
+    }
+
+    /// Speaks the next departure if the interval has passed since the last announcement. Speaking
+    /// runs in the background, failures are only logged.
+    pub fn announce(&mut self, board: &[(StationHeader, Vec<DisplayEntry>)]) {
+        let interval = Duration::from_secs(u64::from(self.config.interval_minutes) * 60);
+        if self.last.is_some_and(|last| last.elapsed() < interval) {
+            return;
+        }
+        if let Some(only) = &self.config.only
+            && !only.is_active(Local::now().naive_local())
+        {
+            return;
+        }
+        let Some(text) = self.text(board) else {
+            return;
+        };
+        self.last = Some(Instant::now());
+        debug!("Announcing '{}'", text);
+
+        if let Some(command) = self.config.command.clone() {
+            let text = text.clone();
+            tokio::spawn(async move {
+                if let Err(e) = speak(&command, &text).await {
+                    warn!("Announcement command '{}' failed: {}", command, e);
+                }
+            });
+        }
+        if let Some(url) = self.config.url.clone() {
+            let request = self.http.get(&url).query(&[("text", &text)]).send();
+            tokio::spawn(async move {
+                match request.await.and_then(|r| r.error_for_status()) {
+                    Ok(_) => {}
+                    Err(e) => warn!("Announcement via {} failed: {}", url, e),
+                }
+            });
+        }
+    }
+
+    /// e.g. "Alexanderplatz: U8 toward Wittenau departs in 4 minutes from platform 2."
+    fn text(&self, board: &[(StationHeader, Vec<DisplayEntry>)]) -> Option<String> {
+        let (header, entry) = board
+            .iter()
+            .flat_map(|(header, entries)| entries.iter().map(move |e| (header, e)))
+            .filter(|(_, e)| e.actual_mins >= 0 && e.reachable && !e.cancelled && !e.ghost)
+            .filter(|(_, e)| {
+                self.lines.is_empty() || self.lines.iter().any(|l| l.eq_ignore_ascii_case(&e.line))
+            })
+            .min_by_key(|(_, e)| e.actual_mins)?;
+        // in the merged view each departure knows its station
+        let header = entry.station.as_ref().unwrap_or(header);
+        Some(format!("{}: {}", header.name, sentence(header, entry)))
+    }
+}
+
+async fn speak(command: &str, text: &str) -> anyhow::Result<()> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env(TEXT_ENV, text)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // the command may take the text from the environment instead
+        let _ = stdin.write_all(format!("{}\n", text).as_bytes()).await;
+    }
+    let status = child.wait().await?;
+    if !status.success() {
+        anyhow::bail!("exited with {}", status);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::compat::VersionedDepartures;
+    use crate::board::{Board, StationBoard};
+    use crate::view::build_display_lines;
+    use crate::InputStop;
+    use serde_json::json;
+
+    #[test]
+    fn announces_the_next_departure_of_the_watched_lines() {
+        let when = |m| (Local::now() + chrono::Duration::minutes(m)).to_rfc3339();
+        let versioned: VersionedDepartures = serde_json::from_value(json!({"departures": [
+            {"tripId": "1", "direction": "Hermannstr.", "line": {"name": "U8"}, "when": when(2)},
+            {"tripId": "2", "direction": "Wittenau", "line": {"name": "U8"}, "when": when(6)},
+            {"tripId": "3", "direction": "Pankow", "line": {"name": "U2"}, "when": when(1)},
+        ]}))
+        .unwrap();
+        let stop = InputStop::new("900100003".to_string(), "Alex".to_string());
+        let board = Board {
+            stations: vec![StationBoard::new(&stop, versioned.into())],
+            fetched_at: Local::now(),
+        };
+        let mut stops: InputStops =
+            serde_yaml::from_str("stops: []\nwatch_lines: [u8]\nannounce:\n  command: 'true'\n")
+                .unwrap();
+        let announcer = Announcer::new(&stops).unwrap();
+        let text = announcer
+            .text(&build_display_lines(&board, &stops))
+            .unwrap();
+        assert!(
+            text.starts_with("Alex: U8 toward Hermannstr. departs in"),
+            "{}",
+            text
+        );
+
+        stops.announce.as_mut().unwrap().lines = vec!["U2".to_string()];
+        let announcer = Announcer::new(&stops).unwrap();
+        let text = announcer
+            .text(&build_display_lines(&board, &stops))
+            .unwrap();
+        assert!(text.starts_with("Alex: U2 toward Pankow"), "{}", text);
+    }
+}
@@ -0,0 +1,270 @@
+//! Short-lived cache of departure responses, so that rapid refreshes or several views of the same
+//! stops do not send the same requests again. Optionally kept in a file between runs, so a one-shot
+//! board right after another does not request anything either.
+
+use crate::api::compat::VersionedDepartures;
+use crate::api::departures::{DeparturesParams, DeparturesResponse};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+
+/// Hits and misses of the response cache since the client was created.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    stored: Instant,
+    response: DeparturesResponse,
+    /// As sent by the API, to write the entry to the cache file
+    body: Bytes,
+}
+
+/// Entry of the cache file. `stored_at` is in seconds since the epoch, as an [`Instant`] does not
+/// survive a restart.
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedEntry {
+    stored_at: u64,
+    body: serde_json::Value,
+}
+
+#[derive(Debug)]
+pub(crate) struct ResponseCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, Entry>>,
+    /// Where the entries are kept between runs
+    file: Option<PathBuf>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ResponseCache {
+    pub(crate) fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::default(),
+            file: None,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Also keeps the entries in `file`: the ones younger than the TTL are loaded right away, and
+    /// [`ResponseCache::save`] writes them back. A missing or unreadable file starts empty.
+    pub(crate) fn with_file(mut self, file: PathBuf) -> Self {
+        let now = Instant::now();
+        let saved = std::fs::read(&file)
+            .ok()
+            .map(|content| serde_json::from_slice::<HashMap<String, SavedEntry>>(&content));
+        match saved {
+            Some(Ok(saved)) => {
+                let entries = self.entries.get_mut().unwrap();
+                for (key, saved) in saved {
+                    let age = SystemTime::now()
+                        .duration_since(UNIX_EPOCH + Duration::from_secs(saved.stored_at))
+                        .unwrap_or_default();
+                    let Some(stored) = now.checked_sub(age).filter(|_| age < self.ttl) else {
+                        continue;
+                    };
+                    let Ok(versioned) =
+                        serde_json::from_value::<VersionedDepartures>(saved.body.clone())
+                    else {
+                        continue;
+                    };
+                    entries.insert(
+                        key,
+                        Entry {
+                            stored,
+                            response: versioned.into(),
+                            body: Bytes::from(saved.body.to_string()),
+                        },
+                    );
+                }
+                debug!(
+                    "Loaded {} cached responses from {}",
+                    entries.len(),
+                    file.display()
+                );
+            }
+            Some(Err(e)) => warn!("Ignoring unreadable {}: {}", file.display(), e),
+            None => {}
+        }
+        self.file = Some(file);
+        self
+    }
+
+    /// `when` is left out: it moves with every refresh, while the response hardly changes.
+    pub(crate) fn key(endpoint: &str, stop_id: &str, params: &DeparturesParams) -> String {
+        let params = DeparturesParams {
+            when: None,
+            ..params.clone()
+        };
+        format!(
+            "{}/{}?{}",
+            stop_id,
+            endpoint,
+            serde_json::to_string(&params).unwrap_or_default()
+        )
+    }
+
+    /// The response stored for `key` less than the TTL before `now`.
+    pub(crate) fn get(&self, key: &str, now: Instant) -> Option<DeparturesResponse> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, entry| now.saturating_duration_since(entry.stored) < self.ttl);
+        let response = entries.get(key).map(|entry| entry.response.clone());
+        let counter = if response.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        response
+    }
+
+    pub(crate) fn insert(
+        &self,
+        key: String,
+        body: &Bytes,
+        response: &DeparturesResponse,
+        now: Instant,
+    ) {
+        self.entries.lock().unwrap().insert(
+            key,
+            Entry {
+                stored: now,
+                response: response.clone(),
+                body: body.clone(),
+            },
+        );
+    }
+
+    /// Writes the entries younger than the TTL to the cache file, if there is one. Failures to write
+    /// are only logged.
+    pub(crate) async fn save(&self) {
+        let Some(file) = &self.file else {
+            return;
+        };
+        let saved: HashMap<String, SavedEntry> = {
+            let now = Instant::now();
+            let epoch_now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            let entries = self.entries.lock().unwrap();
+            entries
+                .iter()
+                .filter(|(_, entry)| now.saturating_duration_since(entry.stored) < self.ttl)
+                .filter_map(|(key, entry)| {
+                    let body = serde_json::from_slice(&entry.body).ok()?;
+                    let age = now.saturating_duration_since(entry.stored);
+                    Some((
+                        key.clone(),
+                        SavedEntry {
+                            stored_at: epoch_now.saturating_sub(age).as_secs(),
+                            body,
+                        },
+                    ))
+                })
+                .collect()
+        };
+        if let Err(e) = write(file, &saved).await {
+            warn!(
+                "Could not save the response cache to {}: {}",
+                file.display(),
+                e
+            );
+        }
+    }
+
+    /// Forgets all responses, e.g. when the user asks for a refresh.
+    pub(crate) fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    pub(crate) fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Via a temporary file, so a crash does not leave half a cache behind.
+async fn write(file: &Path, saved: &HashMap<String, SavedEntry>) -> std::io::Result<()> {
+    if let Some(dir) = file.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    let tmp = file.with_extension("json.tmp");
+    tokio::fs::write(&tmp, serde_json::to_vec(saved)?).await?;
+    tokio::fs::rename(&tmp, file).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_expire_and_ignore_when() {
+        let cache = ResponseCache::new(Duration::from_secs(15));
+        let now = Instant::now();
+        let response = DeparturesResponse {
+            departures: vec![],
+            realtime_data_updated_at: Some(1),
+        };
+        let params = |when: &str| DeparturesParams {
+            when: Some(when.to_string()),
+            duration: Some(30),
+            ..Default::default()
+        };
+        let key = ResponseCache::key("departures", "900100003", &params("12:00"));
+        cache.insert(key, &Bytes::new(), &response, now);
+
+        let later = ResponseCache::key("departures", "900100003", &params("12:01"));
+        assert!(cache.get(&later, now + Duration::from_secs(14)).is_some());
+        let arrivals = ResponseCache::key("arrivals", "900100003", &params("12:01"));
+        assert!(cache.get(&arrivals, now).is_none());
+        assert!(cache.get(&later, now + Duration::from_secs(15)).is_none());
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 2 });
+
+        cache.insert(later.clone(), &Bytes::new(), &response, now);
+        cache.clear();
+        assert!(cache.get(&later, now).is_none());
+    }
+
+    #[tokio::test]
+    async fn entries_are_kept_between_runs() {
+        let file = std::env::temp_dir().join(format!("bvg-cache-{}.json", std::process::id()));
+        let ttl = Duration::from_secs(60);
+        let params = DeparturesParams::default();
+        let fresh = ResponseCache::key("departures", "900100003", &params);
+        let old = ResponseCache::key("departures", "900100001", &params);
+
+        let cache = ResponseCache::new(ttl).with_file(file.clone());
+        let body =
+            Bytes::from_static(br#"{"departures": [{"tripId": "1|2", "direction": "Pankow"}]}"#);
+        let response: DeparturesResponse = serde_json::from_slice::<VersionedDepartures>(&body)
+            .unwrap()
+            .into();
+        let now = Instant::now();
+        cache.insert(fresh.clone(), &body, &response, now);
+        cache.insert(old.clone(), &body, &response, now - Duration::from_secs(61));
+        cache.save().await;
+
+        let restarted = ResponseCache::new(ttl).with_file(file.clone());
+        let loaded = restarted.get(&fresh, Instant::now()).unwrap();
+        assert_eq!(loaded.departures[0].trip_id.as_deref(), Some("1|2"));
+        assert!(restarted.get(&old, Instant::now()).is_none());
+
+        // a shorter TTL than the one of the last run still applies
+        let impatient = ResponseCache::new(Duration::ZERO).with_file(file.clone());
+        assert!(impatient.get(&fresh, Instant::now()).is_none());
+        std::fs::remove_file(&file).unwrap();
+    }
+}
@@ -0,0 +1,167 @@
+//! Stops hitting the API for a while after repeated failures, so that every view refreshing at
+//! once does not turn an outage into a retry storm.
+
+use crate::api::departures::DeparturesError;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+#[derive(Debug)]
+pub(crate) struct CircuitBreaker {
+    /// Consecutive failures after which the circuit opens
+    threshold: u32,
+    cool_down: Duration,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    failures: u32,
+    open_until: Option<Instant>,
+    /// Start of the request let through after the cool-down, while its result is pending
+    probe_since: Option<Instant>,
+    /// Whether the last failure was a 429
+    rate_limited: bool,
+}
+
+impl CircuitBreaker {
+    pub(crate) fn new(threshold: u32, cool_down: Duration) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            cool_down,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Fails fast while the circuit is open. After the cool-down one request is let through as a
+    /// probe and the others keep failing fast until it is done; if the probe fails, the circuit
+    /// opens again right away. A probe that never reports back is replaced after another cool-down.
+    pub(crate) fn check(&self) -> Result<(), DeparturesError> {
+        let mut state = self.state.lock().expect("breaker lock");
+        let now = Instant::now();
+        match (state.open_until, state.probe_since) {
+            (Some(until), _) if until > now => Err(DeparturesError::CircuitOpen {
+                retry_in: until - now,
+                rate_limited: state.rate_limited,
+            }),
+            (Some(_), Some(since)) if now - since < self.cool_down => {
+                Err(DeparturesError::CircuitOpen {
+                    retry_in: self.cool_down - (now - since),
+                    rate_limited: state.rate_limited,
+                })
+            }
+            (Some(_), _) => {
+                state.probe_since = Some(now);
+                Ok(())
+            }
+            (None, _) => Ok(()),
+        }
+    }
+
+    pub(crate) fn succeeded(&self) {
+        let mut state = self.state.lock().expect("breaker lock");
+        state.failures = 0;
+        state.open_until = None;
+        state.probe_since = None;
+        state.rate_limited = false;
+    }
+
+    pub(crate) fn failed(&self, error: &DeparturesError) {
+        let mut state = self.state.lock().expect("breaker lock");
+        state.rate_limited = matches!(error, DeparturesError::RateLimited { .. });
+        if state.probe_since.take().is_some() {
+            warn!(
+                "The API is still unavailable, pausing requests for {}s",
+                self.cool_down.as_secs()
+            );
+            state.open_until = Some(Instant::now() + self.cool_down);
+            return;
+        }
+        state.failures += 1;
+        if state.failures >= self.threshold && state.open_until.is_none() {
+            warn!(
+                "{} requests in a row failed, pausing requests for {}s",
+                state.failures,
+                self.cool_down.as_secs()
+            );
+            state.open_until = Some(Instant::now() + self.cool_down);
+        }
+    }
+}
+
+impl DeparturesError {
+    /// Whether the error means the API is unavailable, as opposed to e.g. an unknown stop id.
+    pub(crate) fn is_upstream_failure(&self) -> bool {
+        match self {
+            DeparturesError::Http(_) | DeparturesError::RateLimited { .. } => true,
+            DeparturesError::Status { status, .. } => status.is_server_error(),
+            DeparturesError::Url(_)
+            | DeparturesError::CircuitOpen { .. }
+            | DeparturesError::Json(_)
+            | DeparturesError::TimedOut { .. }
+            | DeparturesError::Fixture { .. }
+            | DeparturesError::Unsupported(_) => false,
+        }
+    }
+
+    /// Set if the API rate limits us, with the `Retry-After` it sent. Also set while the circuit
+    /// is open because of 429s, which then are not sent anymore.
+    pub fn rate_limit(&self) -> Option<Option<Duration>> {
+        match self {
+            DeparturesError::RateLimited { retry_after } => Some(*retry_after),
+            DeparturesError::CircuitOpen {
+                rate_limited: true, ..
+            } => Some(None),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_after_threshold_and_probes_after_cool_down() {
+        let unavailable = DeparturesError::Status {
+            status: reqwest::StatusCode::BAD_GATEWAY,
+            body: String::new(),
+        };
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+        breaker.failed(&unavailable);
+        breaker.succeeded();
+        breaker.failed(&unavailable);
+        assert!(breaker.check().is_ok());
+        breaker.failed(&unavailable);
+        assert!(matches!(
+            breaker.check(),
+            Err(DeparturesError::CircuitOpen { .. })
+        ));
+
+        let breaker = CircuitBreaker::new(2, Duration::ZERO);
+        breaker.failed(&unavailable);
+        breaker.failed(&unavailable);
+        // the probe is let through, but a single failure opens the circuit again
+        assert!(breaker.check().is_ok());
+        breaker.failed(&unavailable);
+        assert!(breaker.state.lock().unwrap().open_until.is_some());
+
+        // while the probe is pending, the other requests still fail fast
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(60));
+        breaker.failed(&unavailable);
+        breaker.state.lock().unwrap().open_until = Some(Instant::now());
+        assert!(breaker.check().is_ok());
+        assert!(matches!(
+            breaker.check(),
+            Err(DeparturesError::CircuitOpen { .. })
+        ));
+        breaker.succeeded();
+        assert!(breaker.check().is_ok());
+        assert!(breaker.check().is_ok());
+
+        // while open because of 429s, callers still learn they are rate limited
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(60));
+        breaker.failed(&DeparturesError::RateLimited { retry_after: None });
+        assert_eq!(breaker.check().unwrap_err().rate_limit(), Some(None));
+    }
+}
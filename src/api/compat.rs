@@ -0,0 +1,53 @@
+//! Compatibility with older hafas-rest deployments.
+//!
+//! v6 wraps departures in an object (`{"departures": [...], "realtimeDataUpdatedAt": ...}`),
+//! v5 returns a bare array. Renamed fields of older versions are handled with `serde(alias)`
+//! on the response types themselves.
+
+use crate::api::departures::{Departure, DeparturesResponse};
+use serde::Deserialize;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiVersion {
+    V5,
+    V6,
+}
+
+impl fmt::Display for ApiVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiVersion::V5 => write!(f, "v5"),
+            ApiVersion::V6 => write!(f, "v6"),
+        }
+    }
+}
+
+/// Departures payload of any supported API version. The version is detected from the shape.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum VersionedDepartures {
+    V6(DeparturesResponse),
+    V5(Vec<Departure>),
+}
+
+impl VersionedDepartures {
+    pub fn version(&self) -> ApiVersion {
+        match self {
+            VersionedDepartures::V6(_) => ApiVersion::V6,
+            VersionedDepartures::V5(_) => ApiVersion::V5,
+        }
+    }
+}
+
+impl From<VersionedDepartures> for DeparturesResponse {
+    fn from(value: VersionedDepartures) -> Self {
+        match value {
+            VersionedDepartures::V6(response) => response,
+            VersionedDepartures::V5(departures) => DeparturesResponse {
+                departures,
+                realtime_data_updated_at: None,
+            },
+        }
+    }
+}
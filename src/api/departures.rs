@@ -1,16 +1,28 @@
-use crate::api::BvgClient;
-use crate::{InputStop, InputStops};
+use crate::api::cache::ResponseCache;
+use crate::api::compat::VersionedDepartures;
+use crate::api::directions::{DirectionMatcher, DirectionMatchers};
+use crate::api::locations::Coordinate;
+use crate::api::products::{Mode, Product};
+use crate::api::trips::Trip;
+use crate::api::{check_status, BvgClient};
+use crate::board::{Board, StationBoard};
+use crate::request_log;
+use crate::{InputStop, InputStops, NightNetwork};
 use async_trait::async_trait;
-use chrono::{DateTime, Utc};
-use reqwest::Response;
+use bytes::Bytes;
+use chrono::{DateTime, Local, Timelike, Utc};
+use futures::future;
+use futures::stream::{self, StreamExt};
+use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
-use serde_with::serde_as;
-use tracing::{debug, info};
+use serde_with::{serde_as, DefaultOnNull};
+use std::sync::Arc;
+use tracing::{debug, info, warn};
 use url::Url;
 
 /// Query parameters for GET /stops/:id/departures
 ///
-/// Mirrors https://v6.bvg.transport.rest/api.html#stops-id-departures
+/// Mirrors <https://v6.bvg.transport.rest/api.html#stops-id-departures>
 #[serde_as]
 #[derive(Debug, Clone, Serialize, Default)]
 pub struct DeparturesParams {
@@ -63,29 +75,55 @@ pub struct DeparturesParams {
     pub pretty: Option<bool>,
 }
 
+impl DeparturesParams {
+    /// Only asks for departures of `products`. All products if empty.
+    pub fn only_products(&mut self, products: &[Product]) {
+        if products.is_empty() {
+            return;
+        }
+        let wanted = |p: Product| Some(products.contains(&p));
+        self.suburban = wanted(Product::Suburban);
+        self.subway = wanted(Product::Subway);
+        self.tram = wanted(Product::Tram);
+        self.bus = wanted(Product::Bus);
+        self.ferry = wanted(Product::Ferry);
+        self.express = wanted(Product::Express);
+        self.regional = wanted(Product::Regional);
+    }
+}
+
 /// Typed response. The docs show an envelope with `departures` and an optional timestamp.
 /// See example payload in the docs. Fields we don’t strictly need are `Option`.
+#[serde_as]
 #[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
 #[allow(dead_code)]
 pub struct DeparturesResponse {
+    /// Some deployments send `null` instead of an empty list. Arrivals are sent as `arrivals`.
+    #[serde_as(as = "DefaultOnNull")]
+    #[serde(alias = "arrivals")]
     pub departures: Vec<Departure>,
     #[serde(default)]
     pub realtime_data_updated_at: Option<i64>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
 #[allow(dead_code)]
 pub struct Departure {
     pub trip_id: Option<String>,
     pub direction: Option<String>,
+    /// Where an arrival comes from
+    #[serde(default)]
+    pub provenance: Option<String>,
 
     pub line: Option<Line>,
 
     /// realtime departure time (RFC3339 with offset), if available
     #[serde(default)]
     pub when: Option<DateTime<Utc>>,
-    /// scheduled departure time
-    #[serde(default)]
+    /// scheduled departure time (`formerScheduledWhen` before v5)
+    #[serde(default, alias = "formerScheduledWhen")]
     pub planned_when: Option<DateTime<Utc>>,
 
     /// delay in seconds
@@ -94,7 +132,7 @@ pub struct Departure {
 
     #[serde(default)]
     pub platform: Option<String>,
-    #[serde(default)]
+    #[serde(default, alias = "formerScheduledPlatform")]
     pub planned_platform: Option<String>,
 
     #[serde(default)]
@@ -102,6 +140,14 @@ pub struct Departure {
 
     #[serde(default)]
     pub remarks: Option<Vec<Remark>>,
+
+    /// How `when` was predicted, e.g. "prognosed" for realtime data
+    #[serde(default)]
+    pub prognosis_type: Option<String>,
+
+    /// Only present if the trip is cancelled
+    #[serde(default)]
+    pub cancelled: Option<bool>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -114,9 +160,9 @@ pub struct Line {
     #[serde(default)]
     pub name: Option<String>, // e.g. "U6"
     #[serde(default)]
-    pub mode: Option<String>, // e.g. "train" | "bus" ...
+    pub mode: Option<Mode>,
     #[serde(default)]
-    pub product: Option<String>, // e.g. "subway" | "bus"
+    pub product: Option<Product>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -128,6 +174,8 @@ pub struct Stop {
     pub id: Option<String>, // stop id
     #[serde(default)]
     pub name: Option<String>,
+    #[serde(default)]
+    pub location: Option<Coordinate>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -155,14 +203,49 @@ pub enum DeparturesError {
         status: reqwest::StatusCode,
         body: String,
     },
+    #[error("Rate limited by the API")]
+    RateLimited {
+        /// From the `Retry-After` header, if the server sent one
+        retry_after: Option<std::time::Duration>,
+    },
+    #[error(
+        "{}, next try in {}s",
+        if *rate_limited { "Rate limited by the API" } else { "API unavailable" },
+        retry_in.as_secs()
+    )]
+    CircuitOpen {
+        retry_in: std::time::Duration,
+        /// Whether it opened because of 429s
+        rate_limited: bool,
+    },
+    #[error("Timed out after {}ms", after.as_millis())]
+    TimedOut { after: std::time::Duration },
+    #[error("Unexpected response: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Could not read fixture {}: {source}", path.display())]
+    Fixture {
+        path: std::path::PathBuf,
+        source: std::io::Error,
+    },
+    #[error("{0} is not supported by this source")]
+    Unsupported(&'static str),
 }
 
+/// Source of departure boards. Implemented by [`BvgClient`]; implement it to feed the views with
+/// other data, e.g. in tests.
 #[async_trait]
 pub trait DeparturesApi {
-    async fn get_departures(
-        &self,
-        stops: &InputStops,
-    ) -> Result<Vec<(String, DeparturesResponse)>, DeparturesError>;
+    /// Departures of all stops in the config, filtered as configured.
+    async fn get_departures(&self, stops: &InputStops) -> Result<Board, DeparturesError>;
+
+    /// A single trip with all its stopovers. Sources without trips keep the default, which fails.
+    async fn get_trip(&self, _trip_id: &str) -> Result<Trip, DeparturesError> {
+        Err(DeparturesError::Unsupported("Getting a trip"))
+    }
+
+    /// Forgets cached responses, so that the next [`DeparturesApi::get_departures`] asks the API.
+    /// Called when the user asks for a refresh.
+    fn clear_cache(&self) {}
 }
 
 #[async_trait]
@@ -171,80 +254,712 @@ impl DeparturesApi for BvgClient {
     ///
     /// Example equivalent to:
     /// `curl 'https://v6.bvg.transport.rest/stops/900055151/departures?duration=10&linesOfStops=false&remarks=true&language=en'`
-    async fn get_departures(
-        &self,
-        stops: &InputStops,
-    ) -> Result<Vec<(String, DeparturesResponse)>, DeparturesError> {
+    async fn get_departures(&self, stops: &InputStops) -> Result<Board, DeparturesError> {
         info!("Getting departures");
 
-        let mut result = vec![];
+        let fetched_at = Local::now();
+
+        // start in the past so recently departed trips can still be shown
+        let grace = chrono::Duration::minutes(stops.grace_minutes.into());
+        let when = (Utc::now() - grace).to_rfc3339();
+
+        let night_network = stops
+            .night_mode
+            .as_ref()
+            .and_then(|n| n.night_network.as_ref())
+            .filter(|n| n.is_active(fetched_at.hour()));
+
+        let requests: Vec<_> = stops
+            .stops
+            .iter()
+            .map(|s| self.station_departures_in_time(s, &when, stops.grace_minutes, night_network))
+            .collect();
+        // `buffered` keeps the config order, whichever request finishes first
+        let results: Vec<Result<StationBoard, DeparturesError>> = stream::iter(requests)
+            .buffered(self.concurrency)
+            .collect()
+            .await;
+
+        // stops the API could not answer for already show their saved departures, see
+        // `fetch_or_saved`
+        let all_failed = results.iter().all(Result::is_err);
+        let mut stations = vec![];
+        for (result, s) in results.into_iter().zip(&stops.stops) {
+            match result {
+                Ok(station) => stations.push(station),
+                // one failing stop should not hide the others, but if all fail there is nothing to show
+                Err(e) if all_failed => return Err(e),
+                Err(e) => {
+                    warn!("Could not get departures for {}: {}", s.name, e);
+                    stations.push(StationBoard::failed(s, &e));
+                }
+            }
+        }
+
+        if let Some(stats) = self.cache_stats() {
+            debug!(
+                "Response cache: {} hits, {} misses",
+                stats.hits, stats.misses
+            );
+        }
 
-        for s in &stops.stops {
-            debug!("Getting for stop {}", s.name);
+        let mut board = Board {
+            stations,
+            fetched_at,
+        };
+        board.merge_groups(&stops.stops);
+        board.flag_implausible_delays(stops.max_delay_minutes);
+        #[cfg(feature = "geo")]
+        if let Some(cache) = &self.polylines {
+            cache.retain(&board);
+        }
+        if let Some(me) = stops
+            .my_location
+            .as_ref()
+            .filter(|_| stops.sort_by_distance)
+        {
+            board.sort_by_distance(me);
+        }
+        Ok(board)
+    }
+
+    async fn get_trip(&self, trip_id: &str) -> Result<Trip, DeparturesError> {
+        BvgClient::get_trip(self, trip_id).await
+    }
+
+    fn clear_cache(&self) {
+        if let Some(cache) = &self.cache {
+            cache.clear();
+        }
+    }
+}
 
+impl BvgClient {
+    /// [`Self::station_departures`], given up after the `timeout_ms` of the stop for the saved
+    /// departures, like when the API cannot be reached.
+    async fn station_departures_in_time(
+        &self,
+        s: &InputStop,
+        when: &str,
+        grace_minutes: u32,
+        night_network: Option<&NightNetwork>,
+    ) -> Result<StationBoard, DeparturesError> {
+        let departures = self.station_departures(s, when, grace_minutes, night_network, None);
+        let Some(timeout_ms) = s.timeout_ms else {
+            return departures.await;
+        };
+        let after = std::time::Duration::from_millis(timeout_ms);
+        match tokio::time::timeout(after, departures).await {
+            Ok(result) => result,
+            // the request is dropped unfinished, so `fetch` does not log it
+            Err(_) => {
+                debug!(
+                    target: request_log::TARGET,
+                    stop = s.name.as_str(),
+                    status = "timeout",
+                    latency_ms = timeout_ms,
+                    retries = 0u64,
+                    "timed out"
+                );
+                self.station_departures(s, when, grace_minutes, night_network, Some(after))
+                    .await
+            }
+        }
+    }
+
+    /// With `timed_out`, only the saved departures are shown, filtered the same way.
+    async fn station_departures(
+        &self,
+        s: &InputStop,
+        when: &str,
+        grace_minutes: u32,
+        night_network: Option<&NightNetwork>,
+        timed_out: Option<std::time::Duration>,
+    ) -> Result<StationBoard, DeparturesError> {
+        debug!("Getting for stop {}", s.name);
+
+        let mut params = DeparturesParams {
+            when: Some(when.to_string()),
+            duration: Some(s.look_ahead + grace_minutes),
+            lines_of_stops: Some(false),
+            remarks: Some(true),
+            language: Some(self.language.clone()),
+            ..Default::default()
+        };
+        params.only_products(&s.products);
+
+        // fetch, once per direction the API filters by and once for the rest
+        let endpoint = if s.arrivals { "arrivals" } else { "departures" };
+        let ids: Vec<&str> = if s.arrivals {
+            vec![]
+        } else {
+            s.direction_ids().collect()
+        };
+        let mut directions: Vec<Option<&str>> = ids.iter().copied().map(Some).collect();
+        if ids.is_empty() || ids.len() < s.directions.len() {
+            directions.insert(0, None);
+        }
+        let mut boards = future::try_join_all(directions.into_iter().map(|direction| {
             let params = DeparturesParams {
-                duration: Some(s.look_ahead),
-                lines_of_stops: Some(false),
-                remarks: Some(true),
-                language: Some("de".into()),
-                ..Default::default()
+                direction: direction.map(str::to_string),
+                ..params.clone()
             };
+            async move {
+                let mut station = match timed_out {
+                    Some(after) => {
+                        self.saved(&params, s, endpoint, DeparturesError::TimedOut { after })
+                            .await?
+                    }
+                    None => self.fetch_or_saved(&params, s, endpoint).await?,
+                };
+                // the API already filtered by the stop ids
+                if direction.is_none() {
+                    Self::filter(s, &mut station);
+                } else {
+                    Self::filter_excluded(s, &mut station);
+                }
+                Ok::<_, DeparturesError>(station)
+            }
+        }))
+        .await?;
+
+        // a trip can head to several of the directions
+        let mut station = boards.remove(0);
+        if !boards.is_empty() {
+            for other in boards {
+                station.absorb(other);
+            }
+            station.dedupe_trips();
+        }
 
-            // fetch
-            let res = self.fetch(&params, &s).await?;
+        // filter
+        #[cfg(feature = "script")]
+        if let Some(script) = &s.script_filter {
+            Self::filter_script(script, &mut station);
+        }
+        if let Some(network) = night_network {
+            Self::filter_night_network(network, &mut station);
+        }
+        #[cfg(feature = "translate")]
+        if let Some(translator) = &self.translator {
+            translator.translate_station(&mut station).await;
+        }
+        #[cfg(feature = "geo")]
+        if let Some(cache) = &self.polylines {
+            let trip_ids: Vec<Option<String>> =
+                station.rows.iter().map(|r| r.trip_id.clone()).collect();
+            let shapes: Vec<_> = stream::iter(trip_ids)
+                .map(|trip_id| async move {
+                    match trip_id {
+                        Some(trip_id) => self.polyline(cache, &trip_id).await,
+                        None => None,
+                    }
+                })
+                .buffered(self.concurrency)
+                .collect()
+                .await;
+            for (row, shape) in station.rows.iter_mut().zip(shapes) {
+                row.polyline = shape;
+            }
+        }
+        info!(
+            "{}: {} shown, {} filtered ({})",
+            s.name,
+            station.rows.len(),
+            station.filter_stats.total(),
+            station.filter_stats
+        );
+
+        Ok(station)
+    }
+
+    /// Departures or arrivals of a stop, or the ones saved offline if the API cannot be reached.
+    /// Saved ones are marked as stale, and as rate limited if that is why they are shown.
+    async fn fetch_or_saved(
+        &self,
+        params: &DeparturesParams,
+        s: &InputStop,
+        endpoint: &str,
+    ) -> Result<StationBoard, DeparturesError> {
+        match self.fetch_board(params, s, endpoint).await {
+            Ok(response) => Ok(StationBoard::new(s, response)),
+            Err(e) if e.is_offline() => self.saved(params, s, endpoint, e).await,
+            Err(e) => Err(e),
+        }
+    }
 
-            // filter
-            let mut response = res.json::<DeparturesResponse>().await?;
-            Self::filter(&s, &mut response);
+    /// The saved departures or arrivals of a stop, marked as stale, or `e` if there are none.
+    async fn saved(
+        &self,
+        params: &DeparturesParams,
+        s: &InputStop,
+        endpoint: &str,
+        e: DeparturesError,
+    ) -> Result<StationBoard, DeparturesError> {
+        let saved = self.offline.load(&s.id, &saved_as(endpoint, params)).await;
+        let Some((saved_at, response)) = saved else {
+            return Err(e);
+        };
+        warn!("Showing the saved departures of {}: {}", s.name, e);
+        let mut station = StationBoard::new(s, response);
+        station.stale_since = Some(saved_at);
+        station.rate_limited = e.rate_limit();
+        Ok(station)
+    }
 
-            result.push((s.name.clone(), response));
+    /// GET /stops/:id/arrivals, in the shape of departures. `direction` is empty, see `provenance`.
+    ///
+    /// Example equivalent to:
+    /// `curl 'https://v6.bvg.transport.rest/stops/900055151/arrivals?duration=10&remarks=true'`
+    pub async fn get_arrivals(
+        &self,
+        s: &InputStop,
+        params: &DeparturesParams,
+    ) -> Result<DeparturesResponse, DeparturesError> {
+        self.fetch_board(params, s, "arrivals").await
+    }
+
+    /// Departures or arrivals of a stop, from whatever API version answered.
+    async fn fetch_board(
+        &self,
+        params: &DeparturesParams,
+        s: &InputStop,
+        endpoint: &str,
+    ) -> Result<DeparturesResponse, DeparturesError> {
+        if let Some(fixtures) = &self.fixtures {
+            return fixtures.load(&s.id, endpoint).await;
+        }
+
+        let cached = self
+            .cache
+            .as_ref()
+            .map(|cache| (cache, ResponseCache::key(endpoint, &s.id, params)));
+        if let Some((cache, key)) = &cached
+            && let Some(response) = cache.get(key, std::time::Instant::now())
+        {
+            debug!("Using cached {} of stop {}", endpoint, s.name);
+            return Ok(response);
         }
 
-        Ok(result)
+        let body = self.fetch(params, s, endpoint).await?;
+        let versioned = serde_json::from_slice::<VersionedDepartures>(&body)?;
+        debug!(
+            "Got {} response for {} of stop {}",
+            versioned.version(),
+            endpoint,
+            s.name
+        );
+        let response = DeparturesResponse::from(versioned);
+        self.offline
+            .save(&s.id, &saved_as(endpoint, params), &body, &response)
+            .await;
+        if let Some((cache, key)) = cached {
+            cache.insert(key, &body, &response, std::time::Instant::now());
+        }
+        Ok(response)
     }
-}
 
-impl BvgClient {
+    /// Body of the response, retried as configured.
     async fn fetch(
         &self,
         params: &DeparturesParams,
         s: &InputStop,
-    ) -> Result<Response, DeparturesError> {
-        let url = self.departures_url(&s)?;
+        endpoint: &str,
+    ) -> Result<Bytes, DeparturesError> {
+        self.breaker.check()?;
+        let started = std::time::Instant::now();
+        let mut attempt = 1;
+        let result = loop {
+            let result = self.request(params, s, endpoint).await;
+            if let Err(e) = &result
+                && let Some(delay) = self.retry.delay(attempt, e)
+            {
+                warn!(
+                    "{} for {} (attempt {} of {}), retrying in {}ms",
+                    e,
+                    s.name,
+                    attempt,
+                    self.retry.max_attempts,
+                    delay.as_millis()
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+                continue;
+            }
+            break result;
+        };
+        let status = match &result {
+            Ok((status, _)) => status.as_u16().to_string(),
+            Err(DeparturesError::Status { status, .. }) => status.as_u16().to_string(),
+            Err(DeparturesError::RateLimited { .. }) => "429".to_string(),
+            Err(DeparturesError::Http(e)) if e.is_timeout() => "timeout".to_string(),
+            Err(_) => "failed".to_string(),
+        };
+        debug!(
+            target: request_log::TARGET,
+            stop = s.name.as_str(),
+            status = status.as_str(),
+            latency_ms = started.elapsed().as_millis() as u64,
+            retries = u64::from(attempt - 1),
+            "{} {}",
+            endpoint,
+            status
+        );
+        match &result {
+            Err(e) if e.is_upstream_failure() => self.breaker.failed(e),
+            _ => self.breaker.succeeded(),
+        }
+        result.map(|(_, body)| body)
+    }
+
+    async fn request(
+        &self,
+        params: &DeparturesParams,
+        s: &InputStop,
+        endpoint: &str,
+    ) -> Result<(StatusCode, Bytes), DeparturesError> {
+        let url = self.stop_url(s, endpoint)?;
+        // held until the body is read, which takes as long as the rest on slow connections
+        let _permit = self.limit.acquire().await.expect("limit semaphore");
         let res = self.http.get(url).query(&params).send().await?;
 
-        if !res.status().is_success() {
-            let status = res.status();
-            let body = res.text().await.unwrap_or_default();
-            return Err(DeparturesError::Status { status, body });
+        if res.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = res
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse().ok())
+                .map(std::time::Duration::from_secs);
+            return Err(DeparturesError::RateLimited { retry_after });
         }
-        Ok(res)
+        let res = check_status(res).await?;
+        Ok((res.status(), res.bytes().await?))
     }
 
-    fn filter(s: &InputStop, response: &mut DeparturesResponse) {
-        response.departures.retain(|d| {
-            // retain all departures whose direction is contained in user input
-            if s.directions.is_empty() {
+    /// Removes departures that do not match the direction filters of the stop and records how many.
+    pub fn filter(s: &InputStop, station: &mut StationBoard) {
+        let Some(matchers) = Self::direction_matchers(s) else {
+            return;
+        };
+        let before = station.rows.len();
+        station.rows.retain(|d| {
+            // retain all departures whose direction matches user input
+            if matchers.directions.is_empty() {
                 return true;
             }
 
             if let Some(real_direction) = &d.direction {
-                s.directions
-                    .iter()
-                    .any(|input_direction| real_direction.contains(input_direction))
+                matchers.directions.matches(real_direction)
             } else {
                 true
             }
         });
+        station
+            .filter_stats
+            .record("directions", before - station.rows.len());
+        Self::exclude(&matchers.exclude, station);
+    }
+
+    /// Removes departures toward the `exclude_directions` of the stop and records how many.
+    pub fn filter_excluded(s: &InputStop, station: &mut StationBoard) {
+        if s.exclude_directions.is_empty() {
+            return;
+        }
+        if let Some(matchers) = Self::direction_matchers(s) {
+            Self::exclude(&matchers.exclude, station);
+        }
+    }
+
+    fn direction_matchers(s: &InputStop) -> Option<Arc<DirectionMatchers>> {
+        s.direction_matchers()
+            .inspect_err(|e| warn!("Not filtering {} by direction: {}", s.name, e))
+            .ok()
+    }
+
+    fn exclude(matcher: &DirectionMatcher, station: &mut StationBoard) {
+        if matcher.is_empty() {
+            return;
+        }
+        let before = station.rows.len();
+        station.rows.retain(|d| {
+            !d.direction
+                .as_deref()
+                .is_some_and(|dir| matcher.matches(dir))
+        });
+        station
+            .filter_stats
+            .record("exclude_directions", before - station.rows.len());
+    }
+
+    /// Removes departures the script of the stop drops. Departures the script fails on are kept.
+    #[cfg(feature = "script")]
+    pub fn filter_script(script: &crate::script::ScriptFilter, station: &mut StationBoard) {
+        let before = station.rows.len();
+        let mut failed = None;
+        station.rows.retain(|d| {
+            script.keep(d).unwrap_or_else(|e| {
+                failed = Some(e);
+                true
+            })
+        });
+        // once per stop, a broken script fails on every departure
+        if let Some(e) = failed {
+            warn!("{}: {:#}", station.name, e);
+        }
+        station
+            .filter_stats
+            .record("script", before - station.rows.len());
     }
 
-    fn departures_url(&self, s: &InputStop) -> Result<Url, DeparturesError> {
+    /// Keeps only lines of the night network.
+    pub fn filter_night_network(network: &NightNetwork, station: &mut StationBoard) {
+        let before = station.rows.len();
+        station
+            .rows
+            .retain(|d| d.line.as_deref().is_some_and(|l| network.runs(l)));
+        station
+            .filter_stats
+            .record("night network", before - station.rows.len());
+    }
+
+    /// e.g. `stops/:id/departures`
+    fn stop_url(&self, s: &InputStop, endpoint: &str) -> Result<Url, DeparturesError> {
         let mut url = self.base.join("stops/")?;
         url.path_segments_mut()
             .expect("url base")
             .pop_if_empty()
             .push(&s.id)
-            .push("departures");
+            .push(endpoint);
         Ok(url)
     }
 }
+
+/// Name a response is saved under offline, e.g. "departures-900100001" if asked for a direction.
+fn saved_as(endpoint: &str, params: &DeparturesParams) -> String {
+    match &params.direction {
+        Some(direction) => format!("{}-{}", endpoint, direction),
+        None => endpoint.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::view::build_display_lines;
+    use crate::view::summary::{filter_note, station_summary};
+    use proptest::prelude::*;
+    use serde_json::{json, Value};
+
+    fn stop(directions: Vec<String>) -> InputStop {
+        InputStop {
+            directions,
+            ..InputStop::new("900100003".to_string(), "Alexanderplatz".to_string())
+        }
+    }
+
+    /// Any JSON, with a bias towards keys of the departures payload.
+    fn arb_json() -> impl Strategy<Value = Value> {
+        let leaf = prop_oneof![
+            Just(Value::Null),
+            any::<bool>().prop_map(Value::from),
+            any::<i64>().prop_map(Value::from),
+            any::<f64>().prop_map(Value::from),
+            ".{0,12}".prop_map(Value::from),
+        ];
+        let key = prop_oneof![
+            Just("departures".to_string()),
+            Just("when".to_string()),
+            Just("delay".to_string()),
+            Just("line".to_string()),
+            Just("remarks".to_string()),
+            "[a-zA-Z]{1,8}",
+        ];
+        leaf.prop_recursive(4, 64, 8, move |inner| {
+            prop_oneof![
+                prop::collection::vec(inner.clone(), 0..8).prop_map(Value::from),
+                prop::collection::vec((key.clone(), inner), 0..8)
+                    .prop_map(|fields| Value::Object(fields.into_iter().collect())),
+            ]
+        })
+    }
+
+    fn nullable<T: Into<Value> + std::fmt::Debug>(
+        s: impl Strategy<Value = T>,
+    ) -> impl Strategy<Value = Value> {
+        prop_oneof![Just(Value::Null), s.prop_map(Into::into)]
+    }
+
+    /// Any time chrono can represent, or one close to now.
+    fn arb_time() -> impl Strategy<Value = Value> {
+        let secs = prop_oneof![
+            -62_135_596_800i64..253_402_300_799,
+            (-600i64..600).prop_map(|m| Utc::now().timestamp() + m * 60),
+        ];
+        nullable(secs.prop_map(|s| {
+            DateTime::from_timestamp(s, 0)
+                .expect("timestamp in range")
+                .to_rfc3339()
+        }))
+    }
+
+    /// Departures as the API may send them: fields missing, null, empty or out of the usual range.
+    fn arb_departure() -> impl Strategy<Value = Value> {
+        let line = nullable(
+            (
+                nullable(".{0,6}"),
+                nullable("subway|bus|tram|suburban|.{0,8}"),
+            )
+                .prop_map(|(name, product)| json!({"name": name, "product": product})),
+        );
+        let remark = (
+            nullable("warning|hint|.{0,6}"),
+            nullable(".{0,20}"),
+            nullable(".{0,20}"),
+        )
+            .prop_map(|(t, summary, text)| json!({"type": t, "summary": summary, "text": text}));
+        (
+            nullable(".{0,12}"),
+            nullable(".{0,20}"),
+            line,
+            arb_time(),
+            arb_time(),
+            nullable(any::<i64>()),
+            nullable(prop::collection::vec(remark, 0..4)),
+        )
+            .prop_map(
+                |(trip_id, direction, line, when, planned, delay, remarks)| {
+                    json!({
+                        "tripId": trip_id,
+                        "direction": direction,
+                        "line": line,
+                        "when": when,
+                        "plannedWhen": planned,
+                        "delay": delay,
+                        "remarks": remarks,
+                    })
+                },
+            )
+    }
+
+    #[test]
+    fn null_departures_are_empty() {
+        let versioned: VersionedDepartures =
+            serde_json::from_value(json!({"departures": null})).unwrap();
+        assert!(DeparturesResponse::from(versioned).departures.is_empty());
+    }
+
+    #[test]
+    fn arrivals_come_from_their_provenance() {
+        let versioned: VersionedDepartures = serde_json::from_value(json!({"arrivals": [
+            {"tripId": "1", "direction": null, "provenance": "S Spandau"}
+        ]}))
+        .unwrap();
+        let station = StationBoard::new(&stop(vec![]), versioned.into());
+        assert_eq!(station.rows[0].direction.as_deref(), Some("S Spandau"));
+    }
+
+    #[test]
+    fn excluded_directions_are_removed_after_the_included_ones() {
+        let versioned: VersionedDepartures = serde_json::from_value(json!({"departures": [
+            {"tripId": "1", "direction": "S+U Pankow (Berlin)"},
+            {"tripId": "2", "direction": "S+U Wittenau (Berlin)"},
+            {"tripId": "3", "direction": "S+U Hermannstr. (Berlin)"},
+            {"tripId": "4", "direction": "U Pankow Garbátyplatz"},
+        ]}))
+        .unwrap();
+        let s = InputStop {
+            exclude_directions: vec!["Garbatyplatz".to_string()],
+            ..stop(vec!["Pankow".to_string(), "Wittenau".to_string()])
+        };
+        let mut station = StationBoard::new(&s, versioned.into());
+        BvgClient::filter(&s, &mut station);
+        let trips: Vec<_> = station
+            .rows
+            .iter()
+            .filter_map(|r| r.trip_id.as_deref())
+            .collect();
+        assert_eq!(trips, vec!["1", "2"]);
+        assert_eq!(
+            station.filter_stats.to_string(),
+            "directions: 1, exclude_directions: 1"
+        );
+    }
+
+    #[test]
+    fn station_groups_show_each_trip_once() {
+        let when = |m: i64| (Local::now() + chrono::Duration::minutes(m)).to_rfc3339();
+        let platform = |id: &str, departures: Value| {
+            let versioned: VersionedDepartures =
+                serde_json::from_value(json!({ "departures": departures })).unwrap();
+            let stop = InputStop {
+                group: Some("Ostkreuz".to_string()),
+                ..InputStop::new(id.to_string(), id.to_string())
+            };
+            (StationBoard::new(&stop, versioned.into()), stop)
+        };
+        let (upper, upper_stop) = platform(
+            "900120551",
+            json!([
+                {"tripId": "1", "line": {"name": "S5"}, "when": when(3)},
+                {"tripId": "2", "line": {"name": "S41"}, "when": when(6)},
+            ]),
+        );
+        let (lower, lower_stop) = platform(
+            "900120552",
+            json!([
+                {"tripId": "2", "line": {"name": "S41"}, "when": when(7)},
+                {"tripId": "3", "line": {"name": "S3"}, "when": when(4)},
+            ]),
+        );
+        let mut board = Board {
+            stations: vec![upper, lower],
+            fetched_at: Local::now(),
+        };
+        board.merge_groups(&[upper_stop, lower_stop]);
+        assert_eq!(board.stations.len(), 1);
+        assert_eq!(board.stations[0].name, "Ostkreuz");
+        let trips: Vec<_> = board.stations[0]
+            .rows
+            .iter()
+            .filter_map(|r| r.trip_id.as_deref())
+            .collect();
+        assert_eq!(trips, vec!["1", "3", "2"]);
+    }
+
+    proptest! {
+        #[test]
+        fn parsing_arbitrary_json_does_not_panic(value in arb_json()) {
+            let _ = serde_json::from_value::<VersionedDepartures>(value);
+        }
+
+        #[test]
+        fn odd_payloads_pass_the_pipeline(
+            departures in prop::collection::vec(arb_departure(), 0..20),
+            v5 in any::<bool>(),
+            directions in prop::collection::vec(".{0,4}", 0..3),
+            grace_minutes in 0u32..10,
+        ) {
+            let count = departures.len();
+            let payload = if v5 {
+                Value::from(departures)
+            } else {
+                json!({"departures": departures, "realtimeDataUpdatedAt": null})
+            };
+            let versioned: VersionedDepartures = serde_json::from_value(payload).unwrap();
+
+            let s = stop(directions);
+            let mut station = StationBoard::new(&s, DeparturesResponse::from(versioned));
+            BvgClient::filter(&s, &mut station);
+            prop_assert_eq!(station.rows.len() + station.filter_stats.total(), count);
+
+            let _ = filter_note(&station);
+            let board = Board { stations: vec![station], fetched_at: Local::now() };
+            let config: InputStops =
+                serde_yaml::from_str(&format!("grace_minutes: {}\nstops: []", grace_minutes)).unwrap();
+            let lines = build_display_lines(&board, &config);
+            prop_assert_eq!(lines.len(), 1);
+            prop_assert!(lines[0].1.len() <= board.stations[0].rows.len());
+            let _ = station_summary(&lines[0].1);
+        }
+    }
+}
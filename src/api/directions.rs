@@ -0,0 +1,137 @@
+//! Matching the `directions` of a stop against the directions of departures.
+//!
+//! Text is compared case-insensitively, without umlauts and punctuation, so "suedkreuz" matches
+//! "S Südkreuz" and "S U Hermannstrasse" matches "S+U Hermannstr.". Entries starting with `re:` are
+//! regular expressions on the direction as sent by the API.
+
+use crate::{is_stop_id, InputStop};
+use regex::Regex;
+
+/// Prefix of `directions` entries that are regular expressions
+pub const REGEX_PREFIX: &str = "re:";
+
+/// How similar a misspelled direction has to be with `fuzzy_directions`, from 0 to 1
+const FUZZY_THRESHOLD: f64 = 0.8;
+
+/// The `directions` or `exclude_directions` of a stop, prepared for matching. Stop ids are left
+/// out, the API filters by them.
+#[derive(Debug, Default)]
+pub struct DirectionMatcher {
+    texts: Vec<String>,
+    regexes: Vec<Regex>,
+    fuzzy: bool,
+}
+
+impl DirectionMatcher {
+    /// Fails on the first invalid regular expression.
+    pub fn new(directions: &[String], fuzzy: bool) -> Result<Self, regex::Error> {
+        let mut texts = vec![];
+        let mut regexes = vec![];
+        for direction in directions.iter().filter(|d| !is_stop_id(d)) {
+            match direction.strip_prefix(REGEX_PREFIX) {
+                Some(pattern) => regexes.push(Regex::new(pattern.trim())?),
+                None => texts.push(normalize(direction)),
+            }
+        }
+        Ok(Self {
+            texts,
+            regexes,
+            fuzzy,
+        })
+    }
+
+    /// Whether there is nothing to filter by.
+    pub fn is_empty(&self) -> bool {
+        self.texts.is_empty() && self.regexes.is_empty()
+    }
+
+    pub fn matches(&self, direction: &str) -> bool {
+        if self.regexes.iter().any(|r| r.is_match(direction)) {
+            return true;
+        }
+        let direction = normalize(direction);
+        self.texts.iter().any(|text| {
+            direction.contains(text.as_str()) || (self.fuzzy && similar(&direction, text))
+        })
+    }
+}
+
+/// Both direction filters of a stop, built once by [`InputStops::load`](crate::InputStops::load).
+#[derive(Debug, Default)]
+pub struct DirectionMatchers {
+    pub directions: DirectionMatcher,
+    pub exclude: DirectionMatcher,
+}
+
+impl DirectionMatchers {
+    /// Fails on the first invalid regular expression.
+    pub fn new(stop: &InputStop) -> Result<Self, regex::Error> {
+        Ok(Self {
+            directions: DirectionMatcher::new(&stop.directions, stop.fuzzy_directions)?,
+            exclude: DirectionMatcher::new(&stop.exclude_directions, stop.fuzzy_directions)?,
+        })
+    }
+}
+
+/// Lower case, umlauts spelled out, punctuation as single spaces and "strasse" as "str", e.g.
+/// "S+U Hermannstraße (Berlin)" becomes "s u hermannstr berlin".
+pub fn normalize(text: &str) -> String {
+    let mut folded = String::with_capacity(text.len());
+    for c in text.chars().flat_map(char::to_lowercase) {
+        match c {
+            'ä' => folded.push_str("ae"),
+            'ö' => folded.push_str("oe"),
+            'ü' => folded.push_str("ue"),
+            'ß' => folded.push_str("ss"),
+            'à' | 'á' | 'â' => folded.push('a'),
+            'è' | 'é' | 'ê' => folded.push('e'),
+            'ì' | 'í' | 'î' => folded.push('i'),
+            'ò' | 'ó' | 'ô' => folded.push('o'),
+            'ù' | 'ú' | 'û' => folded.push('u'),
+            c if c.is_alphanumeric() => folded.push(c),
+            _ => folded.push(' '),
+        }
+    }
+    folded
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace("strasse", "str")
+}
+
+/// Whether some words of `direction` are spelled almost like `text`, both normalized.
+fn similar(direction: &str, text: &str) -> bool {
+    let words: Vec<&str> = direction.split(' ').collect();
+    let n = text.split(' ').count();
+    words.windows(n.min(words.len())).any(|window| {
+        strsim::normalized_damerau_levenshtein(&window.join(" "), text) >= FUZZY_THRESHOLD
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_spelling_variants() {
+        let new = |directions: &[&str], fuzzy| {
+            let directions: Vec<String> = directions.iter().map(|d| d.to_string()).collect();
+            DirectionMatcher::new(&directions, fuzzy)
+        };
+
+        let matcher = new(&["suedkreuz", "S U Hermannstrasse"], false).unwrap();
+        assert!(matcher.matches("S Südkreuz Bhf (Berlin)"));
+        assert!(matcher.matches("S+U Hermannstr. (Berlin)"));
+        assert!(!matcher.matches("S+U Wittenau (Berlin)"));
+
+        let matcher = new(&["Wittenua"], false).unwrap();
+        assert!(!matcher.matches("S+U Wittenau (Berlin)"));
+        let matcher = new(&["Wittenua"], true).unwrap();
+        assert!(matcher.matches("S+U Wittenau (Berlin)"));
+
+        let matcher = new(&["re:^S\\+U (Pankow|Wittenau)"], false).unwrap();
+        assert!(matcher.matches("S+U Pankow (Berlin)"));
+        assert!(!matcher.matches("U Pankow"));
+        assert!(new(&["re:(unclosed"], false).is_err());
+    }
+}
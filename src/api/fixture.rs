@@ -0,0 +1,78 @@
+//! Canned responses from disk instead of the API, to work on the views offline and to record
+//! demos.
+
+use crate::api::compat::VersionedDepartures;
+use crate::api::departures::{DeparturesError, DeparturesResponse};
+use chrono::{DateTime, Utc};
+use std::path::PathBuf;
+use tracing::debug;
+
+/// Directory with a `<stop id>-departures.json` (or `-arrivals.json`) per stop, in the format of
+/// the API, like the files of the offline store.
+#[derive(Debug)]
+pub(crate) struct Fixtures {
+    dir: PathBuf,
+}
+
+impl Fixtures {
+    pub(crate) fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// If the response has a `realtimeDataUpdatedAt`, all times are moved as if it was fetched
+    /// just now, so the countdowns are the same on every run.
+    pub(crate) async fn load(
+        &self,
+        stop_id: &str,
+        endpoint: &str,
+    ) -> Result<DeparturesResponse, DeparturesError> {
+        let path = self.dir.join(format!("{}-{}.json", stop_id, endpoint));
+        debug!("Loading fixture {}", path.display());
+        let body = tokio::fs::read(&path)
+            .await
+            .map_err(|source| DeparturesError::Fixture { path, source })?;
+        let mut response =
+            DeparturesResponse::from(serde_json::from_slice::<VersionedDepartures>(&body)?);
+        if let Some(recorded_at) = response
+            .realtime_data_updated_at
+            .and_then(|t| DateTime::from_timestamp(t, 0))
+        {
+            let shift = Utc::now() - recorded_at;
+            for d in &mut response.departures {
+                d.when = d.when.map(|w| w + shift);
+                d.planned_when = d.planned_when.map(|w| w + shift);
+            }
+            response.realtime_data_updated_at = Some(Utc::now().timestamp());
+        }
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn times_are_relative_to_the_recording() {
+        let dir = std::env::temp_dir().join(format!("bvg-fixture-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("900100003-departures.json"),
+            r#"{"departures": [{"tripId": "1", "when": "2025-01-01T12:05:00Z"}],
+                "realtimeDataUpdatedAt": 1735732800}"#,
+        )
+        .unwrap();
+        let fixtures = Fixtures::new(dir.clone());
+
+        let response = fixtures.load("900100003", "departures").await.unwrap();
+        let minutes =
+            (response.departures[0].when.unwrap() - Utc::now()).num_seconds() as f64 / 60.0;
+        assert!((minutes - 5.0).abs() < 0.1, "{}", minutes);
+        assert!(matches!(
+            fixtures.load("900100003", "arrivals").await,
+            Err(DeparturesError::Fixture { .. })
+        ));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,191 @@
+use crate::api::departures::{DeparturesError, Line, Stop};
+use crate::api::{check_status, BvgClient};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+/// Query parameters for GET /journeys
+///
+/// Mirrors <https://v6.bvg.transport.rest/api.html#get-journeys>
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct JourneysParams {
+    /// Date & time to depart at, RFC3339. If None, server uses "now".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub departure: Option<String>,
+
+    /// Max number of journeys (default 3)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub results: Option<u32>,
+
+    /// Max number of transfers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transfers: Option<u32>,
+
+    /// Parse & return stopovers on the way?
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stopovers: Option<bool>,
+
+    /// Parse & return hints & warnings?
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remarks: Option<bool>,
+
+    /// Response language ("en" default)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+}
+
+/// A connection from one stop to another, made of one or more legs.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Journey {
+    #[serde(default)]
+    pub legs: Vec<Leg>,
+}
+
+/// Part of a journey: a ride on a single line, or a walk between two stops.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Leg {
+    #[serde(default)]
+    pub trip_id: Option<String>,
+    #[serde(default)]
+    pub origin: Option<Stop>,
+    #[serde(default)]
+    pub destination: Option<Stop>,
+
+    /// realtime departure time, if available
+    #[serde(default)]
+    pub departure: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub planned_departure: Option<DateTime<Utc>>,
+    /// delay in seconds
+    #[serde(default)]
+    pub departure_delay: Option<i64>,
+    #[serde(default)]
+    pub departure_platform: Option<String>,
+
+    /// realtime arrival time, if available
+    #[serde(default)]
+    pub arrival: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub planned_arrival: Option<DateTime<Utc>>,
+    /// delay in seconds
+    #[serde(default)]
+    pub arrival_delay: Option<i64>,
+    #[serde(default)]
+    pub arrival_platform: Option<String>,
+
+    /// None for walking legs
+    #[serde(default)]
+    pub line: Option<Line>,
+    #[serde(default)]
+    pub direction: Option<String>,
+    #[serde(default)]
+    pub walking: Option<bool>,
+    /// Only present if the trip is cancelled
+    #[serde(default)]
+    pub cancelled: Option<bool>,
+}
+
+impl Leg {
+    pub fn is_walking(&self) -> bool {
+        self.walking == Some(true) || self.line.is_none()
+    }
+
+    /// Realtime departure if known, else the planned one
+    pub fn departure_time(&self) -> Option<DateTime<Utc>> {
+        self.departure.or(self.planned_departure)
+    }
+
+    /// Realtime arrival if known, else the planned one
+    pub fn arrival_time(&self) -> Option<DateTime<Utc>> {
+        self.arrival.or(self.planned_arrival)
+    }
+}
+
+impl Journey {
+    /// Number of changes between vehicles. Walks do not count as rides.
+    pub fn transfers(&self) -> usize {
+        self.legs
+            .iter()
+            .filter(|l| !l.is_walking())
+            .count()
+            .saturating_sub(1)
+    }
+
+    pub fn departure_time(&self) -> Option<DateTime<Utc>> {
+        self.legs.first()?.departure_time()
+    }
+
+    pub fn arrival_time(&self) -> Option<DateTime<Utc>> {
+        self.legs.last()?.arrival_time()
+    }
+
+    /// Whether any ride of the journey is cancelled, which usually breaks the connection.
+    pub fn is_cancelled(&self) -> bool {
+        self.legs.iter().any(|l| l.cancelled == Some(true))
+    }
+}
+
+/// v6 wraps the journeys in an object, v5 returns them directly.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum JourneysResponse {
+    V6 { journeys: Vec<Journey> },
+    V5(Vec<Journey>),
+}
+
+#[derive(Debug, Serialize)]
+struct Endpoints<'a> {
+    from: &'a str,
+    to: &'a str,
+}
+
+impl BvgClient {
+    /// GET /journeys between two stops, given by id.
+    ///
+    /// Example equivalent to:
+    /// `curl 'https://v6.bvg.transport.rest/journeys?from=900017104&to=900017101&results=3'`
+    pub async fn get_journeys(
+        &self,
+        from: &str,
+        to: &str,
+        params: &JourneysParams,
+    ) -> Result<Vec<Journey>, DeparturesError> {
+        debug!("Getting journeys from {} to {}", from, to);
+
+        let url = self.base.join("journeys")?;
+        let res = self
+            .http
+            .get(url)
+            .query(&Endpoints { from, to })
+            .query(params)
+            .send()
+            .await?;
+
+        let res = check_status(res).await?;
+
+        Ok(match res.json::<JourneysResponse>().await? {
+            JourneysResponse::V6 { journeys } | JourneysResponse::V5(journeys) => journeys,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn walks_are_not_transfers() {
+        let json = serde_json::json!({"journeys": [{"legs": [
+            {"line": {"name": "U8"}},
+            {"walking": true},
+            {"line": {"name": "U7"}, "cancelled": true},
+        ]}]});
+        let JourneysResponse::V6 { journeys } = serde_json::from_value(json).unwrap() else {
+            panic!("expected the v6 envelope");
+        };
+        assert_eq!(journeys[0].transfers(), 1);
+        assert!(journeys[0].legs[1].is_walking());
+        assert!(journeys[0].is_cancelled());
+    }
+}
@@ -0,0 +1,234 @@
+use crate::api::departures::DeparturesError;
+use crate::api::{check_status, BvgClient};
+use crate::geo::GeoPoint;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+/// Query parameters for GET /locations/nearby
+///
+/// Mirrors <https://v6.bvg.transport.rest/api.html#get-locationsnearby>
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct NearbyParams {
+    /// Max number of results (default 8)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub results: Option<u32>,
+
+    /// Max walking distance in meters
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distance: Option<u32>,
+
+    /// Return stops/stations?
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stops: Option<bool>,
+
+    /// Return points of interest?
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub poi: Option<bool>,
+
+    /// Parse & return lines of each stop/station?
+    #[serde(rename = "linesOfStops", skip_serializing_if = "Option::is_none")]
+    pub lines_of_stops: Option<bool>,
+
+    /// Response language ("en" default)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+}
+
+/// Query parameters for GET /locations
+///
+/// Mirrors <https://v6.bvg.transport.rest/api.html#get-locations>
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct LocationsParams {
+    /// Find more than exact matches? (default true)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fuzzy: Option<bool>,
+
+    /// Max number of results (default 10)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub results: Option<u32>,
+
+    /// Return stops/stations?
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stops: Option<bool>,
+
+    /// Return addresses?
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub addresses: Option<bool>,
+
+    /// Return points of interest?
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub poi: Option<bool>,
+
+    /// Parse & return lines of each stop/station?
+    #[serde(rename = "linesOfStops", skip_serializing_if = "Option::is_none")]
+    pub lines_of_stops: Option<bool>,
+
+    /// Response language ("en" default)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Query<'a> {
+    query: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct Coordinates {
+    latitude: f64,
+    longitude: f64,
+}
+
+/// A stop, station, address or POI as returned by the locations endpoints.
+#[derive(Debug, Clone, Deserialize)]
+#[allow(dead_code)]
+pub struct Location {
+    #[serde(default)]
+    pub r#type: Option<String>, // "stop" | "station" | "location"
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Walking distance in meters, only set by /locations/nearby
+    #[serde(default)]
+    pub distance: Option<u32>,
+    /// Coordinates of stops
+    #[serde(default)]
+    pub location: Option<Coordinate>,
+    /// Coordinates of addresses and POIs, which are locations themselves
+    #[serde(default)]
+    pub latitude: Option<f64>,
+    #[serde(default)]
+    pub longitude: Option<f64>,
+    /// Set for points of interest
+    #[serde(default)]
+    pub poi: Option<bool>,
+    /// Set for addresses
+    #[serde(default)]
+    pub address: Option<String>,
+}
+
+/// What a [`Location`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocationKind {
+    Stop,
+    Address,
+    Poi,
+}
+
+impl Location {
+    /// Stops and stations have their own type, addresses and POIs are both of type "location".
+    pub fn kind(&self) -> LocationKind {
+        match self.r#type.as_deref() {
+            Some("stop") | Some("station") => LocationKind::Stop,
+            _ if self.poi == Some(true) => LocationKind::Poi,
+            _ => LocationKind::Address,
+        }
+    }
+
+    /// Coordinates of any kind of location.
+    pub fn geo_point(&self) -> Option<GeoPoint> {
+        match &self.location {
+            Some(coordinate) => coordinate.to_geo_point(),
+            None => Some(GeoPoint {
+                latitude: self.latitude?,
+                longitude: self.longitude?,
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[allow(dead_code)]
+pub struct Coordinate {
+    #[serde(default)]
+    pub latitude: Option<f64>,
+    #[serde(default)]
+    pub longitude: Option<f64>,
+}
+
+impl Coordinate {
+    pub fn to_geo_point(&self) -> Option<GeoPoint> {
+        Some(GeoPoint {
+            latitude: self.latitude?,
+            longitude: self.longitude?,
+        })
+    }
+}
+
+impl BvgClient {
+    /// GET /locations
+    ///
+    /// Example equivalent to:
+    /// `curl 'https://v6.bvg.transport.rest/locations?query=alexanderplatz&results=3'`
+    pub async fn search_locations(
+        &self,
+        query: &str,
+        params: &LocationsParams,
+    ) -> Result<Vec<Location>, DeparturesError> {
+        debug!("Searching locations for '{}'", query);
+
+        let url = self.base.join("locations")?;
+        let res = self
+            .http
+            .get(url)
+            .query(&Query { query })
+            .query(params)
+            .send()
+            .await?;
+
+        let res = check_status(res).await?;
+
+        Ok(res.json::<Vec<Location>>().await?)
+    }
+
+    /// Stops matching `query`, best match first. Addresses and POIs are left out.
+    pub async fn search_stops(
+        &self,
+        query: &str,
+        results: u32,
+    ) -> Result<Vec<Location>, DeparturesError> {
+        let params = LocationsParams {
+            results: Some(results),
+            stops: Some(true),
+            addresses: Some(false),
+            poi: Some(false),
+            ..Default::default()
+        };
+        Ok(self
+            .search_locations(query, &params)
+            .await?
+            .into_iter()
+            .filter(|l| l.kind() == LocationKind::Stop && l.id.is_some())
+            .collect())
+    }
+
+    /// GET /locations/nearby
+    ///
+    /// Example equivalent to:
+    /// `curl 'https://v6.bvg.transport.rest/locations/nearby?latitude=52.52725&longitude=13.4123&results=3'`
+    pub async fn nearby_stops(
+        &self,
+        latitude: f64,
+        longitude: f64,
+        params: &NearbyParams,
+    ) -> Result<Vec<Location>, DeparturesError> {
+        debug!("Getting stops near {}, {}", latitude, longitude);
+
+        let url = self.base.join("locations/nearby")?;
+        let res = self
+            .http
+            .get(url)
+            .query(&Coordinates {
+                latitude,
+                longitude,
+            })
+            .query(params)
+            .send()
+            .await?;
+
+        let res = check_status(res).await?;
+
+        Ok(res.json::<Vec<Location>>().await?)
+    }
+}
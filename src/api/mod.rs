@@ -1,26 +1,177 @@
+pub mod cache;
+mod circuit;
+pub mod compat;
 pub mod departures;
+pub mod directions;
+mod fixture;
+pub mod journeys;
+pub mod locations;
+pub mod offline;
+pub mod products;
+pub mod retry;
+pub mod trips;
 
+use departures::DeparturesError;
 use reqwest::Url;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+/// Turns an unsuccessful response into [`DeparturesError::Status`], with the body the server sent.
+pub(crate) async fn check_status(
+    res: reqwest::Response,
+) -> Result<reqwest::Response, DeparturesError> {
+    if !res.status().is_success() {
+        let status = res.status();
+        let body = res.text().await.unwrap_or_default();
+        return Err(DeparturesError::Status { status, body });
+    }
+    Ok(res)
+}
+
+/// The public transport.rest instance for BVG
+pub const DEFAULT_BASE_URL: &str = "https://v6.bvg.transport.rest/";
 
 /// Minimal API client. Reuse across calls.
 #[derive(Clone)]
 pub struct BvgClient {
     http: reqwest::Client,
     base: Url,
+    language: String,
+    /// Max number of requests in flight
+    concurrency: usize,
+    /// A permit per request in flight, shared by all clones like the breaker
+    limit: Arc<Semaphore>,
+    /// Shared by all clones, so every view backs off together
+    breaker: Arc<circuit::CircuitBreaker>,
+    retry: retry::RetryPolicy,
+    /// Shared by all clones, like the breaker
+    cache: Option<Arc<cache::ResponseCache>>,
+    /// Last departures of each stop, shown while the API cannot be reached
+    offline: Arc<offline::OfflineStore>,
+    fixtures: Option<Arc<fixture::Fixtures>>,
+    #[cfg(feature = "translate")]
+    translator: Option<Arc<crate::translate::RemarkTranslator>>,
+    #[cfg(feature = "geo")]
+    polylines: Option<Arc<trips::PolylineCache>>,
 }
 
 impl Default for BvgClient {
     fn default() -> Self {
-        Self::new(Url::parse("https://v6.bvg.transport.rest/").unwrap())
+        Self::new(Url::parse(DEFAULT_BASE_URL).unwrap())
     }
 }
 
 impl BvgClient {
+    /// Client for the API at `base`, e.g. a self-hosted instance. [`Default`] uses the public one.
     pub fn new(base: Url) -> Self {
         let http = reqwest::Client::builder()
             .user_agent(concat!("bvg-api/", env!("CARGO_PKG_VERSION")))
             .build()
             .expect("reqwest client");
-        Self { http, base }
+        Self {
+            http,
+            base,
+            language: "de".into(),
+            concurrency: 4,
+            limit: Arc::new(Semaphore::new(4)),
+            breaker: Arc::new(circuit::CircuitBreaker::new(5, Duration::from_secs(60))),
+            retry: retry::RetryPolicy::default(),
+            cache: None,
+            offline: Arc::default(),
+            fixtures: None,
+            #[cfg(feature = "translate")]
+            translator: None,
+            #[cfg(feature = "geo")]
+            polylines: None,
+        }
+    }
+
+    /// The API this client talks to.
+    pub fn base_url(&self) -> &Url {
+        &self.base
+    }
+
+    /// Language of the API responses, e.g. remark texts. Defaults to "de".
+    pub fn with_language(mut self, language: impl Into<String>) -> Self {
+        self.language = language.into();
+        self
+    }
+
+    /// Translate remark texts before they are put on the board.
+    #[cfg(feature = "translate")]
+    pub fn with_translator(mut self, translator: crate::translate::RemarkTranslator) -> Self {
+        self.translator = Some(Arc::new(translator));
+        self
+    }
+
+    /// Add the route shape of each departure to the board.
+    #[cfg(feature = "geo")]
+    pub fn with_polylines(mut self) -> Self {
+        self.polylines = Some(Default::default());
+        self
+    }
+
+    /// Number of requests for departures and trips sent at the same time, however many stops,
+    /// directions and routes they are for. Defaults to 4.
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self.limit = Arc::new(Semaphore::new(self.concurrency));
+        self
+    }
+
+    /// Retry failed requests for departures. Defaults to [`retry::RetryPolicy::default`].
+    pub fn with_retry(mut self, retry: retry::RetryPolicy) -> Self {
+        self.retry = retry;
+        self
     }
-}
\ No newline at end of file
+
+    /// Reuse the departures of a stop for `ttl` instead of requesting them again.
+    pub fn with_cache(mut self, ttl: Duration) -> Self {
+        self.cache = Some(Arc::new(cache::ResponseCache::new(ttl)));
+        self
+    }
+
+    /// Like [`BvgClient::with_cache`], but the departures are also kept in `file` between runs, see
+    /// [`BvgClient::save_cache`].
+    pub fn with_persistent_cache(mut self, ttl: Duration, file: PathBuf) -> Self {
+        self.cache = Some(Arc::new(cache::ResponseCache::new(ttl).with_file(file)));
+        self
+    }
+
+    /// Writes the cache to its file, if enabled with [`BvgClient::with_persistent_cache`], e.g. on
+    /// exit.
+    pub async fn save_cache(&self) {
+        if let Some(cache) = &self.cache {
+            cache.save().await;
+        }
+    }
+
+    /// Hits and misses of the cache, if enabled with [`BvgClient::with_cache`].
+    pub fn cache_stats(&self) -> Option<cache::CacheStats> {
+        self.cache.as_ref().map(|c| c.stats())
+    }
+
+    /// Also save the last departures of each stop in `dir`, e.g. [`offline::default_dir`], so they
+    /// are shown while the API cannot be reached even after a restart. Without, they are only kept
+    /// while the client lives.
+    pub fn with_offline_store(mut self, dir: PathBuf) -> Self {
+        self.offline = Arc::new(offline::OfflineStore::new(dir));
+        self
+    }
+
+    /// Read departures from `<stop id>-departures.json` files in `dir` instead of the API, e.g. to
+    /// work on the views offline. Trips and searches still use the API.
+    pub fn with_fixtures(mut self, dir: PathBuf) -> Self {
+        self.fixtures = Some(Arc::new(fixture::Fixtures::new(dir)));
+        self
+    }
+
+    /// Stop requesting departures for `cool_down` after `failures` failed requests in a row and show
+    /// the last departures of each stop instead. Defaults to 5 failures and 60s.
+    pub fn with_circuit_breaker(mut self, failures: u32, cool_down: Duration) -> Self {
+        self.breaker = Arc::new(circuit::CircuitBreaker::new(failures, cool_down));
+        self
+    }
+}
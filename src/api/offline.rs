@@ -0,0 +1,137 @@
+//! Last successful response of each stop, shown while the network or the API is down. Kept in
+//! memory, and optionally on disk so a board can still be shown after a restart.
+
+use crate::api::compat::VersionedDepartures;
+use crate::api::departures::{DeparturesError, DeparturesResponse};
+use chrono::{DateTime, Local};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::{debug, warn};
+
+/// [`Default`] only keeps the responses in memory.
+#[derive(Debug, Default)]
+pub(crate) struct OfflineStore {
+    /// Where responses are saved across restarts
+    dir: Option<PathBuf>,
+    /// Last response of each stop and endpoint, with when it was fetched
+    last: Mutex<HashMap<String, (DateTime<Local>, DeparturesResponse)>>,
+}
+
+impl OfflineStore {
+    pub(crate) fn new(dir: PathBuf) -> Self {
+        Self {
+            dir: Some(dir),
+            last: Mutex::default(),
+        }
+    }
+
+    fn key(stop_id: &str, endpoint: &str) -> String {
+        format!("{}-{}", stop_id, endpoint)
+    }
+
+    /// Keeps the response, on disk as sent by the API. Failures to write are only logged.
+    pub(crate) async fn save(
+        &self,
+        stop_id: &str,
+        endpoint: &str,
+        body: &[u8],
+        response: &DeparturesResponse,
+    ) {
+        let key = Self::key(stop_id, endpoint);
+        self.last
+            .lock()
+            .expect("offline lock")
+            .insert(key.clone(), (Local::now(), response.clone()));
+        let Some(dir) = &self.dir else {
+            return;
+        };
+        let path = dir.join(format!("{}.json", key));
+        let written = async {
+            tokio::fs::create_dir_all(dir).await?;
+            // via a temporary file, so a crash does not leave half a response behind
+            let tmp = path.with_extension("json.tmp");
+            tokio::fs::write(&tmp, body).await?;
+            tokio::fs::rename(&tmp, &path).await
+        };
+        if let Err(e) = written.await {
+            warn!("Could not save departures to {}: {}", path.display(), e);
+        }
+    }
+
+    /// The last saved response and when it was fetched, from disk if there is none in memory yet.
+    pub(crate) async fn load(
+        &self,
+        stop_id: &str,
+        endpoint: &str,
+    ) -> Option<(DateTime<Local>, DeparturesResponse)> {
+        let key = Self::key(stop_id, endpoint);
+        if let Some(last) = self.last.lock().expect("offline lock").get(&key) {
+            return Some(last.clone());
+        }
+        let path = self.dir.as_ref()?.join(format!("{}.json", key));
+        let body = tokio::fs::read(&path).await.ok()?;
+        let saved_at = tokio::fs::metadata(&path).await.ok()?.modified().ok()?;
+        match serde_json::from_slice::<VersionedDepartures>(&body) {
+            Ok(versioned) => {
+                debug!("Loaded departures from {}", path.display());
+                Some((saved_at.into(), versioned.into()))
+            }
+            Err(e) => {
+                warn!("Ignoring unreadable {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+}
+
+/// `$XDG_CACHE_HOME/bvg-departures`, or `~/.cache/bvg-departures`.
+pub fn default_dir() -> Option<PathBuf> {
+    let cache = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))?;
+    Some(cache.join("bvg-departures"))
+}
+
+impl DeparturesError {
+    /// Whether the saved departures should be shown instead, i.e. the API could not be reached.
+    /// Rate limits are included; the station then reports them, see
+    /// [`StationBoard::rate_limited`](crate::board::StationBoard::rate_limited).
+    pub(crate) fn is_offline(&self) -> bool {
+        self.is_upstream_failure() || matches!(self, DeparturesError::CircuitOpen { .. })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn saved_responses_are_loaded() {
+        let dir = std::env::temp_dir().join(format!("bvg-offline-{}", std::process::id()));
+        let store = OfflineStore::new(dir.clone());
+        assert!(store.load("900100003", "departures").await.is_none());
+
+        let body = br#"{"departures": [{"tripId": "1|2", "direction": "Pankow"}]}"#;
+        let response: DeparturesResponse = serde_json::from_slice::<VersionedDepartures>(body)
+            .unwrap()
+            .into();
+        store.save("900100003", "departures", body, &response).await;
+        let (_, loaded) = store.load("900100003", "departures").await.unwrap();
+        assert_eq!(loaded.departures[0].trip_id.as_deref(), Some("1|2"));
+        assert!(store.load("900100003", "arrivals").await.is_none());
+
+        // after a restart, only the file is left
+        let restarted = OfflineStore::new(dir.clone());
+        assert!(restarted.load("900100003", "departures").await.is_some());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let in_memory = OfflineStore::default();
+        in_memory
+            .save("900100003", "departures", body, &response)
+            .await;
+        assert!(in_memory.load("900100003", "departures").await.is_some());
+        assert!(!dir.exists());
+    }
+}
@@ -0,0 +1,117 @@
+//! Products and modes of transport as the API names them.
+//!
+//! New values show up as `Unknown` instead of failing the whole response.
+
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fmt;
+
+/// Enum of the names the API uses, plus `Unknown` for anything else.
+macro_rules! api_names {
+    ($(#[$meta:meta])* $name:ident { $($(#[$variant_meta:meta])* $variant:ident = $text:literal,)* }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+        #[serde(from = "String", into = "String")]
+        pub enum $name {
+            $($(#[$variant_meta])* $variant,)*
+            /// Not known to this version
+            Unknown(String),
+        }
+
+        impl $name {
+            /// All known values
+            pub const KNOWN: &[$name] = &[$($name::$variant),*];
+
+            pub fn as_str(&self) -> &str {
+                match self {
+                    $($name::$variant => $text,)*
+                    $name::Unknown(text) => text,
+                }
+            }
+        }
+
+        impl From<String> for $name {
+            fn from(text: String) -> Self {
+                match text.as_str() {
+                    $($text => $name::$variant,)*
+                    _ => $name::Unknown(text),
+                }
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(value: $name) -> Self {
+                value.as_str().to_string()
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+
+        /// Only the known values are valid in the config.
+        impl JsonSchema for $name {
+            fn schema_name() -> Cow<'static, str> {
+                stringify!($name).into()
+            }
+
+            fn json_schema(_: &mut SchemaGenerator) -> Schema {
+                let known: Vec<&str> = Self::KNOWN.iter().map(Self::as_str).collect();
+                json_schema!({ "type": "string", "enum": known })
+            }
+        }
+    };
+}
+
+api_names! {
+    /// Kind of service, as used by the product filters of the API.
+    Product {
+        /// S-Bahn
+        Suburban = "suburban",
+        /// U-Bahn
+        Subway = "subway",
+        Tram = "tram",
+        Bus = "bus",
+        Ferry = "ferry",
+        /// Long-distance trains, e.g. ICE
+        Express = "express",
+        /// Regional trains, e.g. RE
+        Regional = "regional",
+    }
+}
+
+api_names! {
+    /// Kind of vehicle, coarser than [`Product`]: S-Bahn, U-Bahn and regional trains are all trains.
+    Mode {
+        Train = "train",
+        Bus = "bus",
+        Watercraft = "watercraft",
+        Taxi = "taxi",
+        Gondola = "gondola",
+        Aircraft = "aircraft",
+        Car = "car",
+        Bicycle = "bicycle",
+        Walking = "walking",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_names_are_kept() {
+        let products: Vec<Product> = serde_json::from_str(r#"["subway", "hovercraft"]"#).unwrap();
+        assert_eq!(
+            products,
+            [Product::Subway, Product::Unknown("hovercraft".to_string())]
+        );
+        assert_eq!(
+            serde_json::to_string(&products).unwrap(),
+            r#"["subway","hovercraft"]"#
+        );
+    }
+}
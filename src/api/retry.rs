@@ -0,0 +1,78 @@
+//! Retries of failed requests, for the occasional 502/503 of the API under load.
+
+use crate::api::departures::DeparturesError;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// When and how often a failed request is sent again. Only failures of the API are retried, see
+/// [`DeparturesError`], not e.g. unknown stop ids.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Including the first one. 1 disables retries.
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for each further one
+    pub base_delay: Duration,
+    /// Random share of the delay, e.g. 0.5 for ±50%, so clients do not retry in lockstep
+    pub jitter: f64,
+    /// Longest `Retry-After` waited for. Longer ones are left to the views, which slow down.
+    pub max_retry_after: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            jitter: 0.5,
+            max_retry_after: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// How long to wait before sending a request again that failed with `error` in `attempt`
+    /// (from 1). None to give up.
+    pub(crate) fn delay(&self, attempt: u32, error: &DeparturesError) -> Option<Duration> {
+        if attempt >= self.max_attempts || !error.is_upstream_failure() {
+            return None;
+        }
+        if let DeparturesError::RateLimited {
+            retry_after: Some(retry_after),
+        } = error
+        {
+            return (*retry_after <= self.max_retry_after).then_some(*retry_after);
+        }
+        let backoff = self.base_delay.saturating_mul(1 << (attempt - 1).min(16));
+        // uniform in [-1, 1)
+        let random =
+            RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64 * 2.0 - 1.0;
+        Some(backoff.mul_f64((1.0 + self.jitter.clamp(0.0, 1.0) * random).max(0.0)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backs_off_and_gives_up() {
+        let policy = RetryPolicy::default();
+        let unavailable = DeparturesError::CircuitOpen {
+            retry_in: Duration::ZERO,
+            rate_limited: false,
+        };
+        assert_eq!(policy.delay(1, &unavailable), None);
+
+        let limited = |secs| DeparturesError::RateLimited {
+            retry_after: Some(Duration::from_secs(secs)),
+        };
+        assert_eq!(policy.delay(1, &limited(2)), Some(Duration::from_secs(2)));
+        assert_eq!(policy.delay(1, &limited(60)), None);
+        assert_eq!(policy.delay(3, &limited(2)), None);
+
+        let limited = DeparturesError::RateLimited { retry_after: None };
+        let second = policy.delay(2, &limited).unwrap();
+        assert!(second >= Duration::from_millis(500) && second <= Duration::from_millis(1500));
+    }
+}
@@ -0,0 +1,188 @@
+use crate::api::departures::{DeparturesError, Line, Stop};
+use crate::api::{check_status, BvgClient};
+#[cfg(feature = "geo")]
+use crate::board::Board;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+#[cfg(feature = "geo")]
+use std::collections::{HashMap, HashSet};
+#[cfg(feature = "geo")]
+use std::sync::Mutex;
+use tracing::debug;
+#[cfg(feature = "geo")]
+use tracing::warn;
+
+/// Query parameters for GET /trips/:id
+///
+/// Mirrors <https://v6.bvg.transport.rest/api.html#get-tripsid>
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct TripParams {
+    /// Fetch & parse a shape for the trip?
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub polyline: Option<bool>,
+
+    /// Fetch & parse stopovers on the way?
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stopovers: Option<bool>,
+
+    /// Parse & return hints & warnings?
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remarks: Option<bool>,
+
+    /// Response language ("en" default)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Trip {
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub direction: Option<String>,
+    #[serde(default)]
+    pub line: Option<Line>,
+    /// GeoJSON FeatureCollection of the route, if asked for and available
+    #[serde(default)]
+    pub polyline: Option<Value>,
+    /// All stops of the trip, if asked for
+    #[serde(default)]
+    pub stopovers: Vec<Stopover>,
+}
+
+/// A stop on the way of a trip. The first one has no arrival, the last one no departure.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Stopover {
+    #[serde(default)]
+    pub stop: Option<Stop>,
+    /// realtime arrival time, if available
+    #[serde(default)]
+    pub arrival: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub planned_arrival: Option<DateTime<Utc>>,
+    /// delay in seconds
+    #[serde(default)]
+    pub arrival_delay: Option<i64>,
+    /// realtime departure time, if available
+    #[serde(default)]
+    pub departure: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub planned_departure: Option<DateTime<Utc>>,
+    /// delay in seconds
+    #[serde(default)]
+    pub departure_delay: Option<i64>,
+    #[serde(default)]
+    pub departure_platform: Option<String>,
+    /// Only present if the trip does not stop here
+    #[serde(default)]
+    pub cancelled: Option<bool>,
+}
+
+/// v6 wraps the trip in an object, v5 returns it directly.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum TripResponse {
+    V6 { trip: Trip },
+    V5(Trip),
+}
+
+/// Route shapes by trip id. A trip keeps its shape, so each one is only fetched once.
+#[cfg(feature = "geo")]
+#[derive(Debug, Default)]
+pub(crate) struct PolylineCache {
+    /// None if the API has no shape for the trip
+    shapes: Mutex<HashMap<String, Option<Value>>>,
+}
+
+#[cfg(feature = "geo")]
+impl PolylineCache {
+    fn get(&self, trip_id: &str) -> Option<Option<Value>> {
+        self.shapes
+            .lock()
+            .expect("cache lock")
+            .get(trip_id)
+            .cloned()
+    }
+
+    fn insert(&self, trip_id: String, shape: Option<Value>) {
+        self.shapes
+            .lock()
+            .expect("cache lock")
+            .insert(trip_id, shape);
+    }
+
+    /// Forgets the shapes of trips that are no longer on the board.
+    pub(crate) fn retain(&self, board: &Board) {
+        let trips: HashSet<&str> = board
+            .stations
+            .iter()
+            .flat_map(|s| &s.rows)
+            .filter_map(|r| r.trip_id.as_deref())
+            .collect();
+        self.shapes
+            .lock()
+            .expect("cache lock")
+            .retain(|id, _| trips.contains(id.as_str()));
+    }
+}
+
+impl BvgClient {
+    /// GET /trips/:id
+    ///
+    /// Example equivalent to:
+    /// `curl 'https://v6.bvg.transport.rest/trips/1|12345|0|86|1012025?polyline=true&stopovers=false'`
+    pub async fn trip(&self, trip_id: &str, params: &TripParams) -> Result<Trip, DeparturesError> {
+        debug!("Getting trip {}", trip_id);
+
+        let mut url = self.base.join("trips/")?;
+        url.path_segments_mut()
+            .expect("url base")
+            .pop_if_empty()
+            .push(trip_id);
+        let _permit = self.limit.acquire().await.expect("limit semaphore");
+        let res = self.http.get(url).query(params).send().await?;
+
+        let res = check_status(res).await?;
+
+        Ok(match res.json::<TripResponse>().await? {
+            TripResponse::V6 { trip } | TripResponse::V5(trip) => trip,
+        })
+    }
+
+    /// GET /trips/:id with all stopovers, e.g. to check when a trip reaches your destination.
+    pub async fn get_trip(&self, trip_id: &str) -> Result<Trip, DeparturesError> {
+        let params = TripParams {
+            polyline: Some(false),
+            stopovers: Some(true),
+            remarks: Some(false),
+            language: Some(self.language.clone()),
+        };
+        self.trip(trip_id, &params).await
+    }
+
+    /// Route shape of a trip as GeoJSON, cached. Failures are logged and not retried.
+    #[cfg(feature = "geo")]
+    pub(crate) async fn polyline(&self, cache: &PolylineCache, trip_id: &str) -> Option<Value> {
+        if let Some(shape) = cache.get(trip_id) {
+            return shape;
+        }
+
+        let params = TripParams {
+            polyline: Some(true),
+            stopovers: Some(false),
+            remarks: Some(false),
+            language: Some(self.language.clone()),
+        };
+        let shape = match self.trip(trip_id, &params).await {
+            Ok(trip) => trip.polyline,
+            Err(e) => {
+                warn!("Could not get the route of trip {}: {}", trip_id, e);
+                None
+            }
+        };
+        cache.insert(trip_id.to_string(), shape.clone());
+        shape
+    }
+}
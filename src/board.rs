@@ -0,0 +1,334 @@
+//! Departure board shared by the API client and all views.
+//!
+//! The API client turns the responses of all configured stops into a [`Board`]. Views only
+//! depend on this model, not on the shape of the API responses.
+
+use crate::api::departures::{Departure, DeparturesError, DeparturesResponse, Remark};
+use crate::api::products::Product;
+use crate::geo::GeoPoint;
+use crate::InputStop;
+use chrono::{DateTime, Local, Utc};
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::time::Duration;
+
+/// Departures of all configured stops, fetched at the same time.
+#[derive(Debug, Clone, Serialize)]
+pub struct Board {
+    /// In the order of the stops in the config, or nearest first with `sort_by_distance`
+    pub stations: Vec<StationBoard>,
+    pub fetched_at: DateTime<Local>,
+}
+
+/// Departures of a single stop, including how the stop is styled in the config.
+#[derive(Debug, Clone, Serialize)]
+pub struct StationBoard {
+    pub stop_id: String,
+    pub name: String,
+    pub icon: Option<String>,
+    pub color: Option<String>,
+    pub location: Option<GeoPoint>,
+    /// Whether `rows` are arrivals instead of departures
+    pub arrivals: bool,
+    pub rows: Vec<BoardRow>,
+    /// Unix timestamp of the realtime data, if the API reports it
+    pub realtime_data_updated_at: Option<i64>,
+    pub filter_stats: FilterStats,
+    /// Why the departures of this stop could not be fetched. The other stops are still shown.
+    pub error: Option<String>,
+    /// Set if the API could not be reached and `rows` are the last departures fetched, at this time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stale_since: Option<DateTime<Local>>,
+    /// Set if the API rate limited the requests of this stop, with the `Retry-After` it sent
+    #[serde(skip)]
+    pub rate_limited: Option<Option<Duration>>,
+}
+
+/// A single departure.
+#[derive(Debug, Clone, Serialize)]
+pub struct BoardRow {
+    /// Stop in the config the departure was fetched for, one of several in the station of a
+    /// `group`
+    #[serde(skip)]
+    pub stop_id: String,
+    pub trip_id: Option<String>,
+    /// Line id, e.g. "u8"
+    pub line_id: Option<String>,
+    /// Line name, e.g. "U8"
+    pub line: Option<String>,
+    pub product: Option<Product>,
+    pub direction: Option<String>,
+    /// Realtime departure time
+    pub when: Option<DateTime<Utc>>,
+    pub planned_when: Option<DateTime<Utc>>,
+    /// Delay in seconds
+    pub delay: Option<i64>,
+    /// e.g. "prognosed" for realtime data
+    pub prognosis_type: Option<String>,
+    pub platform: Option<String>,
+    pub planned_platform: Option<String>,
+    pub remarks: Vec<BoardRemark>,
+    pub cancelled: bool,
+    /// Dropped from the realtime data before leaving without being cancelled, see
+    /// [`GhostWatcher`](crate::watch::GhostWatcher)
+    pub ghost: bool,
+    /// `delay` is beyond `max_delay_minutes`, likely a glitch in the data. It is kept as reported.
+    pub implausible_delay: bool,
+    /// Route of the trip as GeoJSON, if asked for
+    #[cfg(feature = "geo")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub polyline: Option<serde_json::Value>,
+}
+
+/// Hint or warning attached to a departure.
+#[derive(Debug, Clone, Serialize)]
+pub struct BoardRemark {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// e.g. "hint" or "warning"
+    pub kind: Option<String>,
+    /// Summary if available, else the full text
+    pub text: String,
+}
+
+/// How many departures each filter removed for a stop.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FilterStats {
+    /// Filter name (e.g. "directions") and number of removed departures, in the order the filters ran.
+    pub removed: Vec<(&'static str, usize)>,
+}
+
+impl Board {
+    /// Flags delays of more than `max_minutes` either way.
+    pub fn flag_implausible_delays(&mut self, max_minutes: u32) {
+        let max = i64::from(max_minutes) * 60;
+        for row in self.stations.iter_mut().flat_map(|s| &mut s.rows) {
+            row.implausible_delay = row.delay.is_some_and(|d| d.abs() > max);
+        }
+    }
+
+    /// Merges the stations of stops sharing a `group` into one, in place of the first of them.
+    /// Expects the stations in the order of `stops`.
+    pub fn merge_groups(&mut self, stops: &[InputStop]) {
+        if stops.iter().all(|s| s.group.is_none()) {
+            return;
+        }
+        let mut stations: Vec<StationBoard> = vec![];
+        let mut groups: HashMap<&str, usize> = HashMap::new();
+        for (mut station, stop) in self.stations.drain(..).zip(stops) {
+            let Some(group) = stop.group.as_deref() else {
+                stations.push(station);
+                continue;
+            };
+            match groups.get(group) {
+                Some(&i) => stations[i].absorb(station),
+                None => {
+                    groups.insert(group, stations.len());
+                    station.name = group.to_string();
+                    stations.push(station);
+                }
+            }
+        }
+        for &i in groups.values() {
+            stations[i].dedupe_trips();
+        }
+        self.stations = stations;
+    }
+
+    /// "OFFLINE — data from 14:05" if some stations show saved departures, with the oldest time.
+    pub fn offline_banner(&self) -> Option<String> {
+        let since = self.stations.iter().filter_map(|s| s.stale_since).min()?;
+        Some(format!("OFFLINE — data from {}", since.format("%H:%M")))
+    }
+
+    /// Set if the API rate limited some of the stations, with the longest `Retry-After` sent.
+    pub fn rate_limited(&self) -> Option<Option<Duration>> {
+        self.stations.iter().map(|s| s.rate_limited).max().flatten()
+    }
+
+    /// Puts the stations nearest to `from` first. Stations without coordinates go last.
+    pub fn sort_by_distance(&mut self, from: &GeoPoint) {
+        self.stations.sort_by(|a, b| {
+            let distance = |s: &StationBoard| s.location.map(|l| from.distance_to(&l));
+            match (distance(a), distance(b)) {
+                (Some(a), Some(b)) => a.total_cmp(&b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }
+        });
+    }
+}
+
+impl StationBoard {
+    pub fn new(stop: &InputStop, response: DeparturesResponse) -> Self {
+        // every departure of a stop reports the stop, including its coordinates
+        let location = stop.location.or_else(|| {
+            response
+                .departures
+                .iter()
+                .find_map(|d| d.stop.as_ref()?.location.as_ref()?.to_geo_point())
+        });
+        Self {
+            stop_id: stop.id.clone(),
+            name: stop.name.clone(),
+            icon: stop.icon.clone(),
+            color: stop.color.clone(),
+            location,
+            arrivals: stop.arrivals,
+            rows: response
+                .departures
+                .into_iter()
+                .map(|d| BoardRow::new(stop, d))
+                .collect(),
+            realtime_data_updated_at: response.realtime_data_updated_at,
+            filter_stats: FilterStats::default(),
+            error: None,
+            stale_since: None,
+            rate_limited: None,
+        }
+    }
+
+    /// Placeholder for a stop whose departures could not be fetched.
+    pub fn failed(stop: &InputStop, error: &DeparturesError) -> Self {
+        Self {
+            stop_id: stop.id.clone(),
+            name: stop.name.clone(),
+            icon: stop.icon.clone(),
+            color: stop.color.clone(),
+            location: stop.location,
+            arrivals: stop.arrivals,
+            rows: vec![],
+            realtime_data_updated_at: None,
+            filter_stats: FilterStats::default(),
+            error: Some(error.to_string()),
+            stale_since: None,
+            rate_limited: error.rate_limit(),
+        }
+    }
+
+    /// Adds the departures of another stop of the same group, or of another request for the same
+    /// stop. Only shows an error if none of them could be fetched.
+    pub(crate) fn absorb(&mut self, other: StationBoard) {
+        if other.error.is_none() {
+            self.error = None;
+        }
+        self.location = self.location.or(other.location);
+        self.rows.extend(other.rows);
+        self.realtime_data_updated_at = self
+            .realtime_data_updated_at
+            .into_iter()
+            .chain(other.realtime_data_updated_at)
+            .min();
+        self.filter_stats.add(&other.filter_stats);
+        self.stale_since = self.stale_since.into_iter().chain(other.stale_since).min();
+        // the longest `Retry-After`
+        self.rate_limited = self.rate_limited.max(other.rate_limited);
+    }
+
+    /// Keeps the earliest departure of each trip, e.g. a train seen at two platforms of a station.
+    pub(crate) fn dedupe_trips(&mut self) {
+        self.rows.sort_by_key(|r| r.when.or(r.planned_when));
+        let mut seen = HashSet::new();
+        self.rows.retain(|r| match &r.trip_id {
+            Some(id) => seen.insert(id.clone()),
+            None => true,
+        });
+    }
+}
+
+impl BoardRow {
+    /// A departure fetched for `stop`.
+    pub fn new(stop: &InputStop, d: Departure) -> Self {
+        let (line_id, line, product) = match d.line {
+            Some(line) => (line.id, line.name, line.product),
+            None => (None, None, None),
+        };
+        Self {
+            stop_id: stop.id.clone(),
+            trip_id: d.trip_id,
+            line_id,
+            line,
+            product,
+            direction: d.direction.or(d.provenance),
+            when: d.when,
+            planned_when: d.planned_when,
+            delay: d.delay,
+            prognosis_type: d.prognosis_type,
+            platform: d.platform,
+            planned_platform: d.planned_platform,
+            remarks: d
+                .remarks
+                .into_iter()
+                .flatten()
+                .filter_map(BoardRemark::from_remark)
+                .collect(),
+            cancelled: d.cancelled.unwrap_or_default(),
+            ghost: false,
+            implausible_delay: false,
+            #[cfg(feature = "geo")]
+            polyline: None,
+        }
+    }
+
+    /// The platform the departure was moved to, if it differs from the timetable.
+    pub fn changed_platform(&self) -> Option<&str> {
+        match (&self.platform, &self.planned_platform) {
+            (Some(platform), Some(planned)) if platform != planned => Some(platform),
+            _ => None,
+        }
+    }
+
+    /// Texts of remarks of type "warning".
+    pub fn warnings(&self) -> impl Iterator<Item = &str> {
+        self.remarks
+            .iter()
+            .filter(|r| r.kind.as_deref() == Some("warning"))
+            .map(|r| r.text.as_str())
+    }
+}
+
+impl BoardRemark {
+    /// Remarks without any text are of no use on a board.
+    fn from_remark(r: Remark) -> Option<Self> {
+        Some(Self {
+            id: r.id,
+            kind: r.r#type,
+            text: r.summary.or(r.text)?,
+        })
+    }
+}
+
+impl FilterStats {
+    pub fn total(&self) -> usize {
+        self.removed.iter().map(|(_, n)| n).sum()
+    }
+
+    pub(crate) fn record(&mut self, filter: &'static str, removed: usize) {
+        self.removed.push((filter, removed));
+    }
+
+    /// Sums up the counts of another stop, by filter.
+    fn add(&mut self, other: &FilterStats) {
+        for &(filter, n) in &other.removed {
+            match self.removed.iter_mut().find(|(f, _)| *f == filter) {
+                Some((_, total)) => *total += n,
+                None => self.removed.push((filter, n)),
+            }
+        }
+    }
+}
+
+impl fmt::Display for FilterStats {
+    /// e.g. "directions: 8, products: 2"
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = self
+            .removed
+            .iter()
+            .map(|(filter, n)| format!("{}: {}", filter, n))
+            .collect();
+        write!(f, "{}", parts.join(", "))
+    }
+}
@@ -0,0 +1,46 @@
+//! Version and build details, for bug reports across the feature matrix.
+
+use serde::Serialize;
+use std::fmt;
+
+/// How this binary was built. Filled in by the build script.
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    /// Commit the binary was built from, "unknown" outside a git checkout
+    pub git_hash: &'static str,
+    /// Enabled cargo features
+    pub features: Vec<&'static str>,
+    /// Target triple, e.g. "x86_64-unknown-linux-gnu"
+    pub target: &'static str,
+}
+
+impl BuildInfo {
+    pub fn current() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            git_hash: env!("BVG_DEPARTURES_GIT_HASH"),
+            features: env!("BVG_DEPARTURES_FEATURES")
+                .split(',')
+                .filter(|f| !f.is_empty())
+                .collect(),
+            target: env!("BVG_DEPARTURES_TARGET"),
+        }
+    }
+}
+
+impl fmt::Display for BuildInfo {
+    /// e.g. "bvg-departures 0.1.0 (1a2b3c4d5e6f, x86_64-unknown-linux-gnu, features: geo)"
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let features = if self.features.is_empty() {
+            "none".to_string()
+        } else {
+            self.features.join(", ")
+        };
+        write!(
+            f,
+            "bvg-departures {} ({}, {}, features: {})",
+            self.version, self.git_hash, self.target, features
+        )
+    }
+}
@@ -0,0 +1,386 @@
+//! Config errors that point at the offending value in the file, with a hint how to fix them.
+//!
+//! They are [`miette`] diagnostics, rendered by the binary like
+//!
+//! ```text
+//!   × Invalid config: stops[0].look_ahead: invalid type: string "soon", expected u32
+//!    ╭─[stops.yml:3:1]
+//!  3 │     name: Alex
+//!  4 │     look_ahead: soon
+//!    ·                 ────
+//!    ╰────
+//!   help: expected a whole number, without quotes or units
+//! ```
+
+use miette::{LabeledSpan, NamedSource, SourceCode, SourceSpan};
+use serde::de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// An error in the config. Points at the offending value once the files are known, see
+/// [`Diagnostic::in_files`].
+#[derive(Debug)]
+pub struct Diagnostic {
+    message: String,
+    /// Keys and indexes leading to the offending value, e.g. `stops[2].color`
+    key_path: Vec<Key>,
+    help: Option<String>,
+    source: Option<NamedSource>,
+    span: Option<SourceSpan>,
+}
+
+/// A config file as read, see [`Diagnostic::in_files`].
+#[derive(Debug)]
+pub struct ConfigFile {
+    pub path: PathBuf,
+    pub content: String,
+    /// Number of stops the file adds to the merged config
+    pub stops: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Key {
+    Name(String),
+    Index(usize),
+}
+
+impl Diagnostic {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            key_path: vec![],
+            help: None,
+            source: None,
+            span: None,
+        }
+    }
+
+    /// Where the offending value is in the config, as serde_yaml names it, e.g. `stops[2].color`.
+    pub fn at(mut self, key_path: &str) -> Self {
+        self.key_path = parse_key_path(key_path);
+        self
+    }
+
+    pub fn help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    /// Points at the value at the key path in the config `files`, in the order they are merged:
+    /// included files first. Stops are numbered across all files, other keys are looked up from
+    /// the last file on, as that one wins the merge. Without a key path, or if no file has the
+    /// value, only the last file is named.
+    pub fn in_files(mut self, files: &[ConfigFile]) -> Self {
+        let Some((last, rest)) = files.split_last() else {
+            return self;
+        };
+        let mut found = None;
+        if let [Key::Name(stops), Key::Index(i), ..] = self.key_path.as_mut_slice()
+            && stops == "stops"
+        {
+            for file in files {
+                if *i < file.stops {
+                    found = Some(file);
+                    break;
+                }
+                *i -= file.stops;
+            }
+        } else if !self.key_path.is_empty() {
+            found = [last]
+                .into_iter()
+                .chain(rest.iter().rev())
+                .find(|file| locate(&file.content, &self.key_path).is_some());
+        }
+        let file = found.unwrap_or(last);
+        self.span = locate(&file.content, &self.key_path).filter(|_| found.is_some());
+        self.with_source(&file.path, &file.content)
+    }
+
+    /// Error of serde_yaml while reading the merged config, which has no line numbers. Point it
+    /// at the files with [`Self::in_files`].
+    pub fn from_merged(error: serde_path_to_error::Error<serde_yaml::Error>) -> Self {
+        // e.g. "stops[0].look_ahead: invalid type: string \"soon\", expected u32"
+        let message = format!("Invalid config: {}", error);
+        Self {
+            help: yaml_help(&message),
+            ..Self::new(message)
+        }
+        .at(&error.path().to_string())
+    }
+
+    /// Error of serde_yaml while reading `content`.
+    pub fn from_yaml(path: &Path, content: &str, error: &serde_yaml::Error) -> Self {
+        let message = format!("Invalid config: {}", error);
+        let span = error
+            .location()
+            .and_then(|at| offset(content, at.line(), at.column()))
+            .map(|offset| SourceSpan::from((offset, value_len(content, offset))));
+        // the location is shown by the snippet instead
+        let message = match error.location() {
+            Some(at) if span.is_some() => message
+                .strip_suffix(&format!(" at line {} column {}", at.line(), at.column()))
+                .unwrap_or(&message)
+                .to_string(),
+            _ => message,
+        };
+        Self {
+            help: yaml_help(&message),
+            span,
+            ..Self::new(message)
+        }
+        .with_source(path, content)
+    }
+
+    fn with_source(mut self, path: &Path, content: &str) -> Self {
+        if self.span.is_none() {
+            self.message = format!("{} ({})", self.message, path.display());
+        }
+        self.source = Some(NamedSource::new(
+            path.display().to_string(),
+            content.to_string(),
+        ));
+        self
+    }
+}
+
+/// e.g. "stops[2].color" into `stops`, `2`, `color`.
+fn parse_key_path(key_path: &str) -> Vec<Key> {
+    let mut keys = vec![];
+    for part in key_path.split('.') {
+        let mut parts = part.split('[');
+        if let Some(name) = parts.next().filter(|n| !n.is_empty()) {
+            keys.push(Key::Name(name.to_string()));
+        }
+        keys.extend(
+            parts.filter_map(|index| index.trim_end_matches(']').parse().ok().map(Key::Index)),
+        );
+    }
+    keys
+}
+
+/// Start and length of the value at `key_path`, as found by the YAML parser.
+fn locate(content: &str, key_path: &[Key]) -> Option<SourceSpan> {
+    let deserializer = serde_yaml::Deserializer::from_str(content);
+    // the walk fails on purpose at the value, which gives its location
+    let error = Locate(key_path).deserialize(deserializer).err()?;
+    let at = error.location()?;
+    let offset = offset(content, at.line(), at.column())?;
+    Some((offset, value_len(content, offset)).into())
+}
+
+/// Walks down the keys of a YAML document and fails at the value they lead to.
+struct Locate<'a>(&'a [Key]);
+
+impl<'de> DeserializeSeed<'de> for Locate<'_> {
+    type Value = ();
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Locate<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("the offending value")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let (name, rest) = match self.0.split_first() {
+            None => return Err(de::Error::custom("found")),
+            Some((Key::Name(name), rest)) => (name, rest),
+            Some((Key::Index(_), _)) => return Ok(()),
+        };
+        while let Some(key) = map.next_key::<serde_yaml::Value>()? {
+            if key.as_str() == Some(name) {
+                return map.next_value_seed(Locate(rest));
+            }
+            map.next_value::<IgnoredAny>()?;
+        }
+        Ok(())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let (index, rest) = match self.0.split_first() {
+            None => return Err(de::Error::custom("found")),
+            Some((Key::Index(index), rest)) => (*index, rest),
+            Some((Key::Name(_), _)) => return Ok(()),
+        };
+        for _ in 0..index {
+            if seq.next_element::<IgnoredAny>()?.is_none() {
+                return Ok(());
+            }
+        }
+        seq.next_element_seed(Locate(rest)).map(|_| ())
+    }
+
+    // any other value fails with the default "invalid type" error, at its location
+}
+
+/// Byte offset of a 1-based line and column (in characters) of `content`.
+fn offset(content: &str, line: usize, column: usize) -> Option<usize> {
+    let start: usize = content
+        .split_inclusive('\n')
+        .take(line.checked_sub(1)?)
+        .map(str::len)
+        .sum();
+    let rest = content.get(start..)?;
+    let within = rest
+        .char_indices()
+        .nth(column.checked_sub(1)?)
+        .map_or(rest.len(), |(i, _)| i);
+    Some(start + within)
+}
+
+/// Length of the scalar starting at `offset`: up to the closing quote, else up to the end of the
+/// line, a comment or the end of a flow sequence entry.
+fn value_len(content: &str, offset: usize) -> usize {
+    let rest = &content[offset..];
+    match rest.chars().next() {
+        Some(quote @ ('"' | '\'')) => rest[1..].find(quote).map_or(rest.len(), |end| end + 2),
+        _ => {
+            let end = rest
+                .find(['\n', ',', ']', '}'])
+                .unwrap_or(rest.len())
+                .min(rest.find(" #").unwrap_or(rest.len()));
+            rest[..end].trim_end().len()
+        }
+    }
+}
+
+/// Hints for the serde errors people run into most.
+fn yaml_help(message: &str) -> Option<String> {
+    let help = if message.contains("missing field `name`") {
+        "every stop needs a `name`, e.g. `name: Alexanderplatz`"
+    } else if message.contains("missing field `stops`") {
+        "list at least one stop under `stops:`"
+    } else if message.contains("while scanning a quoted scalar") {
+        "a quote is not closed"
+    } else if message.contains("unknown variant") {
+        "check the spelling, `bvg-departures schema config` lists all settings"
+    } else if message.contains("expected u32") || message.contains("expected u64") {
+        "expected a whole number, without quotes or units"
+    } else if message.contains("expected a boolean") {
+        "expected `true` or `false`"
+    } else if message.contains("invalid type") {
+        "`bvg-departures schema config` lists all settings with their types"
+    } else {
+        return None;
+    };
+    Some(help.to_string())
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+impl miette::Diagnostic for Diagnostic {
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        self.help
+            .as_ref()
+            .map(|help| Box::new(help) as Box<dyn fmt::Display>)
+    }
+
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        self.source.as_ref().map(|source| source as &dyn SourceCode)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        let span = self.span?;
+        Some(Box::new(std::iter::once(LabeledSpan::new_with_span(
+            None, span,
+        ))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::InputStops;
+
+    fn spanned<'a>(diagnostic: &Diagnostic, content: &'a str) -> &'a str {
+        let span = diagnostic.span.expect("span");
+        &content[span.offset()..span.offset() + span.len()]
+    }
+
+    #[test]
+    fn points_at_the_offending_value() {
+        let content = "stops:\n  - id: '900100003'\n    name: Alex\n    look_ahead: soon\n";
+        let error = serde_yaml::from_str::<InputStops>(content).unwrap_err();
+        let diagnostic = Diagnostic::from_yaml(Path::new("stops.yml"), content, &error);
+        assert_eq!(
+            diagnostic.to_string(),
+            "Invalid config: stops[0].look_ahead: invalid type: string \"soon\", expected u32"
+        );
+        assert_eq!(spanned(&diagnostic, content), "soon");
+        assert_eq!(
+            diagnostic.help.as_deref(),
+            Some("expected a whole number, without quotes or units")
+        );
+    }
+
+    #[test]
+    fn finds_values_by_key_path() {
+        let included = "stops:\n  - id: '1'\n    name: A\n    color: \"#FF88\" # orange\n";
+        let own = "include: [included.yml]\nversion: 9\nstops:\n  - id: '2'\n    name: B\n    products: [subway, hovercraft]\n";
+        let files = [
+            ConfigFile {
+                path: PathBuf::from("included.yml"),
+                content: included.to_string(),
+                stops: 1,
+            },
+            ConfigFile {
+                path: PathBuf::from("stops.yml"),
+                content: own.to_string(),
+                stops: 1,
+            },
+        ];
+        let at = |key_path: &str| Diagnostic::new("Invalid").at(key_path).in_files(&files);
+
+        let diagnostic = at("stops[0].color");
+        assert_eq!(spanned(&diagnostic, included), "\"#FF88\"");
+        assert_eq!(
+            diagnostic.source.as_ref().map(NamedSource::name),
+            Some("included.yml")
+        );
+        assert_eq!(spanned(&at("stops[1].products[1]"), own), "hovercraft");
+        assert_eq!(spanned(&at("version"), own), "9");
+        // not in any file
+        let diagnostic = at("night_mode.service_day_start_hour");
+        assert!(diagnostic.span.is_none());
+        assert_eq!(diagnostic.to_string(), "Invalid (stops.yml)");
+    }
+
+    #[test]
+    fn traces_errors_in_the_merged_config_back_to_the_file() {
+        let dir = std::env::temp_dir().join(format!("bvg-diagnostic-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // the same error in both files, only the order of the stops tells them apart
+        let included =
+            "stops:\n  - id: '1'\n    name: A\n  - id: '2'\n    name: B\n    look_ahead: soon\n";
+        let own =
+            "include: [included.yml]\nstops:\n  - id: '3'\n    name: C\n    look_ahead: soon\n";
+        std::fs::write(dir.join("included.yml"), included).unwrap();
+        std::fs::write(dir.join("stops.yml"), own).unwrap();
+
+        let error = InputStops::load(&dir.join("stops.yml")).unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let diagnostic = error.downcast::<Diagnostic>().unwrap();
+        assert!(diagnostic
+            .to_string()
+            .starts_with("Invalid config: stops[1].look_ahead"));
+        assert!(diagnostic
+            .source
+            .as_ref()
+            .is_some_and(|source| source.name().ends_with("included.yml")));
+        assert_eq!(
+            diagnostic.span.map(|span| span.offset()),
+            included.find("soon")
+        );
+    }
+}
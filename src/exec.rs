@@ -0,0 +1,96 @@
+use crate::board::Board;
+use crate::view::json::BoardJson;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// Name of the environment variable that holds the path to the JSON board.
+pub const BOARD_ENV: &str = "BVG_DEPARTURES_BOARD";
+
+/// Runs a user command after each refresh.
+///
+/// The board is passed as JSON on stdin, the same as `--format json` prints. The same JSON is
+/// also written to a file whose path is exported as `BVG_DEPARTURES_BOARD`, for commands that do
+/// not read stdin. The file is removed once the last clone of the hook is dropped.
+#[derive(Debug, Clone)]
+pub struct ExecHook {
+    command: String,
+    board_file: Arc<BoardFile>,
+    /// Set while the command runs, so a slow command is not started again on every refresh
+    running: Arc<AtomicBool>,
+}
+
+#[derive(Debug)]
+struct BoardFile(PathBuf);
+
+impl Drop for BoardFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+impl ExecHook {
+    pub fn new(command: String) -> Self {
+        let board_path =
+            std::env::temp_dir().join(format!("bvg-departures-{}.json", std::process::id()));
+        Self {
+            command,
+            board_file: Arc::new(BoardFile(board_path)),
+            running: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Starts the command in the background. Await the handle to wait for it, e.g. before a
+    /// one-shot output exits. Skipped while the previous run is still going. Failures are logged,
+    /// but never abort the caller.
+    pub fn run(&self, board: &Board) -> JoinHandle<()> {
+        if self.running.swap(true, Ordering::AcqRel) {
+            debug!("'{}' is still running, skipping this refresh", self.command);
+            return tokio::spawn(async {});
+        }
+        let json = serde_json::to_vec(&BoardJson::from(board));
+        let hook = self.clone();
+        tokio::spawn(async move {
+            let result = match json {
+                Ok(json) => hook.try_run(json).await,
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = result {
+                warn!("Command '{}' failed: {}", hook.command, e);
+            }
+            hook.running.store(false, Ordering::Release);
+        })
+    }
+
+    async fn try_run(&self, json: Vec<u8>) -> anyhow::Result<()> {
+        let board_path = &self.board_file.0;
+        tokio::fs::write(board_path, &json).await?;
+
+        debug!("Running '{}'", self.command);
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(&self.command)
+            .env(BOARD_ENV, board_path)
+            .stdin(Stdio::piped())
+            // keep the board output (and the TUI) clean
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            // the command may not read stdin at all, so a broken pipe is fine
+            let _ = stdin.write_all(&json).await;
+        }
+
+        let status = child.wait().await?;
+        if !status.success() {
+            anyhow::bail!("exited with {}", status);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,85 @@
+//! Distances and compass bearings between coordinates.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+const EARTH_RADIUS_M: f64 = 6_371_000.0;
+
+/// WGS84 coordinates, e.g. `{ latitude: 52.5219, longitude: 13.4132 }`
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct GeoPoint {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl GeoPoint {
+    /// Great-circle distance in meters.
+    pub fn distance_to(&self, other: &GeoPoint) -> f64 {
+        let (lat1, lat2) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lon = (other.longitude - self.longitude).to_radians();
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_M * a.sqrt().asin()
+    }
+
+    /// Initial bearing towards `other` in degrees, 0 is north, 90 is east.
+    pub fn bearing_to(&self, other: &GeoPoint) -> f64 {
+        let (lat1, lat2) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let d_lon = (other.longitude - self.longitude).to_radians();
+        let y = d_lon.sin() * lat2.cos();
+        let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * d_lon.cos();
+        y.atan2(x).to_degrees().rem_euclid(360.0)
+    }
+
+    /// Distance and direction for a station header, e.g. "350m NE" or "1.2km S".
+    pub fn describe(&self, other: &GeoPoint) -> String {
+        let meters = self.distance_to(other);
+        let distance = if meters < 1000.0 {
+            // 10m steps, more precision is noise for stop coordinates
+            format!("{}m", (meters / 10.0).round() as u32 * 10)
+        } else {
+            format!("{:.1}km", meters / 1000.0)
+        };
+        format!("{} {}", distance, compass(self.bearing_to(other)))
+    }
+}
+
+/// One of the eight compass points for a bearing in degrees.
+pub fn compass(bearing: f64) -> &'static str {
+    const POINTS: [&str; 8] = ["N", "NE", "E", "SE", "S", "SW", "W", "NW"];
+    POINTS[((bearing.rem_euclid(360.0) + 22.5) / 45.0) as usize % 8]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALEXANDERPLATZ: GeoPoint = GeoPoint {
+        latitude: 52.521508,
+        longitude: 13.411267,
+    };
+
+    #[test]
+    fn describes_distance_and_direction() {
+        let fernsehturm = GeoPoint {
+            latitude: 52.520817,
+            longitude: 13.409419,
+        };
+        assert_eq!(ALEXANDERPLATZ.describe(&fernsehturm), "150m SW");
+
+        let ostkreuz = GeoPoint {
+            latitude: 52.503,
+            longitude: 13.469,
+        };
+        assert_eq!(ALEXANDERPLATZ.describe(&ostkreuz), "4.4km SE");
+    }
+
+    #[test]
+    fn compass_wraps_around_north() {
+        assert_eq!(compass(0.0), "N");
+        assert_eq!(compass(350.0), "N");
+        assert_eq!(compass(-10.0), "N");
+        assert_eq!(compass(44.0), "NE");
+        assert_eq!(compass(180.0), "S");
+    }
+}
@@ -0,0 +1,152 @@
+//! Addresses to coordinates, e.g. for `nearby`. The transport.rest locations endpoint is used by
+//! default; Nominatim (OpenStreetMap) knows more addresses outside of Berlin.
+
+use crate::api::locations::LocationsParams;
+use crate::api::BvgClient;
+use crate::geo::GeoPoint;
+use anyhow::anyhow;
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// A place found for an address.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Geocoded {
+    pub point: GeoPoint,
+    /// Name or full address of the place, if known
+    pub name: Option<String>,
+}
+
+/// Finds the coordinates of an address or place name.
+#[async_trait]
+pub trait Geocoder {
+    /// Best match for `address`
+    async fn geocode(&self, address: &str) -> anyhow::Result<Geocoded>;
+}
+
+/// Geocoder used for addresses, see `geocoder` in the config.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GeocoderKind {
+    /// Addresses and points of interest of the transport.rest API
+    #[default]
+    TransportRest,
+    /// OpenStreetMap Nominatim. Needs the `nominatim` feature.
+    #[cfg(feature = "nominatim")]
+    Nominatim,
+}
+
+impl GeocoderKind {
+    pub fn geocoder(self, client: &BvgClient) -> Box<dyn Geocoder + Send + Sync> {
+        match self {
+            GeocoderKind::TransportRest => Box::new(LocationsGeocoder {
+                client: client.clone(),
+            }),
+            #[cfg(feature = "nominatim")]
+            GeocoderKind::Nominatim => Box::new(NominatimGeocoder::default()),
+        }
+    }
+}
+
+/// GET /locations of the transport.rest API
+pub struct LocationsGeocoder {
+    pub client: BvgClient,
+}
+
+#[async_trait]
+impl Geocoder for LocationsGeocoder {
+    async fn geocode(&self, address: &str) -> anyhow::Result<Geocoded> {
+        let params = LocationsParams {
+            results: Some(1),
+            addresses: Some(true),
+            poi: Some(true),
+            ..Default::default()
+        };
+        self.client
+            .search_locations(address, &params)
+            .await?
+            .into_iter()
+            .find_map(|l| {
+                Some(Geocoded {
+                    point: l.geo_point()?,
+                    name: l.name.or(l.address),
+                })
+            })
+            .ok_or_else(|| anyhow!("Could not find {:?}", address))
+    }
+}
+
+#[cfg(feature = "nominatim")]
+pub use nominatim::NominatimGeocoder;
+
+#[cfg(feature = "nominatim")]
+mod nominatim {
+    use super::{Geocoded, Geocoder};
+    use crate::geo::GeoPoint;
+    use anyhow::{anyhow, Context};
+    use async_trait::async_trait;
+    use reqwest::Url;
+    use serde::Deserialize;
+    use tracing::debug;
+
+    /// GET /search of a Nominatim instance, the public one by default. Mind its usage policy of at
+    /// most one request per second.
+    pub struct NominatimGeocoder {
+        http: reqwest::Client,
+        base: Url,
+    }
+
+    impl Default for NominatimGeocoder {
+        fn default() -> Self {
+            Self::new(Url::parse("https://nominatim.openstreetmap.org/").unwrap())
+        }
+    }
+
+    impl NominatimGeocoder {
+        pub fn new(base: Url) -> Self {
+            let http = reqwest::Client::builder()
+                // required by the usage policy
+                .user_agent(concat!("bvg-departures/", env!("CARGO_PKG_VERSION")))
+                .build()
+                .expect("reqwest client");
+            Self { http, base }
+        }
+    }
+
+    /// Coordinates are sent as strings
+    #[derive(Debug, Deserialize)]
+    struct NominatimPlace {
+        lat: String,
+        lon: String,
+        display_name: Option<String>,
+    }
+
+    #[async_trait]
+    impl Geocoder for NominatimGeocoder {
+        async fn geocode(&self, address: &str) -> anyhow::Result<Geocoded> {
+            debug!("Geocoding {:?} with Nominatim", address);
+            let places: Vec<NominatimPlace> = self
+                .http
+                .get(self.base.join("search")?)
+                .query(&[("q", address), ("format", "jsonv2"), ("limit", "1")])
+                .send()
+                .await
+                .and_then(|r| r.error_for_status())
+                .context("Nominatim request failed")?
+                .json()
+                .await
+                .context("Invalid response from Nominatim")?;
+            let place = places
+                .into_iter()
+                .next()
+                .ok_or_else(|| anyhow!("Could not find {:?}", address))?;
+            Ok(Geocoded {
+                point: GeoPoint {
+                    latitude: place.lat.parse()?,
+                    longitude: place.lon.parse()?,
+                },
+                name: place.display_name,
+            })
+        }
+    }
+}
@@ -0,0 +1,815 @@
+//! GraphQL endpoint over the board, served by `bvg-departures serve`. Dashboards can ask for just
+//! the stops and fields they need in one request, e.g.
+//!
+//! ```graphql
+//! {
+//!   board(stops: ["Alexanderplatz"]) {
+//!     stations { name departures { when direction line { name color } remarks { text } } }
+//!   }
+//! }
+//! ```
+//!
+//! Types mirror the JSON of `--format json`, with camelCase field names.
+//!
+//! `/board` serves the same JSON without GraphQL, `/healthz` is for container health checks, see
+//! [`router`].
+
+use crate::api::departures::{DeparturesApi, DeparturesError};
+use crate::api::products::Product;
+use crate::board::{Board, BoardRemark, BoardRow, StationBoard};
+use crate::view::json::BoardJson;
+use crate::view::product_hex;
+use crate::InputStops;
+use async_graphql::http::GraphiQLSource;
+use async_graphql::{Context, EmptyMutation, EmptySubscription, Object, Schema, SimpleObject};
+use axum::extract::{self, Request, State};
+use axum::http::header::{AUTHORIZATION, CONTENT_TYPE, WWW_AUTHENTICATE};
+use axum::http::{HeaderValue, Method, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use base64::prelude::{Engine, BASE64_STANDARD};
+use chrono::{DateTime, Local};
+use schemars::generate::SchemaSettings;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tracing::{debug, info};
+
+pub type BoardSchema = Schema<Query, EmptyMutation, EmptySubscription>;
+
+/// How long ago departures may have been fetched for `/healthz` to report healthy.
+const MAX_FETCH_AGE: chrono::Duration = chrono::Duration::minutes(5);
+
+/// Where the departures come from. Stored in the schema, see [`schema`].
+struct Source {
+    api_client: Box<dyn DeparturesApi + Send + Sync>,
+    stops: InputStops,
+    /// Config file the stops were loaded from, read again by `/healthz`
+    config: Option<PathBuf>,
+    /// When departures of at least one stop were last fetched from the API
+    last_fetch: Mutex<Option<DateTime<Local>>>,
+}
+
+impl Source {
+    fn new(
+        api_client: impl DeparturesApi + Send + Sync + 'static,
+        stops: InputStops,
+        config: Option<PathBuf>,
+    ) -> Self {
+        Self {
+            api_client: Box::new(api_client),
+            stops,
+            config,
+            last_fetch: Mutex::default(),
+        }
+    }
+
+    /// Departures of `stops`, remembering when the API last answered.
+    async fn get_departures(&self, stops: &InputStops) -> Result<Board, DeparturesError> {
+        let board = self.api_client.get_departures(stops).await?;
+        if board
+            .stations
+            .iter()
+            .any(|s| s.error.is_none() && s.stale_since.is_none())
+        {
+            *self.last_fetch.lock().expect("last fetch lock") = Some(board.fetched_at);
+        }
+        Ok(board)
+    }
+
+    /// The part of the board in `slice`.
+    async fn board(&self, slice: &BoardSlice) -> Result<Board, BoardError> {
+        let mut stops = self.stops.clone();
+        if let Some(wanted) = &slice.stops {
+            stops.stops.retain(|s| {
+                wanted
+                    .iter()
+                    .any(|w| *w == s.id || w.eq_ignore_ascii_case(&s.name))
+            });
+            if stops.stops.is_empty() {
+                return Err(BoardError::UnknownStops(wanted.clone()));
+            }
+        }
+        if !slice.products.is_empty() {
+            stops.stops.retain_mut(|s| {
+                s.products = if s.products.is_empty() {
+                    slice.products.clone()
+                } else {
+                    s.products
+                        .iter()
+                        .filter(|p| slice.products.contains(p))
+                        .cloned()
+                        .collect()
+                };
+                // none of them left would mean all of them
+                !s.products.is_empty()
+            });
+        }
+        let mut board = self.get_departures(&stops).await?;
+        for station in &mut board.stations {
+            if !slice.lines.is_empty() {
+                station.rows.retain(|row| {
+                    row.line.as_ref().is_some_and(|line| {
+                        slice.lines.iter().any(|l| l.eq_ignore_ascii_case(line))
+                    })
+                });
+            }
+            if let Some(max) = slice.max {
+                station.rows.truncate(max);
+            }
+        }
+        Ok(board)
+    }
+
+    fn fetch_age(&self) -> Option<chrono::Duration> {
+        self.last_fetch
+            .lock()
+            .expect("last fetch lock")
+            .map(|at| Local::now() - at)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+enum BoardError {
+    #[error("None of {0:?} is in the config")]
+    UnknownStops(Vec<String>),
+    #[error("Unknown product {0:?}, products are suburban, subway, tram, bus, ferry, express and regional")]
+    UnknownProduct(String),
+    #[error(transparent)]
+    Departures(#[from] DeparturesError),
+}
+
+/// Part of the board asked for, by the arguments of the `board` query or the query string of
+/// `/board`. Applied like the filters of the config: stops and products before the request, the
+/// rest after it.
+#[derive(Debug, Default)]
+struct BoardSlice {
+    /// Ids or names of stops in the config, all if missing
+    stops: Option<Vec<String>>,
+    /// Line names, e.g. "U8". All if empty.
+    lines: Vec<String>,
+    /// Departures per station
+    max: Option<usize>,
+    /// Only these of the products configured for each stop. All if empty.
+    products: Vec<Product>,
+}
+
+impl BoardSlice {
+    fn products(names: Vec<String>) -> Result<Vec<Product>, BoardError> {
+        names
+            .into_iter()
+            .map(|name| match Product::from(name.to_lowercase()) {
+                Product::Unknown(_) => Err(BoardError::UnknownProduct(name)),
+                product => Ok(product),
+            })
+            .collect()
+    }
+}
+
+/// Query string of `/board`, with comma separated lists, e.g. `?stop=Alexanderplatz&line=U2,U8`.
+#[derive(Debug, Deserialize)]
+struct BoardQuery {
+    stop: Option<String>,
+    line: Option<String>,
+    max: Option<usize>,
+    products: Option<String>,
+}
+
+impl TryFrom<BoardQuery> for BoardSlice {
+    type Error = BoardError;
+
+    fn try_from(query: BoardQuery) -> Result<Self, Self::Error> {
+        let list = |value: Option<String>| -> Vec<String> {
+            value
+                .iter()
+                .flat_map(|v| v.split(','))
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(String::from)
+                .collect()
+        };
+        Ok(Self {
+            stops: query.stop.is_some().then(|| list(query.stop)),
+            lines: list(query.line),
+            max: query.max,
+            products: Self::products(list(query.products))?,
+        })
+    }
+}
+
+#[derive(Clone)]
+struct AppState {
+    schema: BoardSchema,
+    source: Arc<Source>,
+}
+
+/// Schema answering with the departures of `stops` from `api_client`.
+pub fn schema(
+    api_client: impl DeparturesApi + Send + Sync + 'static,
+    stops: InputStops,
+) -> BoardSchema {
+    build_schema(Arc::new(Source::new(api_client, stops, None)))
+}
+
+fn build_schema(source: Arc<Source>) -> BoardSchema {
+    Schema::build(Query, EmptyMutation, EmptySubscription)
+        .data(source)
+        .finish()
+}
+
+/// The schema at `/graphql`: queries as POST, GraphiQL in the browser. `/board` answers with the
+/// JSON of `--format json`, narrowed down by `?stop=`, `?line=`, `?max=` and `?products=` like the
+/// arguments of the `board` query. `/openapi.json` describes these endpoints. `/healthz` answers 200
+/// while departures were fetched in the last 5 minutes and `config`, if given, is still valid,
+/// else 503.
+///
+/// All but `/healthz` are behind `access`. Fails if one of its CORS origins is not a valid origin.
+pub fn router(
+    api_client: impl DeparturesApi + Send + Sync + 'static,
+    stops: InputStops,
+    config: Option<PathBuf>,
+    access: Access,
+) -> anyhow::Result<Router> {
+    let origins = access
+        .cors_origins
+        .iter()
+        .map(|origin| {
+            HeaderValue::from_str(origin)
+                .map_err(|_| anyhow::anyhow!("Invalid CORS origin {:?}", origin))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let source = Arc::new(Source::new(api_client, stops, config));
+    let protected = Router::new()
+        .route("/graphql", get(graphiql).post(execute))
+        .route("/board", get(board))
+        .route("/openapi.json", get(|| async { Json(openapi()) }))
+        .route_layer(middleware::from_fn_with_state(Arc::new(access), authorize));
+    let router = Router::new()
+        .merge(protected)
+        // for health checks of containers, which do not know the credentials
+        .route("/healthz", get(healthz))
+        .with_state(AppState {
+            schema: build_schema(source.clone()),
+            source,
+        });
+    if origins.is_empty() {
+        return Ok(router);
+    }
+    Ok(router.layer(
+        CorsLayer::new()
+            .allow_origin(AllowOrigin::list(origins))
+            .allow_methods([Method::GET, Method::POST])
+            .allow_headers([AUTHORIZATION, CONTENT_TYPE]),
+    ))
+}
+
+/// Who may use the endpoints of [`router`]. [`Default`] lets everyone in, but browsers only from
+/// pages served by the router itself.
+#[derive(Debug, Clone, Default)]
+pub struct Access {
+    /// Let in requests with `Authorization: Bearer <token>`
+    pub bearer_token: Option<String>,
+    /// Let in requests with these user and password as basic auth, e.g. from a browser
+    pub basic_auth: Option<(String, String)>,
+    /// Origins of web pages allowed to call the endpoints, e.g. `http://dashboard.local:3000`
+    pub cors_origins: Vec<String>,
+}
+
+impl Access {
+    /// Whether the `Authorization` header is enough. Any is without token or basic auth.
+    fn allows(&self, authorization: Option<&HeaderValue>) -> bool {
+        if self.bearer_token.is_none() && self.basic_auth.is_none() {
+            return true;
+        }
+        let Some(authorization) = authorization.and_then(|v| v.to_str().ok()) else {
+            return false;
+        };
+        if let (Some(token), Some(given)) =
+            (&self.bearer_token, authorization.strip_prefix("Bearer "))
+            && same(token.as_bytes(), given.as_bytes())
+        {
+            return true;
+        }
+        if let (Some((user, password)), Some(given)) =
+            (&self.basic_auth, authorization.strip_prefix("Basic "))
+            && let Ok(given) = BASE64_STANDARD.decode(given)
+        {
+            return same(format!("{}:{}", user, password).as_bytes(), &given);
+        }
+        false
+    }
+}
+
+/// Compares all bytes, so the time taken does not tell how much of a guess was right.
+fn same(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+async fn authorize(State(access): State<Arc<Access>>, request: Request, next: Next) -> Response {
+    if access.allows(request.headers().get(AUTHORIZATION)) {
+        return next.run(request).await;
+    }
+    let mut response = StatusCode::UNAUTHORIZED.into_response();
+    if access.basic_auth.is_some() {
+        // makes browsers ask for the password
+        response.headers_mut().insert(
+            WWW_AUTHENTICATE,
+            HeaderValue::from_static("Basic realm=\"bvg-departures\""),
+        );
+    }
+    response
+}
+
+/// Serves `router` until Ctrl-C.
+pub async fn serve(router: Router, addr: SocketAddr) -> anyhow::Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    info!(
+        "Serving GraphQL at http://{}/graphql",
+        listener.local_addr()?
+    );
+    axum::serve(listener, router)
+        .with_graceful_shutdown(async {
+            tokio::signal::ctrl_c().await.ok();
+        })
+        .await?;
+    Ok(())
+}
+
+async fn graphiql() -> Html<String> {
+    Html(GraphiQLSource::build().endpoint("/graphql").finish())
+}
+
+async fn execute(
+    State(state): State<AppState>,
+    Json(request): Json<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    Json(state.schema.execute(request).await)
+}
+
+/// The board as with `--format json`, or the part of it in the query string.
+async fn board(
+    State(state): State<AppState>,
+    extract::Query(query): extract::Query<BoardQuery>,
+) -> Response {
+    let board = match BoardSlice::try_from(query) {
+        Ok(slice) => state.source.board(&slice).await,
+        Err(e) => Err(e),
+    };
+    match board {
+        Ok(board) => Json(BoardJson::from(&board)).into_response(),
+        Err(e @ BoardError::Departures(_)) => {
+            (StatusCode::BAD_GATEWAY, e.to_string()).into_response()
+        }
+        Err(e) => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    }
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct Health {
+    /// Seconds since departures were last fetched, null if they never were
+    last_fetch_age_seconds: Option<i64>,
+    /// Why the config file cannot be loaded anymore
+    config_error: Option<String>,
+}
+
+/// OpenAPI description of the endpoints. The schemas are generated like the one of the config,
+/// GraphQL clients find theirs at `/graphql`.
+fn openapi() -> serde_json::Value {
+    let mut generator = SchemaSettings::openapi3().for_serialize().into_generator();
+    let board = generator.subschema_for::<BoardJson>();
+    let health = generator.subschema_for::<Health>();
+    let list = |name: &str, description: &str| {
+        json!({
+            "name": name,
+            "in": "query",
+            "description": description,
+            "schema": {"type": "string"},
+        })
+    };
+    let json = |description: &str, schema: &schemars::Schema| json!({"description": description, "content": {"application/json": {"schema": schema}}});
+    let unauthorized = json!({"description": "Missing or wrong credentials, see `serve --token`"});
+    json!({
+        "openapi": "3.0.3",
+        "info": {"title": "bvg-departures", "version": env!("CARGO_PKG_VERSION")},
+        "paths": {
+            "/board": {"get": {
+                "summary": "Departures of the stops in the config, as with --format json",
+                "parameters": [
+                    list("stop", "Comma separated ids or names of stops in the config"),
+                    list("line", "Comma separated line names, e.g. U2,U8"),
+                    {
+                        "name": "max",
+                        "in": "query",
+                        "description": "Departures per station",
+                        "schema": {"type": "integer", "minimum": 0},
+                    },
+                    list("products", "Comma separated products, e.g. subway,tram"),
+                ],
+                "responses": {
+                    "200": json("The board", &board),
+                    "400": {"description": "Unknown stop or product"},
+                    "401": unauthorized,
+                    "502": {"description": "The API could not be reached"},
+                },
+            }},
+            "/graphql": {"post": {
+                "summary": "GraphQL query, GET opens GraphiQL with the schema",
+                "requestBody": {"required": true, "content": {"application/json": {"schema": {
+                    "type": "object",
+                    "required": ["query"],
+                    "properties": {
+                        "query": {"type": "string"},
+                        "variables": {"type": "object"},
+                        "operationName": {"type": "string"},
+                    },
+                }}}},
+                "responses": {
+                    "200": {"description": "GraphQL response, errors included"},
+                    "401": unauthorized,
+                },
+            }},
+            "/healthz": {"get": {
+                "summary": "Whether departures were fetched lately and the config still loads",
+                "responses": {
+                    "200": json("Healthy", &health),
+                    "503": json("Unhealthy", &health),
+                },
+            }},
+        },
+        "components": {"schemas": generator.take_definitions(true)},
+    })
+}
+
+async fn healthz(State(state): State<AppState>) -> (StatusCode, Json<Health>) {
+    let source = &state.source;
+    // nobody asked for departures lately: try, so an idle server stays healthy
+    if source.fetch_age().is_none_or(|age| age > MAX_FETCH_AGE)
+        && let Err(e) = source.get_departures(&source.stops).await
+    {
+        debug!("Health check could not fetch departures: {}", e);
+    }
+    let config_error = source
+        .config
+        .as_ref()
+        .and_then(|path| InputStops::load(path).err())
+        .map(|e| format!("{:#}", e));
+    let age = source.fetch_age();
+    let status = if config_error.is_none() && age.is_some_and(|age| age <= MAX_FETCH_AGE) {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    let health = Health {
+        last_fetch_age_seconds: age.map(|age| age.num_seconds()),
+        config_error,
+    };
+    (status, Json(health))
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    /// Departures of the stops in the config, or only of `stops`, given by id or name. `lines`
+    /// and `products` narrow them down further, `max` limits the departures of each station.
+    async fn board(
+        &self,
+        ctx: &Context<'_>,
+        stops: Option<Vec<String>>,
+        lines: Option<Vec<String>>,
+        max: Option<usize>,
+        products: Option<Vec<String>>,
+    ) -> async_graphql::Result<BoardObject> {
+        let source = ctx.data::<Arc<Source>>()?;
+        let slice = BoardSlice {
+            stops,
+            lines: lines.unwrap_or_default(),
+            max,
+            products: BoardSlice::products(products.unwrap_or_default())?,
+        };
+        Ok(BoardObject::from(&source.board(&slice).await?))
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(name = "Board")]
+pub struct BoardObject {
+    /// RFC 3339
+    fetched_at: String,
+    stations: Vec<Station>,
+}
+
+#[derive(SimpleObject)]
+struct Station {
+    stop_id: String,
+    name: String,
+    /// Hex color of the stop in the config
+    color: Option<String>,
+    /// Whether `departures` are arrivals, with `direction` being where they come from
+    arrivals: bool,
+    /// Why the departures of this stop are missing
+    error: Option<String>,
+    /// Set if the API could not be reached and these are the last departures fetched, RFC 3339
+    stale_since: Option<String>,
+    departures: Vec<Departure>,
+}
+
+#[derive(SimpleObject)]
+struct Departure {
+    trip_id: Option<String>,
+    line: Option<Line>,
+    direction: Option<String>,
+    /// RFC 3339
+    planned_when: Option<String>,
+    /// Realtime departure if known, RFC 3339
+    when: Option<String>,
+    /// In seconds
+    delay: Option<i64>,
+    platform: Option<String>,
+    planned_platform: Option<String>,
+    cancelled: bool,
+    /// The delay is beyond `max_delay_minutes`, likely a glitch in the data
+    implausible_delay: bool,
+    remarks: Vec<Remark>,
+}
+
+#[derive(SimpleObject)]
+struct Line {
+    /// e.g. "u8"
+    id: Option<String>,
+    /// e.g. "U8"
+    name: String,
+    /// e.g. "subway"
+    product: Option<String>,
+    /// Hex color of the product, as on the board
+    color: String,
+}
+
+#[derive(SimpleObject)]
+struct Remark {
+    /// e.g. "hint" or "warning"
+    kind: Option<String>,
+    text: String,
+}
+
+impl From<&Board> for BoardObject {
+    fn from(board: &Board) -> Self {
+        Self {
+            fetched_at: board.fetched_at.to_rfc3339(),
+            stations: board.stations.iter().map(Station::from).collect(),
+        }
+    }
+}
+
+impl From<&StationBoard> for Station {
+    fn from(station: &StationBoard) -> Self {
+        Self {
+            stop_id: station.stop_id.clone(),
+            name: station.name.clone(),
+            color: station.color.clone(),
+            arrivals: station.arrivals,
+            error: station.error.clone(),
+            stale_since: station.stale_since.map(|t| t.to_rfc3339()),
+            departures: station.rows.iter().map(Departure::from).collect(),
+        }
+    }
+}
+
+impl From<&BoardRow> for Departure {
+    fn from(row: &BoardRow) -> Self {
+        Self {
+            trip_id: row.trip_id.clone(),
+            line: row.line.as_ref().map(|name| Line {
+                id: row.line_id.clone(),
+                name: name.clone(),
+                product: row.product.as_ref().map(ToString::to_string),
+                color: product_hex(row.product.as_ref()).to_string(),
+            }),
+            direction: row.direction.clone(),
+            planned_when: row.planned_when.map(|t| t.to_rfc3339()),
+            when: row.when.map(|t| t.to_rfc3339()),
+            delay: row.delay,
+            platform: row.platform.clone(),
+            planned_platform: row.planned_platform.clone(),
+            cancelled: row.cancelled,
+            implausible_delay: row.implausible_delay,
+            remarks: row.remarks.iter().map(Remark::from).collect(),
+        }
+    }
+}
+
+impl From<&BoardRemark> for Remark {
+    fn from(remark: &BoardRemark) -> Self {
+        Self {
+            kind: remark.kind.clone(),
+            text: remark.text.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::departures::DeparturesError;
+    use async_trait::async_trait;
+    use chrono::Local;
+    use serde_json::json;
+
+    /// Answers with one departure per stop
+    struct FakeApi;
+
+    #[async_trait]
+    impl DeparturesApi for FakeApi {
+        async fn get_departures(&self, stops: &InputStops) -> Result<Board, DeparturesError> {
+            let stations = stops
+                .stops
+                .iter()
+                .map(|stop| {
+                    let response = serde_json::from_value(json!({"departures": [{
+                        "tripId": format!("trip-{}", stop.id),
+                        "direction": "Pankow",
+                        "line": {"id": "u2", "name": "U2", "product": "subway"},
+                        "remarks": [{"type": "warning", "summary": "Elevator out of order"}],
+                    }]}))
+                    .unwrap();
+                    StationBoard::new(stop, response)
+                })
+                .collect();
+            Ok(Board {
+                stations,
+                fetched_at: Local::now(),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn queries_the_requested_stops_and_fields() {
+        let stops: InputStops = serde_yaml::from_str(
+            "stops:\n  - id: '1'\n    name: Alex\n  - id: '2'\n    name: Zoo\n",
+        )
+        .unwrap();
+        let schema = schema(FakeApi, stops);
+        let response = schema
+            .execute(
+                r#"{ board(stops: ["zoo"]) { stations { stopId departures {
+                    tripId line { name color } remarks { kind text } } } } }"#,
+            )
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            json!({"board": {"stations": [{"stopId": "2", "departures": [{
+                "tripId": "trip-2",
+                "line": {"name": "U2", "color": "#00539F"},
+                "remarks": [{"kind": "warning", "text": "Elevator out of order"}],
+            }]}]}})
+        );
+
+        let response = schema
+            .execute(r#"{ board(stops: ["Pankow"]) { fetchedAt } }"#)
+            .await;
+        assert_eq!(response.errors.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn healthz_checks_the_fetch_and_the_config() {
+        let stops: InputStops =
+            serde_yaml::from_str("stops:\n  - id: '1'\n    name: Alex\n").unwrap();
+        let state = |config: Option<PathBuf>| {
+            let source = Arc::new(Source::new(FakeApi, stops.clone(), config));
+            AppState {
+                schema: build_schema(source.clone()),
+                source,
+            }
+        };
+
+        // fetches by itself if nobody asked for departures yet
+        let (status, Json(health)) = healthz(State(state(None))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(health.last_fetch_age_seconds, Some(0));
+        assert_eq!(health.config_error, None);
+
+        let missing = std::env::temp_dir().join("bvg-healthz-missing.yml");
+        let (status, Json(health)) = healthz(State(state(Some(missing)))).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert!(health.config_error.is_some());
+    }
+
+    #[tokio::test]
+    async fn board_takes_the_filters_from_the_query_string() {
+        let stops: InputStops = serde_yaml::from_str(
+            "stops:\n  - id: '1'\n    name: Alex\n  - id: '2'\n    name: Zoo\n    products: [bus]\n",
+        )
+        .unwrap();
+        let source = Source::new(FakeApi, stops, None);
+        let slice = |query: &str| {
+            let uri = format!("/board?{}", query).parse().unwrap();
+            BoardSlice::try_from(extract::Query::<BoardQuery>::try_from_uri(&uri).unwrap().0)
+        };
+        let rows = |board: &Board| {
+            board
+                .stations
+                .iter()
+                .map(|s| (s.stop_id.clone(), s.rows.len()))
+                .collect::<Vec<_>>()
+        };
+
+        let board = source
+            .board(&slice("stop=zoo,1&line=u2").unwrap())
+            .await
+            .unwrap();
+        assert_eq!(rows(&board), [("1".to_string(), 1), ("2".to_string(), 1)]);
+        let board = source.board(&slice("line=U8").unwrap()).await.unwrap();
+        assert_eq!(rows(&board), [("1".to_string(), 0), ("2".to_string(), 0)]);
+        let board = source.board(&slice("max=0").unwrap()).await.unwrap();
+        assert_eq!(rows(&board), [("1".to_string(), 0), ("2".to_string(), 0)]);
+        // Zoo only has buses
+        let board = source
+            .board(&slice("products=Subway").unwrap())
+            .await
+            .unwrap();
+        assert_eq!(rows(&board), [("1".to_string(), 1)]);
+
+        assert!(matches!(
+            slice("products=zeppelin"),
+            Err(BoardError::UnknownProduct(_))
+        ));
+        assert!(matches!(
+            source.board(&slice("stop=Pankow").unwrap()).await,
+            Err(BoardError::UnknownStops(_))
+        ));
+    }
+
+    #[test]
+    fn openapi_describes_the_endpoints() {
+        let doc = openapi();
+        let paths = doc["paths"].as_object().unwrap();
+        assert_eq!(
+            paths.keys().collect::<Vec<_>>(),
+            ["/board", "/graphql", "/healthz"]
+        );
+        for schema in ["Board", "Station", "Departure", "Health"] {
+            assert!(
+                doc["components"]["schemas"][schema].is_object(),
+                "{}",
+                schema
+            );
+        }
+        assert_eq!(
+            doc["paths"]["/board"]["get"]["responses"]["200"]["content"]["application/json"]
+                ["schema"]["$ref"],
+            "#/components/schemas/Board"
+        );
+    }
+
+    #[tokio::test]
+    async fn access_needs_credentials_and_an_allowed_origin() {
+        let stops: InputStops =
+            serde_yaml::from_str("stops:\n  - id: '1'\n    name: Alex\n").unwrap();
+        let access = Access {
+            bearer_token: Some("secret".to_string()),
+            basic_auth: Some(("me".to_string(), "pass".to_string())),
+            cors_origins: vec!["http://dashboard.local".to_string()],
+        };
+        let router = router(FakeApi, stops, None, access).unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        let client = reqwest::Client::new();
+        let board = || client.get(format!("{}/board", url));
+
+        let response = board().send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+        assert!(response.headers().contains_key("www-authenticate"));
+        let response = board().bearer_auth("guess").send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+        let response = board().bearer_auth("secret").send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        let response = board().basic_auth("me", Some("pass")).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        let response = client.get(format!("{}/healthz", url)).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        let origin = |origin: &'static str| {
+            client
+                .request(reqwest::Method::OPTIONS, format!("{}/graphql", url))
+                .header("origin", origin)
+                .header("access-control-request-method", "POST")
+        };
+        let response = origin("http://dashboard.local").send().await.unwrap();
+        assert_eq!(
+            response.headers()["access-control-allow-origin"],
+            "http://dashboard.local"
+        );
+        let response = origin("http://elsewhere.example").send().await.unwrap();
+        assert!(!response
+            .headers()
+            .contains_key("access-control-allow-origin"));
+    }
+}
@@ -0,0 +1,107 @@
+use crate::api::locations::{Coordinate, NearbyParams};
+use crate::api::BvgClient;
+use crate::{InputStop, InputStops};
+use anyhow::{anyhow, bail, Context};
+use serde_json::Value;
+use std::path::Path;
+use tracing::{info, warn};
+
+/// A saved place (home, work, ...) read from a GeoJSON file.
+#[derive(Debug)]
+struct Place {
+    label: String,
+    latitude: f64,
+    longitude: f64,
+}
+
+/// Finds the nearest stop for each point in a GeoJSON file and turns it into a config entry.
+///
+/// The stop is named after the place label, e.g. "Home (S Ostkreuz)".
+pub async fn import_stops(client: &BvgClient, path: &Path) -> anyhow::Result<InputStops> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Could not read {}", path.display()))?;
+    let places = parse_places(&content)?;
+    info!("Found {} places in {}", places.len(), path.display());
+
+    let params = NearbyParams {
+        results: Some(1),
+        stops: Some(true),
+        poi: Some(false),
+        ..Default::default()
+    };
+
+    let mut stops = vec![];
+    for place in places {
+        let nearest = client
+            .nearby_stops(place.latitude, place.longitude, &params)
+            .await?
+            .into_iter()
+            .find(|l| l.id.is_some());
+
+        let Some(stop) = nearest else {
+            warn!("No stop found near {}", place.label);
+            continue;
+        };
+
+        let location = stop.location.as_ref().and_then(Coordinate::to_geo_point);
+        let stop_name = stop.name.unwrap_or_default();
+        stops.push(InputStop {
+            location,
+            ..InputStop::new(
+                stop.id.expect("stop with id"),
+                format!("{} ({})", place.label, stop_name),
+            )
+        });
+    }
+
+    Ok(InputStops::new(stops))
+}
+
+/// Accepts a FeatureCollection or a single Feature. Only Point geometries are used.
+fn parse_places(content: &str) -> anyhow::Result<Vec<Place>> {
+    let json: Value = serde_json::from_str(content).context("Invalid GeoJSON")?;
+
+    let features = match json.get("type").and_then(Value::as_str) {
+        Some("FeatureCollection") => json
+            .get("features")
+            .and_then(Value::as_array)
+            .cloned()
+            .ok_or_else(|| anyhow!("FeatureCollection without features"))?,
+        Some("Feature") => vec![json],
+        other => bail!("Unsupported GeoJSON type {:?}", other),
+    };
+
+    let mut places = vec![];
+    for (i, feature) in features.iter().enumerate() {
+        let geometry = feature.get("geometry");
+        if geometry.and_then(|g| g.get("type")).and_then(Value::as_str) != Some("Point") {
+            warn!("Skipping feature {} without Point geometry", i);
+            continue;
+        }
+
+        // GeoJSON positions are [longitude, latitude]
+        let coordinates = geometry
+            .and_then(|g| g.get("coordinates"))
+            .and_then(Value::as_array)
+            .and_then(|c| Some((c.first()?.as_f64()?, c.get(1)?.as_f64()?)));
+        let Some((longitude, latitude)) = coordinates else {
+            warn!("Skipping feature {} with invalid coordinates", i);
+            continue;
+        };
+
+        let properties = feature.get("properties");
+        let label = ["name", "title", "label"]
+            .iter()
+            .find_map(|key| properties?.get(key)?.as_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("Place {}", i + 1));
+
+        places.push(Place {
+            label,
+            latitude,
+            longitude,
+        });
+    }
+
+    Ok(places)
+}
@@ -0,0 +1,45 @@
+use std::process::Stdio;
+use tokio::process::{Child, Command};
+use tracing::{info, warn};
+
+/// Command that holds an idle inhibitor lock until it is killed. logind forwards the lock to the
+/// session, which keeps both X11 and Wayland desktops from blanking the screen.
+const INHIBIT_COMMAND: &[&str] = &[
+    "systemd-inhibit",
+    "--what=idle",
+    "--who=bvg-departures",
+    "--why=Showing departures",
+    "--mode=block",
+    "sleep",
+    "infinity",
+];
+
+/// Keeps the screen from blanking while it is alive.
+pub struct ScreensaverInhibitor {
+    /// Killed on drop, which releases the lock
+    _child: Child,
+}
+
+impl ScreensaverInhibitor {
+    /// Takes the inhibitor lock. Returns `None` if that is not possible, e.g. without systemd.
+    pub fn start() -> Option<Self> {
+        let spawned = Command::new(INHIBIT_COMMAND[0])
+            .args(&INHIBIT_COMMAND[1..])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn();
+
+        match spawned {
+            Ok(child) => {
+                info!("Inhibiting screensaver");
+                Some(Self { _child: child })
+            }
+            Err(e) => {
+                warn!("Could not inhibit screensaver: {}", e);
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! Interactive setup of a new config file: search stops by name instead of looking up raw ids.
+
+use crate::api::BvgClient;
+use crate::{InputStop, InputStops};
+use anyhow::{bail, Context};
+use dialoguer::{Confirm, Input, MultiSelect};
+use std::path::Path;
+use tracing::warn;
+
+/// Max number of search results offered per query
+const SEARCH_RESULTS: u32 = 8;
+
+/// Asks for stops until an empty search and writes them as config to `path`.
+pub async fn init_config(client: &BvgClient, path: &Path, force: bool) -> anyhow::Result<()> {
+    if path.exists() && !force {
+        bail!(
+            "{} already exists, use --force to overwrite it",
+            path.display()
+        );
+    }
+
+    let mut stops = vec![];
+    loop {
+        let query: String = Input::new()
+            .with_prompt("Search stop (empty to finish)")
+            .allow_empty(true)
+            .interact_text()?;
+        let query = query.trim();
+        if query.is_empty() {
+            break;
+        }
+
+        let found = client.search_stops(query, SEARCH_RESULTS).await?;
+        if found.is_empty() {
+            warn!("No stops found for '{}'", query);
+            continue;
+        }
+        let names: Vec<String> = found
+            .iter()
+            .map(|l| l.name.clone().unwrap_or_default())
+            .collect();
+        let picked = MultiSelect::new()
+            .with_prompt("Stops to add (space to select, enter to confirm)")
+            .items(&names)
+            .interact()?;
+
+        for i in picked {
+            let location = &found[i];
+            let mut stop =
+                InputStop::new(location.id.clone().expect("stop with id"), names[i].clone());
+            stop.location = location.location.as_ref().and_then(|c| c.to_geo_point());
+            stop.look_ahead = Input::new()
+                .with_prompt(format!(
+                    "{}: show departures for how many minutes",
+                    stop.name
+                ))
+                .default(stop.look_ahead)
+                .interact_text()?;
+            let directions: String = Input::new()
+                .with_prompt(format!(
+                    "{}: only directions containing (comma separated, empty for all)",
+                    stop.name
+                ))
+                .allow_empty(true)
+                .interact_text()?;
+            stop.directions = parse_directions(&directions);
+            stops.push(stop);
+        }
+    }
+
+    if stops.is_empty() {
+        bail!("No stops selected, nothing written");
+    }
+
+    let config = InputStops::new(stops);
+    config.validate()?;
+    let yaml = serde_yaml::to_string(&config)?;
+    println!("{}", yaml);
+    if !Confirm::new()
+        .with_prompt(format!("Write to {}?", path.display()))
+        .default(true)
+        .interact()?
+    {
+        return Ok(());
+    }
+
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Could not create {}", dir.display()))?;
+    }
+    std::fs::write(path, yaml).with_context(|| format!("Could not write {}", path.display()))?;
+    println!("Wrote {}", path.display());
+    Ok(())
+}
+
+fn parse_directions(input: &str) -> Vec<String> {
+    input
+        .split(',')
+        .map(str::trim)
+        .filter(|d| !d.is_empty())
+        .map(str::to_string)
+        .collect()
+}
@@ -0,0 +1,688 @@
+//! Departures of BVG stops in Berlin, from the [transport.rest](https://v6.bvg.transport.rest/) API.
+//!
+//! The `bvg-departures` binary is a thin command line client of this library. To embed the
+//! departures elsewhere, load a config (or build one with [`InputStops::new`]) and fetch a
+//! [`Board`] with [`BvgClient`], which implements [`DeparturesApi`]:
+//!
+//! ```no_run
+//! use bvg_departures::{BvgClient, DeparturesApi, InputStops};
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let stops = InputStops::load("input/stops.yml".as_ref())?;
+//! let board = BvgClient::default().get_departures(&stops).await?;
+//! for station in &board.stations {
+//!     for row in &station.rows {
+//!         println!("{}: {:?} to {:?}", station.name, row.line, row.direction);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The board is already filtered by the `directions` of each stop. [`BvgClient::filter`] and
+//! [`BvgClient::filter_night_network`] can be applied to boards built by hand, e.g. from
+//! [`DeparturesResponse`]s fetched elsewhere. The [`view`] module holds the terminal and status
+//! bar frontends of the binary.
+
+pub mod announce;
+pub mod api;
+pub mod board;
+pub mod build_info;
+pub mod diagnostic;
+pub mod exec;
+pub mod geo;
+pub mod geocode;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+pub mod import;
+pub mod inhibit;
+pub mod init;
+pub mod locate;
+pub mod migrate;
+pub mod notify;
+pub mod request_log;
+pub mod resolve;
+pub mod schedule;
+#[cfg(feature = "script")]
+pub mod script;
+pub mod snapshot;
+#[cfg(feature = "translate")]
+pub mod translate;
+pub mod view;
+pub mod watch;
+
+pub use api::departures::{DeparturesApi, DeparturesError, DeparturesParams, DeparturesResponse};
+pub use api::BvgClient;
+pub use board::{Board, BoardRow, StationBoard};
+
+use crate::api::directions::{DirectionMatcher, DirectionMatchers};
+use crate::diagnostic::{ConfigFile, Diagnostic};
+use crate::geo::GeoPoint;
+use crate::migrate::CONFIG_VERSION;
+use anyhow::{bail, Context};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// The config: which stops to show and how. See `bvg-departures schema config` for all keys.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct InputStops {
+    /// Version of the config format. Older files can be upgraded with `config migrate`.
+    #[serde(default)]
+    pub version: u32,
+    /// Other config files whose stops come before the ones in this file. Settings in this file win.
+    /// Relative to this file.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<PathBuf>,
+    /// Stops shown on the board, in this order
+    pub stops: Vec<InputStop>,
+    /// Keep departed trips on the board for this many minutes, like platform displays do.
+    #[serde(default = "u32_value_2")]
+    pub grace_minutes: u32,
+    /// Base URL of the transport.rest API. Overridden by `--base-url` / `BVG_DEPARTURES_BASE_URL`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+    /// Language of API responses, e.g. "de" or "en". Overridden by `--language` / `BVG_DEPARTURES_LANGUAGE`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Link to a departure, copied with `y` in the TUI. Placeholders: {trip_id}, {stop_id}, {line}
+    /// and {base_url}. Defaults to the trip in the BVG journey planner, which opens in the BVG app.
+    /// "{base_url}/trips/{trip_id}?stopovers=true&pretty=true" links the trip in the API instead.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deep_link: Option<String>,
+    /// Notify when a trip pinned with `p` in the TUI is this many minutes late or more, at least 1.
+    #[serde(default = "u32_value_5")]
+    pub watch_delay_minutes: u32,
+    /// Only send notifications for pinned trips in these days and hours, e.g.
+    /// "Mon-Fri 07:00-09:00". Always if missing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<String>")]
+    pub watch_only: Option<schedule::Schedule>,
+    /// Lines, e.g. "U8", for which the TUI notifies once they run normally again after
+    /// cancellations or delays of `watch_delay_minutes`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub watch_lines: Vec<String>,
+    /// No notifications in these days and hours, e.g. "22:00-07:00", even within `watch_only`.
+    /// The alerts are still logged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<String>")]
+    pub quiet_hours: Option<schedule::Schedule>,
+    /// Minutes before the same alert about a trip or line is sent again, e.g. for a trip that keeps
+    /// going from late to on time and back
+    #[serde(default = "u32_value_15")]
+    pub notify_cooldown_minutes: u32,
+    /// Speak the next departure at an interval in the TUI and with `--status-file`. Off if missing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub announce: Option<announce::Announce>,
+    /// Service days and night lines. Off if missing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub night_mode: Option<NightMode>,
+    /// Directions shown for the S-Bahn ring (S41/S42) instead of the ones from the API
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ring_labels: Option<RingLabels>,
+    /// YAML file mapping official stop names to nicknames, e.g. `"S+U Hermannstr. (Berlin)": Hermi`.
+    /// Relative to the config file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aliases: Option<PathBuf>,
+    /// YAML file remembering the ids of stops configured by name only, so they are only looked
+    /// up once. Relative to the config file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id_cache: Option<PathBuf>,
+    /// Where the responses of the last run and the last departures of each stop are kept.
+    /// Relative to the config file. Defaults to `$XDG_CACHE_HOME/bvg-departures`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_dir: Option<PathBuf>,
+    /// Where you usually look at the board from. Station headers then show the distance and
+    /// direction to each stop, e.g. "350m NE".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub my_location: Option<GeoPoint>,
+    /// How addresses are turned into coordinates, e.g. for `nearby`. Defaults to `transport_rest`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub geocoder: Option<geocode::GeocoderKind>,
+    /// Hide departures that leave before you could walk to the stop, see `walk_minutes`.
+    #[serde(default)]
+    pub hide_unreachable: bool,
+    /// Count down to when you have to leave instead of to the departure, for stops with
+    /// `walk_minutes`.
+    #[serde(default)]
+    pub show_leave_in: bool,
+    /// Show the planned time next to the actual one, e.g. "14:32 → 14:38", if they differ.
+    #[serde(default)]
+    pub show_planned_times: bool,
+    /// Delays beyond this many minutes are taken for data glitches: shown as "?" and left out of
+    /// sorting and notifications.
+    #[serde(default = "u32_value_300")]
+    pub max_delay_minutes: u32,
+    /// Show the nearest stops first. Needs `my_location`.
+    #[serde(default)]
+    pub sort_by_distance: bool,
+    /// Add the route of each departure as GeoJSON to the `--exec` board. Needs the `geo` feature.
+    #[cfg(feature = "geo")]
+    #[serde(default)]
+    pub polylines: bool,
+    /// Translate remark texts with a command. Needs the `translate` feature.
+    #[cfg(feature = "translate")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub translate: Option<translate::TranslateConfig>,
+    /// Contents of the `aliases` file, read by [`InputStops::load`]
+    #[serde(skip)]
+    pub direction_aliases: HashMap<String, String>,
+}
+
+/// A stop in the config.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct InputStop {
+    /// Stop id as used by the BVG API, e.g. "900100003". If missing, the stop is looked up by
+    /// `name` at startup.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
+    /// Name shown in the station header
+    pub name: String,
+    /// Show departures for this many minutes
+    #[serde(default = "u32_value_15")]
+    look_ahead: u32,
+    // directions can be missing or empty, so Option<Vec<String>> is safe
+    /// Directions do not need to match the BVG-API response. It is used for filtering during post-processing.
+    /// Stop ids, e.g. "900100001", are passed to the API instead, which then only returns trips
+    /// stopping there later. Not for arrivals.
+    /// Case, umlauts and punctuation do not matter. Entries starting with `re:` are regular
+    /// expressions, e.g. `"re:^S\\+U (Pankow|Wittenau)"`.
+    #[serde(default)]
+    pub directions: Vec<String>,
+    /// Hide departures toward these directions, e.g. `[Pankow]` for the one you never take. Matched
+    /// like `directions` but without stop ids, and applied after them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude_directions: Vec<String>,
+    /// Also match slightly misspelled `directions` and `exclude_directions`, e.g. "Wittenua"
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub fuzzy_directions: bool,
+    /// Only show these products, e.g. `[suburban]` to hide the buses. All if empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub products: Vec<api::products::Product>,
+    /// Show arrivals instead of departures, e.g. to pick someone up. `directions` then filter by
+    /// where the trips come from.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub arrivals: bool,
+    /// Color of the station header as hex, e.g. "#FF8800"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    /// Icon shown in front of the station header, e.g. "🏫"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    /// Minutes it takes to walk to the stop. Departures you cannot catch anymore are dimmed, or
+    /// hidden with `hide_unreachable`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub walk_minutes: Option<u32>,
+    /// Coordinates of the stop. Taken from the API responses if missing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<GeoPoint>,
+    /// Stops with the same group, e.g. the platforms of a large station, are shown as one station
+    /// named after the group. Trips seen at several of them are only shown once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Give up on this stop after this many milliseconds, retries included. The other stops are
+    /// shown without waiting for it, it shows its saved departures if there are any, and it is
+    /// tried again on the next refresh.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+    /// Rhai script deciding which departures to keep, relative to the config file. Needs the
+    /// `script` feature.
+    #[cfg(feature = "script")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<PathBuf>,
+    /// The compiled `script`, set by [`InputStops::load`]
+    #[cfg(feature = "script")]
+    #[serde(skip)]
+    pub script_filter: Option<std::sync::Arc<script::ScriptFilter>>,
+    /// The compiled `directions` and `exclude_directions`, set by [`InputStops::load`]
+    #[serde(skip)]
+    pub direction_matchers: Option<std::sync::Arc<DirectionMatchers>>,
+}
+
+impl InputStop {
+    /// Entries of `directions` that are stop ids rather than text.
+    pub fn direction_ids(&self) -> impl Iterator<Item = &str> {
+        self.directions
+            .iter()
+            .map(String::as_str)
+            .filter(|d| is_stop_id(d))
+    }
+
+    /// Stop with default settings and no filters.
+    pub fn new(id: String, name: String) -> Self {
+        Self {
+            id,
+            name,
+            look_ahead: u32_value_15(),
+            directions: vec![],
+            exclude_directions: vec![],
+            fuzzy_directions: false,
+            products: vec![],
+            arrivals: false,
+            color: None,
+            icon: None,
+            walk_minutes: None,
+            location: None,
+            group: None,
+            timeout_ms: None,
+            #[cfg(feature = "script")]
+            script: None,
+            #[cfg(feature = "script")]
+            script_filter: None,
+            direction_matchers: None,
+        }
+    }
+
+    /// The `direction_matchers` set by [`InputStops::load`], or built now for stops from
+    /// elsewhere. Fails on the first invalid regular expression.
+    pub fn direction_matchers(&self) -> Result<std::sync::Arc<DirectionMatchers>, regex::Error> {
+        match &self.direction_matchers {
+            Some(matchers) => Ok(matchers.clone()),
+            None => DirectionMatchers::new(self).map(std::sync::Arc::new),
+        }
+    }
+}
+
+/// Whether a `directions` entry is a stop id like "900100001".
+pub(crate) fn is_stop_id(direction: &str) -> bool {
+    !direction.is_empty() && direction.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Directions of the S-Bahn ring. The API only knows "Ring", which is not much help.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct RingLabels {
+    /// Shown for the S41
+    #[serde(default = "clockwise")]
+    pub clockwise: String,
+    /// Shown for the S42
+    #[serde(default = "counterclockwise")]
+    pub counterclockwise: String,
+}
+
+impl Default for RingLabels {
+    fn default() -> Self {
+        Self {
+            clockwise: clockwise(),
+            counterclockwise: counterclockwise(),
+        }
+    }
+}
+
+fn clockwise() -> String {
+    "Ring ↻ clockwise".to_string()
+}
+
+fn counterclockwise() -> String {
+    "Ring ↺ counterclockwise".to_string()
+}
+
+/// Night bus handling: departures after midnight count to the previous service day, night lines
+/// are labeled and the board can be limited to the night network.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct NightMode {
+    /// Departures before this hour belong to the previous service day
+    #[serde(default = "u32_value_3")]
+    pub service_day_start_hour: u32,
+    /// Only show the night network during these hours
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub night_network: Option<NightNetwork>,
+}
+
+/// Hours in which only night lines are shown.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct NightNetwork {
+    /// First hour of the night network, e.g. 1 for 01:00
+    pub from_hour: u32,
+    /// Hour in which the day network starts again, e.g. 5 for 05:00
+    pub to_hour: u32,
+    /// Lines that run at night besides the N lines, e.g. "M10" or "U8" on weekends
+    #[serde(default)]
+    pub lines: Vec<String>,
+}
+
+impl NightNetwork {
+    /// Whether `hour` (0-23) lies in the night network hours, which may span midnight.
+    pub fn is_active(&self, hour: u32) -> bool {
+        if self.from_hour <= self.to_hour {
+            (self.from_hour..self.to_hour).contains(&hour)
+        } else {
+            hour >= self.from_hour || hour < self.to_hour
+        }
+    }
+
+    pub fn runs(&self, line: &str) -> bool {
+        is_night_line(line) || self.lines.iter().any(|l| l == line)
+    }
+}
+
+/// Night lines are named "N" followed by a number, e.g. "N9".
+pub fn is_night_line(line: &str) -> bool {
+    line.strip_prefix('N')
+        .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+}
+
+impl InputStops {
+    /// Config with the given stops and default settings.
+    pub fn new(stops: Vec<InputStop>) -> Self {
+        Self {
+            version: CONFIG_VERSION,
+            include: vec![],
+            stops,
+            grace_minutes: u32_value_2(),
+            base_url: None,
+            language: None,
+            deep_link: None,
+            watch_delay_minutes: u32_value_5(),
+            watch_only: None,
+            watch_lines: vec![],
+            quiet_hours: None,
+            notify_cooldown_minutes: u32_value_15(),
+            announce: None,
+            night_mode: None,
+            ring_labels: None,
+            aliases: None,
+            id_cache: None,
+            cache_dir: None,
+            hide_unreachable: false,
+            show_leave_in: false,
+            show_planned_times: false,
+            max_delay_minutes: u32_value_300(),
+            my_location: None,
+            geocoder: None,
+            sort_by_distance: false,
+            #[cfg(feature = "geo")]
+            polylines: false,
+            #[cfg(feature = "translate")]
+            translate: None,
+            direction_aliases: HashMap::new(),
+        }
+    }
+
+    /// Reads and validates a config file, including the files it refers to.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        Self::load_with(path, |path| std::fs::read_to_string(path))
+    }
+
+    /// Like [`Self::load`], with `read` giving the content of each file, e.g. of files upgraded in
+    /// memory.
+    pub fn load_with(
+        path: &Path,
+        read: impl Fn(&Path) -> std::io::Result<String>,
+    ) -> anyhow::Result<Self> {
+        let mut files = vec![];
+        let merged = read_with_includes(path, &read, &mut Vec::new(), &mut files)?;
+        let mut stops: InputStops = serde_path_to_error::deserialize(merged)
+            .map_err(|e| Diagnostic::from_merged(e).in_files(&files))?;
+        // compiled once here instead of on every refresh, validate reports the invalid ones
+        for s in &mut stops.stops {
+            s.direction_matchers = DirectionMatchers::new(s).ok().map(std::sync::Arc::new);
+        }
+        if let Err(e) = stops.validate() {
+            return Err(match e.downcast::<Diagnostic>() {
+                Ok(diagnostic) => diagnostic.in_files(&files).into(),
+                Err(e) => e,
+            });
+        }
+
+        if let Some(aliases) = &stops.aliases {
+            let content = std::fs::read_to_string(aliases)
+                .with_context(|| format!("Could not read aliases {}", aliases.display()))?;
+            stops.direction_aliases = serde_yaml::from_str(&content)
+                .with_context(|| format!("Invalid aliases {}", aliases.display()))?;
+        }
+        #[cfg(feature = "script")]
+        for s in &mut stops.stops {
+            if let Some(path) = &s.script {
+                s.script_filter = Some(std::sync::Arc::new(script::ScriptFilter::load(path)?));
+            }
+        }
+        Ok(stops)
+    }
+
+    /// Checks values that serde cannot check on its own. Errors are [`Diagnostic`]s.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.version > CONFIG_VERSION {
+            bail!(Diagnostic::new(format!(
+                "Config version {} is newer than this build supports ({})",
+                self.version, CONFIG_VERSION
+            ))
+            .at("version")
+            .help("update bvg-departures"));
+        }
+        if let Some(night) = &self.night_mode {
+            let hours = [("service_day_start_hour", night.service_day_start_hour)]
+                .into_iter()
+                .chain(night.night_network.iter().flat_map(|n| {
+                    [
+                        ("night_network.from_hour", n.from_hour),
+                        ("night_network.to_hour", n.to_hour),
+                    ]
+                }));
+            for (key, hour) in hours {
+                if hour > 23 {
+                    bail!(Diagnostic::new(format!(
+                        "Invalid hour {} in night_mode, expected 0-23",
+                        hour
+                    ))
+                    .at(&format!("night_mode.{}", key))
+                    .help("hours are full hours of the day, e.g. 4 for 04:00"));
+                }
+            }
+        }
+        if self.watch_delay_minutes == 0 {
+            bail!(Diagnostic::new("watch_delay_minutes must be at least 1")
+                .at("watch_delay_minutes")
+                .help("with 0, every trip on time would count as late"));
+        }
+        if self.sort_by_distance && self.my_location.is_none() {
+            bail!(Diagnostic::new("sort_by_distance needs my_location")
+                .at("sort_by_distance")
+                .help("add `my_location: { latitude: 52.52, longitude: 13.41 }`"));
+        }
+        for (i, s) in self.stops.iter().enumerate() {
+            if let Some((j, api::products::Product::Unknown(product))) = s
+                .products
+                .iter()
+                .enumerate()
+                .find(|(_, p)| matches!(p, api::products::Product::Unknown(_)))
+            {
+                bail!(Diagnostic::new(format!(
+                    "Unknown product {:?} for stop {}",
+                    product, s.name
+                ))
+                .at(&format!("stops[{}].products[{}]", i, j))
+                .help("products are suburban, subway, tram, bus, ferry, express and regional"));
+            }
+            // matchers built by `load` compiled already
+            let unbuilt = [
+                ("directions", &s.directions),
+                ("exclude_directions", &s.exclude_directions),
+            ]
+            .into_iter()
+            .filter(|_| s.direction_matchers.is_none());
+            for (key, directions) in unbuilt {
+                if let Err(e) = DirectionMatcher::new(directions, s.fuzzy_directions) {
+                    let k = directions
+                        .iter()
+                        .position(|d| {
+                            d.strip_prefix(api::directions::REGEX_PREFIX)
+                                .is_some_and(|p| regex::Regex::new(p.trim()).is_err())
+                        })
+                        .unwrap_or_default();
+                    // the last line of regex errors says what is wrong, the rest points at it
+                    let error = e.to_string();
+                    let reason = error.lines().last().unwrap_or_default();
+                    bail!(Diagnostic::new(format!(
+                        "Invalid direction pattern for stop {}: {}",
+                        s.name,
+                        reason.trim_start_matches("error: ")
+                    ))
+                    .at(&format!("stops[{}].{}[{}]", i, key, k))
+                    .help("remove the `re:` prefix to match the text as is"));
+                }
+            }
+            if let Some(color) = &s.color {
+                let hex = color.strip_prefix('#').unwrap_or_default();
+                if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                    bail!(Diagnostic::new(format!(
+                        "Invalid color {:?} for stop {}, expected \"#RRGGBB\"",
+                        color, s.name
+                    ))
+                    .at(&format!("stops[{}].color", i))
+                    .help("e.g. \"#FF8800\", in quotes as `#` starts a comment"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy of the config where each stop showing departures is followed by its arrivals, for
+    /// the composite view. The arrivals are not filtered by direction nor grouped. Stops whose
+    /// arrivals are in the config already keep them.
+    pub fn with_arrivals(&self) -> Self {
+        let mut stops = self.clone();
+        stops.stops.clear();
+        for s in &self.stops {
+            stops.stops.push(s.clone());
+            let listed = self.stops.iter().any(|o| o.arrivals && o.id == s.id);
+            if s.arrivals || listed {
+                continue;
+            }
+            let mut arrivals = s.clone();
+            arrivals.arrivals = true;
+            arrivals.directions.clear();
+            arrivals.exclude_directions.clear();
+            arrivals.direction_matchers = Some(Default::default());
+            arrivals.group = None;
+            stops.stops.push(arrivals);
+        }
+        stops
+    }
+
+    /// Copy of the config with all direction filters removed.
+    pub fn without_filters(&self) -> Self {
+        let mut stops = self.clone();
+        for s in &mut stops.stops {
+            s.directions.clear();
+            s.exclude_directions.clear();
+            s.direction_matchers = Some(Default::default());
+        }
+        stops
+    }
+}
+
+/// Reads a config file with `read` and merges in the files from its `include` list.
+///
+/// `stack` holds the files currently being read, to detect include cycles. Paths in the result are
+/// relative to the working directory. Every file read is added to `files`, in the order they are
+/// merged, so that errors in the merged config can be traced back to the file.
+fn read_with_includes(
+    path: &Path,
+    read: &dyn Fn(&Path) -> std::io::Result<String>,
+    stack: &mut Vec<PathBuf>,
+    files: &mut Vec<ConfigFile>,
+) -> anyhow::Result<Value> {
+    let canonical = path
+        .canonicalize()
+        .with_context(|| format!("Could not read {}", path.display()))?;
+    if stack.contains(&canonical) {
+        let chain: Vec<String> = stack
+            .iter()
+            .chain([&canonical])
+            .map(|p| p.display().to_string())
+            .collect();
+        bail!("Config include cycle: {}", chain.join(" -> "));
+    }
+
+    let content = read(path).with_context(|| format!("Could not read {}", path.display()))?;
+    let Value::Mapping(mut own) =
+        serde_yaml::from_str(&content).map_err(|e| Diagnostic::from_yaml(path, &content, &e))?
+    else {
+        bail!("Invalid config {}: expected a mapping", path.display());
+    };
+
+    let dir = path.parent().unwrap_or(Path::new("."));
+    for key in ["aliases", "id_cache", "cache_dir"] {
+        if let Some(Value::String(file)) = own.get_mut(key) {
+            *file = dir.join(&*file).display().to_string();
+        }
+    }
+    let mut own_stops = 0;
+    if let Some(Value::Sequence(stops)) = own.get_mut("stops") {
+        own_stops = stops.len();
+        for stop in stops {
+            if let Some(Value::String(script)) = stop.get_mut("script") {
+                *script = dir.join(&*script).display().to_string();
+            }
+        }
+    }
+    let includes: Vec<PathBuf> = match own.remove("include") {
+        Some(includes) => serde_yaml::from_value(includes)
+            .with_context(|| format!("Invalid include list in {}", path.display()))?,
+        None => vec![],
+    };
+
+    stack.push(canonical);
+    let mut merged = Mapping::new();
+    for include in includes {
+        let included =
+            read_with_includes(&dir.join(&include), read, stack, files).with_context(|| {
+                format!(
+                    "Could not include {} from {}",
+                    include.display(),
+                    path.display()
+                )
+            })?;
+        merge(&mut merged, included);
+    }
+    stack.pop();
+
+    merge(&mut merged, Value::Mapping(own));
+    files.push(ConfigFile {
+        path: path.to_path_buf(),
+        content,
+        stops: own_stops,
+    });
+    Ok(Value::Mapping(merged))
+}
+
+/// Appends the stops of `config` and overwrites all other keys.
+fn merge(merged: &mut Mapping, config: Value) {
+    let Value::Mapping(config) = config else {
+        return;
+    };
+    for (key, value) in config {
+        match (merged.get_mut(&key), value) {
+            (Some(Value::Sequence(stops)), Value::Sequence(more))
+                if key.as_str() == Some("stops") =>
+            {
+                stops.extend(more)
+            }
+            (_, value) => {
+                merged.insert(key, value);
+            }
+        }
+    }
+}
+
+fn u32_value_15() -> u32 {
+    15
+}
+
+fn u32_value_2() -> u32 {
+    2
+}
+
+fn u32_value_3() -> u32 {
+    3
+}
+
+fn u32_value_5() -> u32 {
+    5
+}
+
+fn u32_value_300() -> u32 {
+    300
+}
@@ -0,0 +1,192 @@
+//! Zero-config board for wherever you are: find the current location and the stops around it.
+
+use crate::api::locations::NearbyParams;
+use crate::api::BvgClient;
+use crate::geo::GeoPoint;
+use crate::geocode::{Geocoded, Geocoder};
+use crate::{InputStop, InputStops};
+use anyhow::{anyhow, Context};
+use serde::Deserialize;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+use tracing::{debug, info, warn};
+
+/// Demo client shipped with GeoClue, depending on the distribution.
+const WHERE_AM_I: &[&str] = &[
+    "/usr/libexec/geoclue-2.0/demos/where-am-i",
+    "/usr/lib/geoclue-2.0/demos/where-am-i",
+];
+
+/// Seconds GeoClue may take for a fix
+const GEOCLUE_TIMEOUT: u64 = 10;
+
+const IP_LOCATION_URL: &str = "https://ipapi.co/json/";
+
+/// Finds the current location via GeoClue, falling back to IP geolocation.
+pub async fn locate() -> anyhow::Result<GeoPoint> {
+    match geoclue().await {
+        Ok(location) => return Ok(location),
+        Err(e) => warn!("GeoClue unavailable, falling back to IP geolocation: {}", e),
+    }
+    ip_location().await
+}
+
+/// Config with the stops nearest to the current location, nearest first.
+pub async fn nearby_config(client: &BvgClient, results: u32) -> anyhow::Result<InputStops> {
+    let here = locate().await?;
+    info!("Located at {}, {}", here.latitude, here.longitude);
+
+    let params = NearbyParams {
+        results: Some(results),
+        stops: Some(true),
+        poi: Some(false),
+        ..Default::default()
+    };
+    let stops: Vec<InputStop> = client
+        .nearby_stops(here.latitude, here.longitude, &params)
+        .await?
+        .into_iter()
+        .filter_map(|l| {
+            Some(InputStop {
+                location: l.location.as_ref().and_then(|c| c.to_geo_point()),
+                ..InputStop::new(l.id?, l.name?)
+            })
+        })
+        .collect();
+    if stops.is_empty() {
+        anyhow::bail!("No stops found near {}, {}", here.latitude, here.longitude);
+    }
+
+    let mut config = InputStops::new(stops);
+    config.my_location = Some(here);
+    config.sort_by_distance = true;
+    Ok(config)
+}
+
+/// Coordinates like "52.5219,13.4132", else the place `geocoder` finds for `place`.
+pub async fn resolve_place(geocoder: &dyn Geocoder, place: &str) -> anyhow::Result<GeoPoint> {
+    if let Some(point) = parse_coordinates(place) {
+        return Ok(point);
+    }
+    let Geocoded { point, name } = geocoder.geocode(place).await?;
+    info!(
+        "Found {} at {}, {}",
+        name.as_deref().unwrap_or(place),
+        point.latitude,
+        point.longitude
+    );
+    Ok(point)
+}
+
+/// "latitude,longitude", with or without a space after the comma.
+fn parse_coordinates(text: &str) -> Option<GeoPoint> {
+    let (latitude, longitude) = text.split_once(',')?;
+    Some(GeoPoint {
+        latitude: latitude.trim().parse().ok()?,
+        longitude: longitude.trim().parse().ok()?,
+    })
+}
+
+async fn geoclue() -> anyhow::Result<GeoPoint> {
+    let program = WHERE_AM_I
+        .iter()
+        .find(|p| std::path::Path::new(p).exists())
+        .ok_or_else(|| anyhow!("where-am-i not found"))?;
+
+    // where-am-i keeps running until the timeout and prints every location update
+    let output = tokio::time::timeout(
+        Duration::from_secs(GEOCLUE_TIMEOUT + 2),
+        Command::new(program)
+            .arg(format!("--timeout={}", GEOCLUE_TIMEOUT))
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output(),
+    )
+    .await
+    .context("GeoClue did not answer")??;
+
+    parse_where_am_i(&String::from_utf8_lossy(&output.stdout))
+        .ok_or_else(|| anyhow!("GeoClue did not report a location"))
+}
+
+/// Takes the last location from output like "Latitude:    52.520000°".
+fn parse_where_am_i(output: &str) -> Option<GeoPoint> {
+    let value = |key: &str| {
+        output
+            .lines()
+            .filter_map(|l| l.trim().strip_prefix(key))
+            .filter_map(|v| v.trim().trim_end_matches('°').parse().ok())
+            .next_back()
+    };
+    Some(GeoPoint {
+        latitude: value("Latitude:")?,
+        longitude: value("Longitude:")?,
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct IpLocation {
+    latitude: f64,
+    longitude: f64,
+}
+
+async fn ip_location() -> anyhow::Result<GeoPoint> {
+    debug!("Getting location from {}", IP_LOCATION_URL);
+    let response = reqwest::Client::new()
+        .get(IP_LOCATION_URL)
+        .header(
+            reqwest::header::USER_AGENT,
+            concat!("bvg-departures/", env!("CARGO_PKG_VERSION")),
+        )
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .context("Could not get location from IP")?;
+    let location: IpLocation = response
+        .json()
+        .await
+        .context("Invalid response from IP geolocation")?;
+    Ok(GeoPoint {
+        latitude: location.latitude,
+        longitude: location.longitude,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn takes_last_location_from_where_am_i() {
+        let output = "Client object: /org/freedesktop/GeoClue2/Client/1\n\n\
+            New location:\n\
+            Latitude:    52.000000°\n\
+            Longitude:   13.000000°\n\
+            Accuracy:    25000.000000 meters\n\n\
+            New location:\n\
+            Latitude:    52.521508°\n\
+            Longitude:   13.411267°\n\
+            Accuracy:    20.000000 meters\n";
+        assert_eq!(
+            parse_where_am_i(output),
+            Some(GeoPoint {
+                latitude: 52.521508,
+                longitude: 13.411267
+            })
+        );
+        assert_eq!(parse_where_am_i("Client object: /x\n"), None);
+    }
+
+    #[test]
+    fn coordinates_or_address() {
+        assert_eq!(
+            parse_coordinates("52.5219, 13.4132"),
+            Some(GeoPoint {
+                latitude: 52.5219,
+                longitude: 13.4132
+            })
+        );
+        assert_eq!(parse_coordinates("Karl-Marx-Str. 1, Berlin"), None);
+    }
+}
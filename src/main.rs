@@ -1,92 +1,897 @@
-use crate::api::BvgClient;
-use std::fs;
+use bvg_departures::api::journeys::JourneysParams;
+use bvg_departures::api::locations::{LocationKind, NearbyParams};
+use bvg_departures::api::offline;
+use bvg_departures::api::retry::RetryPolicy;
+use bvg_departures::build_info::BuildInfo;
+use bvg_departures::diagnostic::Diagnostic;
+use bvg_departures::exec::ExecHook;
+use bvg_departures::inhibit::ScreensaverInhibitor;
+use bvg_departures::migrate;
+use bvg_departures::request_log::{self, RequestLog};
+use bvg_departures::snapshot::BoardSnapshot;
+use bvg_departures::view::csv::CsvDisplayBuilder;
+use bvg_departures::view::journey::format_journeys;
+use bvg_departures::view::json::JsonDisplayBuilder;
+use bvg_departures::view::status_file::{StatusFileDisplayBuilder, StatusMarkup};
+use bvg_departures::view::std_out::StdoutDisplayBuilder;
+use bvg_departures::view::tui::{LogBuffer, TuiDisplayBuilder};
+use bvg_departures::view::verbose::VerboseDisplayBuilder;
+use bvg_departures::view::{OutputFormat, ResultDisplay};
+use bvg_departures::{import, init, locate, resolve, BvgClient, InputStops};
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{ArgAction, Args, CommandFactory, FromArgMatches, Parser, Subcommand};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{info, warn};
+use tracing_subscriber::filter::{filter_fn, LevelFilter};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::Layer;
+use url::Url;
 
-mod api;
-mod view;
+/// Config file used if none is given
+const DEFAULT_CONFIG: &str = "input/stops.yml";
 
-use crate::view::std_out::StdoutDisplayBuilder;
-use crate::view::tui::{LogBuffer, TuiDisplayBuilder};
-use crate::view::ResultDisplay;
-use clap::Parser;
-use serde::Deserialize;
-use tracing::info;
+/// Number of stops shown with `--here`
+const NEARBY_STOPS: u32 = 5;
 
-#[derive(Debug, Deserialize)]
-pub struct InputStops {
-    pub stops: Vec<InputStop>,
+/// Requests kept for the pane toggled with L in the TUI
+const REQUEST_LOG_SIZE: usize = 50;
+
+#[derive(Parser, Debug)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    /// Without a subcommand, the board is shown: `bvg-departures stops.yml` is short for
+    /// `bvg-departures board stops.yml`
+    #[clap(flatten)]
+    board: BoardArgs,
+
+    #[clap(flatten)]
+    global: GlobalArgs,
+
+    /// Print version and build information
+    #[clap(long, short = 'V')]
+    version: bool,
+
+    /// Print the version information as JSON
+    #[clap(long, requires = "version")]
+    json: bool,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct InputStop {
-    pub id: String,
-    pub name: String,
-    #[serde(default = "u32_value_15")]
-    look_ahead: u32,
-    // directions can be missing or empty, so Option<Vec<String>> is safe
-    /// Directions do not need to match the BVG-API response. It is used for filtering during post-processing.
-    #[serde(default)]
-    pub directions: Vec<String>,
+impl Cli {
+    /// Like `parse`, but rejects board flags given together with a subcommand, which would be
+    /// ignored otherwise. Flags of all subcommands may come before the subcommand.
+    fn parse_checked() -> Self {
+        Cli::try_parse_checked_from(std::env::args_os()).unwrap_or_else(|e| e.exit())
+    }
+
+    fn try_parse_checked_from<I, T>(args: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        let matches = Cli::command().try_get_matches_from(args)?;
+        if let Some((name, _)) = matches.subcommand() {
+            let board = BoardArgs::augment_args(clap::Command::new("board"));
+            let given = board.get_arguments().find(|a| {
+                matches.value_source(a.get_id().as_str()) == Some(ValueSource::CommandLine)
+            });
+            if let Some(arg) = given {
+                let flag = match (arg.get_long(), arg.get_value_names()) {
+                    (Some(long), _) => format!("--{}", long),
+                    (None, Some([name, ..])) => name.to_string(),
+                    _ => arg.get_id().to_string(),
+                };
+                return Err(Cli::command().error(
+                    ErrorKind::ArgumentConflict,
+                    format!("'{}' cannot be used with the '{}' subcommand", flag, name),
+                ));
+            }
+        }
+        Cli::from_arg_matches(&matches)
+    }
 }
 
-fn u32_value_15() -> u32 {
-    15
+// Flags of all subcommands. Not a doc comment, clap would take it as the about of the binary.
+#[derive(Args, Debug)]
+struct GlobalArgs {
+    /// Do not emit any log output
+    #[clap(long, short, global = true)]
+    quiet: bool,
+
+    /// Base URL of the transport.rest API [default: https://v6.bvg.transport.rest/]
+    #[clap(
+        long,
+        value_name = "URL",
+        env = "BVG_DEPARTURES_BASE_URL",
+        global = true
+    )]
+    base_url: Option<String>,
+
+    /// Language of API responses, e.g. "en" [default: de]
+    #[clap(long, env = "BVG_DEPARTURES_LANGUAGE", global = true)]
+    language: Option<String>,
+
+    /// Number of requests sent at the same time, for departures of all stops and their directions
+    #[clap(
+        long,
+        default_value_t = 4,
+        env = "BVG_DEPARTURES_CONCURRENCY",
+        global = true
+    )]
+    concurrency: usize,
+
+    /// How often a request failing with a server error or timeout is sent again
+    #[clap(
+        long,
+        default_value_t = 2,
+        env = "BVG_DEPARTURES_RETRIES",
+        global = true
+    )]
+    retries: u32,
+
+    /// Milliseconds before the first retry, doubled for each further one. Varied by ±50% at
+    /// random, the share is only configurable in the library (`RetryPolicy::jitter`)
+    #[clap(
+        long,
+        value_name = "MS",
+        default_value_t = 500,
+        env = "BVG_DEPARTURES_RETRY_DELAY",
+        global = true
+    )]
+    retry_delay: u64,
+
+    /// Seconds for which the departures of a stop are reused instead of requested again
+    #[clap(
+        long,
+        value_name = "SECONDS",
+        default_value_t = 15,
+        env = "BVG_DEPARTURES_CACHE_TTL",
+        global = true
+    )]
+    cache_ttl: u64,
+
+    /// Read departures from `<stop id>-departures.json` files in this directory instead of the API,
+    /// e.g. to work on the views offline or for demos
+    #[clap(
+        long,
+        value_name = "DIR",
+        env = "BVG_DEPARTURES_FIXTURE",
+        global = true
+    )]
+    fixture: Option<PathBuf>,
+
+    /// Where the responses of the last run and the last departures of each stop are kept
+    /// [default: `cache_dir` of the config, else $XDG_CACHE_HOME/bvg-departures]
+    #[clap(
+        long,
+        value_name = "DIR",
+        env = "BVG_DEPARTURES_CACHE_DIR",
+        global = true
+    )]
+    cache_dir: Option<PathBuf>,
+
+    /// Always request fresh departures, see `--cache-ttl`
+    #[clap(long, env = "BVG_DEPARTURES_NO_CACHE", global = true)]
+    no_cache: bool,
+
+    /// Do not save the last departures of each stop to disk. While the API cannot be reached, the
+    /// ones of this run are still shown
+    #[clap(long, env = "BVG_DEPARTURES_NO_OFFLINE", global = true)]
+    no_offline: bool,
 }
 
-#[derive(Parser, Debug)]
-struct Cli {
+impl GlobalArgs {
+    /// Settings are taken from the command line, then the environment, then the config file.
+    fn api_client(&self, stops: Option<&InputStops>) -> anyhow::Result<BvgClient> {
+        let mut client = match self
+            .base_url
+            .as_ref()
+            .or(stops.and_then(|s| s.base_url.as_ref()))
+        {
+            Some(base) => BvgClient::new(Url::parse(base)?),
+            None => BvgClient::default(),
+        }
+        .with_concurrency(self.concurrency)
+        .with_retry(RetryPolicy {
+            max_attempts: self.retries.saturating_add(1),
+            base_delay: Duration::from_millis(self.retry_delay),
+            ..RetryPolicy::default()
+        });
+        let cache_dir = self
+            .cache_dir
+            .clone()
+            .or(stops.and_then(|s| s.cache_dir.clone()))
+            .or_else(offline::default_dir);
+        if !self.no_offline
+            && let Some(dir) = &cache_dir
+        {
+            client = client.with_offline_store(dir.join("departures"));
+        }
+        if let Some(dir) = &self.fixture {
+            client = client.with_fixtures(dir.clone());
+        }
+        if !self.no_cache {
+            let ttl = Duration::from_secs(self.cache_ttl);
+            client = match &cache_dir {
+                Some(dir) => client.with_persistent_cache(ttl, dir.join("responses.json")),
+                None => client.with_cache(ttl),
+            };
+        }
+        if let Some(language) = self
+            .language
+            .as_ref()
+            .or(stops.and_then(|s| s.language.as_ref()))
+        {
+            client = client.with_language(language);
+        }
+        #[cfg(feature = "geo")]
+        if stops.is_some_and(|s| s.polylines) {
+            client = client.with_polylines();
+        }
+        #[cfg(feature = "translate")]
+        if let Some(translate) = stops.and_then(|s| s.translate.clone()) {
+            client =
+                client.with_translator(bvg_departures::translate::RemarkTranslator::new(translate));
+        }
+        Ok(client)
+    }
+}
+
+#[derive(Args, Debug)]
+struct BoardArgs {
     /// The path to the file to read
-    #[clap(default_value = "input/stops.yml")]
-    path: std::path::PathBuf,
+    #[clap(default_value = DEFAULT_CONFIG)]
+    path: PathBuf,
+
+    /// Show the stops near the current location instead of the config file. Uses GeoClue if
+    /// available, else IP geolocation.
+    #[clap(long)]
+    here: bool,
 
-    /// Use a simple TUI for display
-    #[clap(long, action)]
-    #[clap(default_value = "true")]
+    /// Use a simple TUI for display. Falls back to plain output if stdout is not a terminal.
+    #[clap(long, action = ArgAction::Set, default_value_t = true, env = "BVG_DEPARTURES_TUI")]
     tui: bool,
+
+    /// Stable, tab-separated output for scripts: no colors, no emoji, no headers.
+    /// Implies `--tui false`.
+    #[clap(
+        long,
+        env = "BVG_DEPARTURES_PORCELAIN",
+        conflicts_with_all = ["merged", "grouped"]
+    )]
+    porcelain: bool,
+
+    /// Output format. Anything but text implies `--tui false`.
+    #[clap(
+        long,
+        value_enum,
+        default_value_t = OutputFormat::Text,
+        env = "BVG_DEPARTURES_FORMAT",
+        alias = "output",
+        conflicts_with = "porcelain"
+    )]
+    format: OutputFormat,
+
+    /// Run this shell command after each refresh. The board is passed as JSON on stdin and
+    /// its file path in `BVG_DEPARTURES_BOARD`.
+    #[clap(long, value_name = "CMD")]
+    exec: Option<String>,
+
+    /// Show the departures of all stops in one list, soonest first
+    #[clap(long, env = "BVG_DEPARTURES_MERGED")]
+    merged: bool,
+
+    /// One row per line and direction with the next few departures, e.g. `U6 → Alt-Tegel: 2, 9,
+    /// 17 min`
+    #[clap(long, env = "BVG_DEPARTURES_GROUPED")]
+    grouped: bool,
+
+    /// Show the arrivals of each stop next to its departures, e.g. to pick someone up. Implies
+    /// `--tui false`.
+    #[clap(
+        long,
+        env = "BVG_DEPARTURES_WITH_ARRIVALS",
+        conflicts_with_all = ["porcelain", "merged", "grouped", "format", "status_file"]
+    )]
+    with_arrivals: bool,
+
+    /// Maximum number of TUI redraws per second. The screen is only redrawn if its content changed.
+    #[clap(long, default_value_t = 10, env = "BVG_DEPARTURES_MAX_FPS")]
+    max_fps: u32,
+
+    /// Keep the screen from blanking while the TUI is running, e.g. on a wall display.
+    /// Needs `systemd-inhibit`.
+    #[clap(long, env = "BVG_DEPARTURES_INHIBIT_SCREENSAVER")]
+    inhibit_screensaver: bool,
+
+    /// Show a small board in a window that stays on top, instead of the TUI
+    #[cfg(feature = "overlay")]
+    #[clap(
+        long,
+        env = "BVG_DEPARTURES_OVERLAY",
+        conflicts_with_all = ["with_arrivals", "status_file"]
+    )]
+    overlay: bool,
+
+    /// Seconds between refreshes in continuous modes
+    #[clap(
+        long,
+        value_name = "SECONDS",
+        default_value_t = 30,
+        env = "BVG_DEPARTURES_REFRESH_INTERVAL"
+    )]
+    refresh_interval: u64,
+
+    /// File the board is written to as JSON on SIGUSR1 in the TUI and with `--status-file`, `-` for
+    /// stderr except in the TUI [default: bvg-departures-<pid>-snapshot.json in the temp dir]
+    #[clap(long, value_name = "PATH", env = "BVG_DEPARTURES_SNAPSHOT_FILE")]
+    snapshot_file: Option<PathBuf>,
+
+    /// Continuously write a single status line to this file, e.g. for i3status or polybar.
+    /// Implies `--tui false`.
+    #[clap(long, value_name = "PATH")]
+    status_file: Option<PathBuf>,
+
+    /// Template for each departure in the status line. Placeholders: {station}, {line},
+    /// {direction}, {time}, {countdown}, {delay}
+    #[clap(long, default_value = "{line} {countdown}", requires = "status_file")]
+    status_template: String,
+
+    /// Number of departures in the status line
+    #[clap(long, default_value_t = 3, requires = "status_file")]
+    status_count: usize,
+
+    /// Markup for line colors in the status line
+    #[clap(long, value_enum, default_value_t = StatusMarkup::Plain, requires = "status_file")]
+    status_markup: StatusMarkup,
+
+    /// Max width of the status line in characters. The delay, then the time, then further
+    /// departures are left out to fit.
+    #[clap(long, value_name = "CHARS", requires = "status_file")]
+    status_max_width: Option<usize>,
+}
+
+impl BoardArgs {
+    #[cfg(feature = "overlay")]
+    fn overlay(&self) -> bool {
+        self.overlay
+    }
+
+    #[cfg(not(feature = "overlay"))]
+    fn overlay(&self) -> bool {
+        false
+    }
+
+    fn use_tui(&self) -> bool {
+        self.tui
+            && !self.porcelain
+            && !self.with_arrivals
+            && self.format == OutputFormat::Text
+            && !self.overlay()
+            && self.status_file.is_none()
+            && std::io::stdout().is_terminal()
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Show the departures of the stops in the config. The default without a subcommand.
+    Board(BoardArgs),
+    /// Check a config file and the files it refers to, e.g. before deploying it to a wall display
+    Validate(ValidateArgs),
+    /// Create a config file by searching stops by name
+    Init(InitArgs),
+    /// Print config entries for the stops nearest to the places in a GeoJSON file
+    ImportStops(ImportStopsArgs),
+    /// Find stops by name and print their ids for the config
+    Search(SearchArgs),
+    /// Print the stops nearest to a place with their distances and ids, for the config
+    Nearby(NearbyArgs),
+    /// Print the next connections between two stops, given by name or id
+    #[clap(alias = "journeys")]
+    Journey(JourneyArgs),
+    /// Print JSON Schemas, e.g. for editor completion via yaml-language-server
+    Schema {
+        #[clap(subcommand)]
+        kind: SchemaKind,
+    },
+    /// Manage the stops config file
+    Config {
+        #[clap(subcommand)]
+        action: ConfigAction,
+    },
+    /// Answer GraphQL queries for the departures of the stops in the config, e.g. for dashboards
+    #[cfg(feature = "graphql")]
+    Serve(ServeArgs),
+}
+
+#[derive(Args, Debug)]
+struct ValidateArgs {
+    /// Config file to check
+    #[clap(default_value = DEFAULT_CONFIG)]
+    file: PathBuf,
+}
+
+#[cfg(feature = "graphql")]
+#[derive(Args, Debug)]
+struct ServeArgs {
+    /// Config file with the stops
+    #[clap(default_value = DEFAULT_CONFIG)]
+    path: PathBuf,
+    /// Address to listen on, the endpoint is at `/graphql` and the health check at `/healthz`
+    #[clap(long, default_value = "127.0.0.1:8080")]
+    listen: std::net::SocketAddr,
+    /// Only answer requests with `Authorization: Bearer <TOKEN>`, or `--basic-auth`
+    #[clap(long, value_name = "TOKEN", env = "BVG_DEPARTURES_SERVE_TOKEN")]
+    token: Option<String>,
+    /// Only answer requests with these credentials, or `--token`. Browsers ask for them
+    #[clap(
+        long,
+        value_name = "USER:PASSWORD",
+        env = "BVG_DEPARTURES_SERVE_BASIC_AUTH",
+        value_parser = parse_basic_auth
+    )]
+    basic_auth: Option<(String, String)>,
+    /// Web pages at this origin may call the endpoints, e.g. `http://dashboard.local:3000`.
+    /// Repeat or separate with commas for more
+    #[clap(
+        long,
+        value_name = "ORIGIN",
+        env = "BVG_DEPARTURES_CORS_ORIGINS",
+        value_delimiter = ','
+    )]
+    cors_origin: Vec<String>,
+}
+
+#[cfg(feature = "graphql")]
+fn parse_basic_auth(value: &str) -> Result<(String, String), String> {
+    value
+        .split_once(':')
+        .map(|(user, password)| (user.to_string(), password.to_string()))
+        .ok_or_else(|| "expected USER:PASSWORD".to_string())
+}
+
+#[derive(Args, Debug)]
+struct InitArgs {
+    /// Config file to create
+    #[clap(default_value = DEFAULT_CONFIG)]
+    file: PathBuf,
+    /// Overwrite the file if it exists
+    #[clap(long)]
+    force: bool,
+}
+
+#[derive(Args, Debug)]
+struct ImportStopsArgs {
+    /// GeoJSON file with Point features, e.g. exported saved places
+    file: PathBuf,
+}
+
+#[derive(Args, Debug)]
+struct SearchArgs {
+    /// Name of the stop, e.g. "alexanderplatz"
+    query: String,
+    /// Max number of stops
+    #[clap(long, default_value_t = 10)]
+    results: u32,
+}
+
+#[derive(Args, Debug)]
+struct NearbyArgs {
+    /// Coordinates like "52.5219,13.4132" or an address, e.g. "Karl-Marx-Str. 1"
+    place: String,
+    /// Max number of stops
+    #[clap(long, default_value_t = 8)]
+    results: u32,
+    /// Max walking distance in meters
+    #[clap(long)]
+    distance: Option<u32>,
+    /// Config file with settings like `geocoder`, used if it exists
+    #[clap(long, default_value = DEFAULT_CONFIG)]
+    config: PathBuf,
+}
+
+#[derive(Args, Debug)]
+struct JourneyArgs {
+    /// Where to start, e.g. "Alexanderplatz" or "900100003"
+    from: String,
+    /// Where to go
+    to: String,
+    /// Max number of connections
+    #[clap(long, default_value_t = 3)]
+    results: u32,
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigAction {
+    /// Upgrade a config file to the current format and print the changes
+    Migrate {
+        /// Config file to upgrade
+        #[clap(default_value = DEFAULT_CONFIG)]
+        file: PathBuf,
+        /// Write the upgraded config back to the file
+        #[clap(long)]
+        write: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum SchemaKind {
+    /// Schema of the stops config file
+    Config,
 }
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    let args = Cli::parse();
+async fn main() {
+    if let Err(e) = run().await {
+        // config errors point at the offending value, wrapped in where the file was included from
+        let contexts: Vec<String> = e
+            .chain()
+            .take_while(|cause| !cause.is::<Diagnostic>())
+            .map(ToString::to_string)
+            .collect();
+        match e.downcast::<Diagnostic>() {
+            Ok(diagnostic) => {
+                for context in contexts {
+                    eprintln!("{}:", context);
+                }
+                eprintln!("{:?}", miette::Report::new(diagnostic));
+            }
+            Err(e) => eprintln!("Error: {:?}", e),
+        }
+        std::process::exit(1);
+    }
+}
 
-    let log_buffer = if args.tui {
+async fn run() -> anyhow::Result<()> {
+    let args = Cli::parse_checked();
+
+    if args.version {
+        let info = BuildInfo::current();
+        if args.json {
+            println!("{}", serde_json::to_string_pretty(&info)?);
+        } else {
+            println!("{}", info);
+        }
+        return Ok(());
+    }
+
+    let global = args.global;
+    let command = args.command.unwrap_or(Command::Board(args.board));
+    let use_tui = matches!(&command, Command::Board(board) if board.use_tui());
+
+    let log_buffer = if use_tui {
         let log_buffer = LogBuffer::new(8);
-        let subscriber = tracing_subscriber::fmt()
-            .with_writer(log_buffer.make_writer())
-            .with_ansi(false)
-            .finish();
+        let request_log = RequestLog::new(REQUEST_LOG_SIZE);
+        // the request log is kept even with --quiet, it only shows up when asked for
+        let logs = (!global.quiet).then(|| {
+            tracing_subscriber::fmt::layer()
+                .with_writer(log_buffer.make_writer())
+                .with_ansi(false)
+                .with_filter(LevelFilter::INFO)
+        });
+        let requests = request_log
+            .clone()
+            .with_filter(filter_fn(|m| m.target() == request_log::TARGET));
+        let subscriber = tracing_subscriber::registry().with(logs).with(requests);
         tracing::subscriber::set_global_default(subscriber)?;
-        Some(log_buffer)
+        Some((log_buffer, request_log))
     } else {
-        // construct a subscriber that prints formatted traces to stdout
-        let subscriber = tracing_subscriber::FmtSubscriber::new();
-        // use that subscriber to process traces emitted after this point
-        tracing::subscriber::set_global_default(subscriber)?;
+        if !global.quiet {
+            // logs always go to stderr so stdout only carries the departures
+            let subscriber = tracing_subscriber::fmt()
+                .with_writer(std::io::stderr)
+                .with_ansi(std::io::stderr().is_terminal())
+                .finish();
+            tracing::subscriber::set_global_default(subscriber)?;
+        }
         None
     };
 
-    info!("Starting with {}", args.path.display());
+    match command {
+        Command::Board(board) => show_board(&global, board, log_buffer).await,
+        Command::Validate(ValidateArgs { file }) => {
+            let stops = InputStops::load(&file)?;
+            warn_if_outdated(&file, &stops);
+            println!("{}: OK, stops: {}", file.display(), stops.stops.len());
+            Ok(())
+        }
+        Command::Init(InitArgs { file, force }) => {
+            init::init_config(&global.api_client(None)?, &file, force).await
+        }
+        Command::ImportStops(ImportStopsArgs { file }) => {
+            let stops = import::import_stops(&global.api_client(None)?, &file).await?;
+            print!("{}", serde_yaml::to_string(&stops)?);
+            Ok(())
+        }
+        Command::Search(SearchArgs { query, results }) => {
+            let stops = global
+                .api_client(None)?
+                .search_stops(&query, results)
+                .await?;
+            if stops.is_empty() {
+                warn!("No stops found for '{}'", query);
+            }
+            for stop in stops {
+                println!(
+                    "{}\t{}",
+                    stop.id.unwrap_or_default(),
+                    stop.name.unwrap_or_default()
+                );
+            }
+            Ok(())
+        }
+        Command::Nearby(NearbyArgs {
+            place,
+            results,
+            distance,
+            config,
+        }) => {
+            // only for settings, `nearby` also works without a config
+            let config = if config.exists() {
+                Some(InputStops::load(&config)?)
+            } else {
+                None
+            };
+            let client = global.api_client(config.as_ref())?;
+            let geocoder = config
+                .and_then(|c| c.geocoder)
+                .unwrap_or_default()
+                .geocoder(&client);
+            let point = locate::resolve_place(geocoder.as_ref(), &place).await?;
+            let params = NearbyParams {
+                results: Some(results),
+                distance,
+                stops: Some(true),
+                poi: Some(false),
+                ..Default::default()
+            };
+            let stops = client
+                .nearby_stops(point.latitude, point.longitude, &params)
+                .await?;
+            if stops.is_empty() {
+                warn!("No stops found near {}", place);
+            }
+            for stop in stops
+                .into_iter()
+                .filter(|s| s.kind() == LocationKind::Stop && s.id.is_some())
+            {
+                println!(
+                    "{}m\t{}\t{}",
+                    stop.distance.unwrap_or_default(),
+                    stop.id.unwrap_or_default(),
+                    stop.name.unwrap_or_default()
+                );
+            }
+            Ok(())
+        }
+        Command::Journey(JourneyArgs { from, to, results }) => {
+            let client = global.api_client(None)?;
+            let from = resolve::resolve_stop(&client, &from).await?;
+            let to = resolve::resolve_stop(&client, &to).await?;
+            let params = JourneysParams {
+                results: Some(results),
+                language: global.language.clone(),
+                ..Default::default()
+            };
+            let journeys = client.get_journeys(&from, &to, &params).await?;
+            if journeys.is_empty() {
+                warn!("No connections found");
+            }
+            print!("{}", format_journeys(&journeys));
+            Ok(())
+        }
+        Command::Schema {
+            kind: SchemaKind::Config,
+        } => {
+            let schema = schemars::schema_for!(InputStops);
+            println!("{}", serde_json::to_string_pretty(&schema)?);
+            Ok(())
+        }
+        Command::Config {
+            action: ConfigAction::Migrate { file, write },
+        } => migrate::migrate_config(&file, write),
+        #[cfg(feature = "graphql")]
+        Command::Serve(ServeArgs {
+            path,
+            listen,
+            token,
+            basic_auth,
+            cors_origin,
+        }) => {
+            let mut stops = InputStops::load(&path)?;
+            let api_client = global.api_client(Some(&stops))?;
+            resolve::resolve_stop_ids(&api_client, &mut stops).await?;
+            let access = bvg_departures::graphql::Access {
+                bearer_token: token,
+                basic_auth,
+                cors_origins: cors_origin,
+            };
+            let router = bvg_departures::graphql::router(api_client, stops, Some(path), access)?;
+            bvg_departures::graphql::serve(router, listen).await
+        }
+    }
+}
+
+/// Suggests `config migrate` if the config is read differently than it was written for.
+fn warn_if_outdated(path: &Path, stops: &InputStops) {
+    if migrate::is_outdated(stops.version) {
+        warn!(
+            "{} uses config version {}, run `bvg-departures config migrate` to upgrade it",
+            path.display(),
+            stops.version
+        );
+    }
+}
 
-    let stops: InputStops = serde_yaml::from_str(&fs::read_to_string(args.path)?)?;
+/// The board of the config, or of the stops nearby with `--here`, in the chosen view.
+async fn show_board(
+    global: &GlobalArgs,
+    args: BoardArgs,
+    log_buffer: Option<(LogBuffer, RequestLog)>,
+) -> anyhow::Result<()> {
+    let use_tui = args.use_tui();
+    let mut stops = if args.here {
+        info!("Starting with the stops nearby");
+        locate::nearby_config(&global.api_client(None)?, NEARBY_STOPS).await?
+    } else {
+        info!("Starting with {}", args.path.display());
+        let stops = InputStops::load(&args.path)?;
+        warn_if_outdated(&args.path, &stops);
+        stops
+    };
+    let api_client = global.api_client(Some(&stops))?;
+    resolve::resolve_stop_ids(&api_client, &mut stops).await?;
+    if args.with_arrivals {
+        stops = stops.with_arrivals();
+    }
+
+    let exec = args.exec.clone().map(ExecHook::new);
+
+    #[cfg(feature = "overlay")]
+    if args.overlay {
+        return bvg_departures::view::overlay::OverlayDisplayBuilder::<BvgClient>::default()
+            .stops(stops)
+            .api_client(api_client)
+            .refresh_interval(Duration::from_secs(args.refresh_interval))
+            .build()?
+            .display()
+            .await;
+    }
+
+    // only the continuous views keep a board around
+    let snapshot = if use_tui || args.status_file.is_some() {
+        let snapshot = BoardSnapshot::default();
+        let target = match &args.snapshot_file {
+            // the TUI draws over stderr
+            Some(path) if path.as_os_str() == "-" && use_tui => anyhow::bail!(
+                "--snapshot-file - writes to stderr, which the TUI draws over; pass a file or --tui false"
+            ),
+            Some(path) if path.as_os_str() == "-" => None,
+            Some(path) => Some(path.clone()),
+            // not bvg-departures-<pid>.json, that is the board file of --exec
+            None => Some(std::env::temp_dir().join(format!(
+                "bvg-departures-{}-snapshot.json",
+                std::process::id()
+            ))),
+        };
+        snapshot.listen(target)?;
+        Some(snapshot)
+    } else {
+        None
+    };
 
-    let display: Box<dyn ResultDisplay> = if args.tui {
-        let log_buffer = log_buffer.expect("log buffer for tui");
+    // the display takes the client, the cache is saved once it returns
+    let cache = api_client.clone();
+    let display: Box<dyn ResultDisplay> = if let Some(path) = args.status_file.clone() {
+        Box::new(
+            StatusFileDisplayBuilder::<BvgClient>::default()
+                .stops(stops)
+                .api_client(api_client)
+                .path(path)
+                .template(args.status_template.clone())
+                .count(args.status_count)
+                .markup(args.status_markup)
+                .max_width(args.status_max_width)
+                .snapshot(snapshot)
+                .exec(exec)
+                .interval(Duration::from_secs(args.refresh_interval))
+                .build()?,
+        )
+    } else if args.format == OutputFormat::Json {
+        Box::new(
+            JsonDisplayBuilder::<BvgClient>::default()
+                .stops(stops)
+                .api_client(api_client)
+                .exec(exec)
+                .build()?,
+        )
+    } else if args.format == OutputFormat::Csv {
+        Box::new(
+            CsvDisplayBuilder::<BvgClient>::default()
+                .stops(stops)
+                .api_client(api_client)
+                .exec(exec)
+                .build()?,
+        )
+    } else if args.format == OutputFormat::PlainVerbose {
+        Box::new(
+            VerboseDisplayBuilder::<BvgClient>::default()
+                .stops(stops)
+                .api_client(api_client)
+                .exec(exec)
+                .build()?,
+        )
+    } else if use_tui {
+        let (log_buffer, request_log) = log_buffer.expect("log buffer for tui");
+        let base_url = api_client.base_url().clone();
         Box::new(
             TuiDisplayBuilder::<BvgClient>::default()
                 .stops(stops)
-                .api_client(BvgClient::default())
+                .api_client(api_client)
                 .log_buffer(log_buffer)
+                .exec(exec)
+                .max_fps(args.max_fps)
+                .refresh_interval(Duration::from_secs(args.refresh_interval))
+                .merged(args.merged)
+                .grouped(args.grouped)
+                .snapshot(snapshot)
+                .request_log(Some(request_log))
+                .base_url(Some(base_url))
                 .build()?,
         )
     } else {
         Box::new(
             StdoutDisplayBuilder::<BvgClient>::default()
                 .stops(stops)
-                .api_client(BvgClient::default())
+                .api_client(api_client)
+                .porcelain(args.porcelain)
+                .decorate(!args.porcelain && std::io::stdout().is_terminal())
+                .exec(exec)
+                .merged(args.merged)
+                .grouped(args.grouped)
+                .with_arrivals(args.with_arrivals)
                 .build()?,
         )
     };
 
+    // held until the display returns
+    let _inhibitor = if use_tui && args.inhibit_screensaver {
+        ScreensaverInhibitor::start()
+    } else {
+        None
+    };
+
     display.display().await?;
+    cache.save_cache().await;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rejected(args: &[&str]) -> Option<ErrorKind> {
+        let args = std::iter::once("bvg-departures").chain(args.iter().copied());
+        Cli::try_parse_checked_from(args).err().map(|e| e.kind())
+    }
+
+    #[test]
+    fn rejects_conflicting_flags() {
+        assert_eq!(rejected(&["--merged"]), None);
+        assert_eq!(
+            rejected(&["--merged", "validate"]),
+            Some(ErrorKind::ArgumentConflict)
+        );
+        #[cfg(feature = "overlay")]
+        for other in [&["--status-file", "status.txt"][..], &["--with-arrivals"]] {
+            let args = [&["--overlay"][..], other].concat();
+            assert_eq!(rejected(&args), Some(ErrorKind::ArgumentConflict));
+        }
+    }
+}
@@ -0,0 +1,182 @@
+//! Upgrades config files written for older versions of the config format.
+//!
+//! Migrations edit the text of the file instead of re-serializing it, so comments and formatting
+//! are kept.
+
+use crate::InputStops;
+use anyhow::{bail, Context};
+use serde_yaml::Value;
+use similar::TextDiff;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// Version of the config format written by this build.
+pub const CONFIG_VERSION: u32 = 1;
+
+struct Migration {
+    /// Version the migration upgrades from, it results in `from + 1`
+    from: u32,
+    description: &'static str,
+    /// Whether files without it are read differently than intended, and worth a warning
+    changes_schema: bool,
+    apply: fn(&str) -> anyhow::Result<String>,
+}
+
+const MIGRATIONS: &[Migration] = &[Migration {
+    from: 0,
+    description: "add the version key",
+    changes_schema: false,
+    apply: add_version_key,
+}];
+
+/// Whether a config at `version` misses migrations that change what it means, not just the
+/// version key.
+pub fn is_outdated(version: u32) -> bool {
+    MIGRATIONS
+        .iter()
+        .any(|m| m.from >= version && m.changes_schema)
+}
+
+/// Version of a config file. Files without a `version` key predate versioning.
+pub fn config_version(content: &str) -> anyhow::Result<u32> {
+    let value: Value = serde_yaml::from_str(content)?;
+    match value.get("version") {
+        None => Ok(0),
+        Some(v) => v
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .with_context(|| format!("Invalid config version {:?}", v)),
+    }
+}
+
+/// Upgrades a config file and the files it includes to [`CONFIG_VERSION`] and prints a diff of
+/// the changes. The files are only changed if `write` is set.
+pub fn migrate_config(path: &Path, write: bool) -> anyhow::Result<()> {
+    let mut files = vec![];
+    read_with_includes(path, &mut files)?;
+    let mut migrated = HashMap::new();
+    for (canonical, file, original) in &files {
+        if let Some(content) = migrate(file, original)? {
+            migrated.insert(canonical.clone(), content);
+        }
+    }
+    if migrated.is_empty() {
+        info!(
+            "{} is already at version {}",
+            path.display(),
+            CONFIG_VERSION
+        );
+        return Ok(());
+    }
+
+    // make sure the result is still a valid config, with the upgraded files
+    InputStops::load_with(path, |file| match migrated.get(&file.canonicalize()?) {
+        Some(content) => Ok(content.clone()),
+        None => std::fs::read_to_string(file),
+    })
+    .context("Migrated config is invalid, please report this")?;
+
+    for (canonical, file, original) in &files {
+        let Some(content) = migrated.get(canonical) else {
+            continue;
+        };
+        let name = file.display().to_string();
+        print!(
+            "{}",
+            TextDiff::from_lines(original, content)
+                .unified_diff()
+                .header(&name, &name)
+        );
+        if write {
+            std::fs::write(file, content)
+                .with_context(|| format!("Could not write {}", file.display()))?;
+            info!("Wrote {} at version {}", file.display(), CONFIG_VERSION);
+        }
+    }
+    if !write {
+        info!("Nothing written, run again with --write to apply");
+    }
+    Ok(())
+}
+
+/// Adds a config file and the files it includes to `files`, each once, with its canonical path,
+/// its path as given and its content.
+fn read_with_includes(
+    path: &Path,
+    files: &mut Vec<(PathBuf, PathBuf, String)>,
+) -> anyhow::Result<()> {
+    let canonical = path
+        .canonicalize()
+        .with_context(|| format!("Could not read {}", path.display()))?;
+    if files.iter().any(|(seen, _, _)| *seen == canonical) {
+        return Ok(());
+    }
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Could not read {}", path.display()))?;
+    let includes: Vec<PathBuf> = match serde_yaml::from_str::<Value>(&content)?.get("include") {
+        Some(includes) => serde_yaml::from_value(includes.clone())
+            .with_context(|| format!("Invalid include list in {}", path.display()))?,
+        None => vec![],
+    };
+    files.push((canonical, path.to_path_buf(), content));
+    let dir = path.parent().unwrap_or(Path::new("."));
+    for include in includes {
+        read_with_includes(&dir.join(include), files)?;
+    }
+    Ok(())
+}
+
+/// The upgraded content of a config file, None if it is at [`CONFIG_VERSION`] already.
+fn migrate(path: &Path, original: &str) -> anyhow::Result<Option<String>> {
+    let mut version = config_version(original)
+        .with_context(|| format!("Could not read the version of {}", path.display()))?;
+    if version > CONFIG_VERSION {
+        bail!(
+            "{} has version {}, but this build only knows up to version {}",
+            path.display(),
+            version,
+            CONFIG_VERSION
+        );
+    }
+    if version == CONFIG_VERSION {
+        return Ok(None);
+    }
+
+    let mut content = original.to_string();
+    for migration in MIGRATIONS {
+        if migration.from < version {
+            continue;
+        }
+        info!(
+            "Migrating {} from version {}: {}",
+            path.display(),
+            migration.from,
+            migration.description
+        );
+        content = (migration.apply)(&content)?;
+        version = migration.from + 1;
+    }
+    debug_assert_eq!(version, CONFIG_VERSION);
+    Ok(Some(content))
+}
+
+/// Inserts `version: 1` in front of the first key, after leading comments such as the
+/// yaml-language-server schema line.
+fn add_version_key(content: &str) -> anyhow::Result<String> {
+    let mut out = String::with_capacity(content.len() + 12);
+    let mut inserted = false;
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim();
+        let preamble = trimmed.is_empty() || trimmed.starts_with('#') || trimmed == "---";
+        if !inserted && !preamble {
+            out.push_str("version: 1\n");
+            inserted = true;
+        }
+        out.push_str(line);
+    }
+    if !inserted {
+        bail!("Config is empty");
+    }
+    Ok(out)
+}
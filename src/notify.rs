@@ -0,0 +1,139 @@
+use crate::schedule::Schedule;
+use crate::InputStops;
+use chrono::{Duration, NaiveDateTime};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::process::Stdio;
+use tokio::process::Command;
+use tracing::{debug, warn};
+
+/// How urgent a desktop notification is, mapped to the urgency levels of `notify-send`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Urgency {
+    Normal,
+    Critical,
+}
+
+impl Urgency {
+    fn as_str(self) -> &'static str {
+        match self {
+            Urgency::Normal => "normal",
+            Urgency::Critical => "critical",
+        }
+    }
+}
+
+/// Shows a desktop notification via `notify-send`. Does not wait for it, failures are only logged.
+pub fn notify(urgency: Urgency, summary: &str, body: &str) {
+    debug!("Notifying '{}'", summary);
+    let spawned = Command::new("notify-send")
+        .arg("--app-name=bvg-departures")
+        .arg(format!("--urgency={}", urgency.as_str()))
+        .arg(summary)
+        .arg(body)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    if let Err(e) = spawned {
+        warn!("Could not send notification '{}': {}", summary, e);
+    }
+}
+
+/// Sends the alerts of the watchers as notifications, keeping quiet outside `watch_only` and in
+/// `quiet_hours` of the config.
+///
+/// Alerts are told apart by the trip id or line they are about and their rule, e.g. "late". The
+/// same alert is only sent again after `notify_cooldown_minutes`, however often it shows up.
+#[derive(Debug)]
+pub struct Notifier {
+    watch_only: Option<Schedule>,
+    quiet_hours: Option<Schedule>,
+    cooldown: Duration,
+    /// When each alert was last sent, by trip id or line and rule
+    sent: HashMap<(String, &'static str), NaiveDateTime>,
+}
+
+impl Notifier {
+    pub fn new(stops: &InputStops) -> Self {
+        Self {
+            watch_only: stops.watch_only.clone(),
+            quiet_hours: stops.quiet_hours.clone(),
+            cooldown: Duration::minutes(i64::from(stops.notify_cooldown_minutes)),
+            sent: HashMap::new(),
+        }
+    }
+
+    /// Notifies about `key`, a trip id or line, unless it is quiet at `now` or the same alert was
+    /// sent within the cooldown.
+    pub fn send(
+        &mut self,
+        key: &str,
+        rule: &'static str,
+        urgency: Urgency,
+        summary: &str,
+        now: NaiveDateTime,
+    ) {
+        if self.allows(key, rule, now) {
+            notify(urgency, summary, "bvg-departures");
+        }
+    }
+
+    /// Whether an alert may be sent, remembering it as sent if so.
+    fn allows(&mut self, key: &str, rule: &'static str, now: NaiveDateTime) -> bool {
+        let quiet = self.watch_only.as_ref().is_some_and(|s| !s.is_active(now))
+            || self.quiet_hours.as_ref().is_some_and(|s| s.is_active(now));
+        if quiet {
+            return false;
+        }
+        match self.sent.entry((key.to_string(), rule)) {
+            Entry::Occupied(last) if now - *last.get() < self.cooldown => false,
+            Entry::Occupied(mut last) => {
+                last.insert(now);
+                true
+            }
+            Entry::Vacant(last) => {
+                last.insert(now);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    /// 2024-01-01 is a Monday
+    fn at(hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 1, 1)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn keeps_quiet_hours_and_cooldowns() {
+        let stops: InputStops = serde_yaml::from_str(
+            "quiet_hours: '22:00-07:00'\nnotify_cooldown_minutes: 10\nstops: []\n",
+        )
+        .unwrap();
+        let mut notifier = Notifier::new(&stops);
+
+        assert!(!notifier.allows("t1", "late", at(6, 59)));
+        // kept quiet, so not counted for the cooldown
+        assert!(notifier.allows("t1", "late", at(7, 0)));
+        assert!(!notifier.allows("t1", "late", at(7, 9)));
+        assert!(notifier.allows("t1", "cancelled", at(7, 9)));
+        assert!(notifier.allows("t2", "late", at(7, 9)));
+        assert!(notifier.allows("t1", "late", at(7, 10)));
+        assert!(!notifier.allows("t1", "late", at(22, 30)));
+
+        let stops: InputStops =
+            serde_yaml::from_str("watch_only: '07:00-09:00'\nstops: []\n").unwrap();
+        let mut notifier = Notifier::new(&stops);
+        assert!(notifier.allows("t1", "late", at(8, 0)));
+        assert!(!notifier.allows("t2", "late", at(9, 0)));
+    }
+}
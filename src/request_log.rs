@@ -0,0 +1,136 @@
+//! Recent requests to the API, collected from tracing events, so that flaky responses can be
+//! seen in the TUI without leaving it.
+
+use chrono::{DateTime, Local};
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// Target of the event the API client emits after each request for departures, with the fields
+/// `stop`, `status`, `latency_ms` and `retries`.
+pub const TARGET: &str = "bvg_departures::requests";
+
+/// One request for the departures of a stop, including its retries.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestRecord {
+    pub time: DateTime<Local>,
+    pub stop: String,
+    /// HTTP status, or why there was none, e.g. "timeout"
+    pub status: String,
+    /// Until the last response, including retries
+    pub latency_ms: u64,
+    pub retries: u64,
+}
+
+impl fmt::Display for RequestRecord {
+    /// e.g. "12:03:04  Alexanderplatz  503  1204ms  2 retries"
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}  {}  {}  {}ms",
+            self.time.format("%H:%M:%S"),
+            self.stop,
+            self.status,
+            self.latency_ms
+        )?;
+        match self.retries {
+            0 => Ok(()),
+            1 => write!(f, "  1 retry"),
+            n => write!(f, "  {} retries", n),
+        }
+    }
+}
+
+/// Tracing layer keeping the last requests. Clones share them. Only needs the events of
+/// [`TARGET`], so it is best added with a filter for them.
+#[derive(Debug, Clone)]
+pub struct RequestLog {
+    records: Arc<Mutex<VecDeque<RequestRecord>>>,
+    max_records: usize,
+}
+
+impl RequestLog {
+    pub fn new(max_records: usize) -> Self {
+        Self {
+            records: Arc::new(Mutex::new(VecDeque::with_capacity(max_records))),
+            max_records,
+        }
+    }
+
+    /// Oldest first
+    pub fn records(&self) -> Vec<RequestRecord> {
+        self.records.lock().unwrap().iter().cloned().collect()
+    }
+
+    fn push(&self, record: RequestRecord) {
+        let mut records = self.records.lock().unwrap();
+        records.push_back(record);
+        while records.len() > self.max_records {
+            records.pop_front();
+        }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for RequestLog {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        if event.metadata().target() != TARGET {
+            return;
+        }
+        let mut record = RequestRecord {
+            time: Local::now(),
+            stop: String::new(),
+            status: String::new(),
+            latency_ms: 0,
+            retries: 0,
+        };
+        event.record(&mut record);
+        self.push(record);
+    }
+}
+
+impl Visit for RequestRecord {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        match field.name() {
+            "latency_ms" => self.latency_ms = value,
+            "retries" => self.retries = value,
+            _ => {}
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "stop" => self.stop = value.to_string(),
+            "status" => self.status = value.to_string(),
+            _ => {}
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.record_str(field, &format!("{:?}", value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn keeps_the_last_requests() {
+        let log = RequestLog::new(2);
+        let subscriber = tracing_subscriber::registry().with(log.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            for stop in ["A", "B", "C"] {
+                tracing::debug!(target: TARGET, stop, status = "503", latency_ms = 120u64, retries = 2u64);
+            }
+            tracing::info!("not a request");
+        });
+        let records = log.records();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].stop, "B");
+        assert!(records[1].to_string().ends_with("C  503  120ms  2 retries"));
+    }
+}
@@ -0,0 +1,148 @@
+//! Stops configured by name only get their id from the locations endpoint at startup.
+
+use crate::api::locations::Location;
+use crate::api::BvgClient;
+use crate::InputStops;
+use anyhow::{bail, Context};
+use std::collections::BTreeMap;
+use std::path::Path;
+use tracing::{debug, info, warn};
+
+/// Number of search results considered per name
+const CANDIDATES: u32 = 5;
+
+/// Fills in the ids of stops that only have a name. Ids found before are read from the
+/// `id_cache` file, new ones are added to it.
+pub async fn resolve_stop_ids(client: &BvgClient, stops: &mut InputStops) -> anyhow::Result<()> {
+    if stops.stops.iter().all(|s| !s.id.is_empty()) {
+        return Ok(());
+    }
+
+    let mut cache = match &stops.id_cache {
+        Some(path) => read_cache(path)?,
+        None => BTreeMap::new(),
+    };
+    let mut changed = false;
+    for stop in stops.stops.iter_mut().filter(|s| s.id.is_empty()) {
+        if let Some(id) = cache.get(&stop.name) {
+            debug!("Using cached id {} for {}", id, stop.name);
+            stop.id = id.clone();
+            continue;
+        }
+
+        stop.id = resolve_stop(client, &stop.name).await?;
+        cache.insert(stop.name.clone(), stop.id.clone());
+        changed = true;
+    }
+
+    if let Some(path) = stops.id_cache.as_ref().filter(|_| changed) {
+        // the ids are still valid for this run, even if they cannot be saved
+        if let Err(e) = write_cache(path, &cache) {
+            warn!("Could not write {}: {:#}", path.display(), e);
+        }
+    }
+    Ok(())
+}
+
+/// Id of the stop called `name`. Ids are passed through as they are.
+pub async fn resolve_stop(client: &BvgClient, name: &str) -> anyhow::Result<String> {
+    if !name.is_empty() && name.chars().all(|c| c.is_ascii_digit()) {
+        return Ok(name.to_string());
+    }
+    let candidates = client.search_stops(name, CANDIDATES).await?;
+    let id = best_match(name, &candidates)
+        .with_context(|| format!("Could not resolve stop {:?}", name))?;
+    info!("Resolved stop {} to id {}", name, id);
+    Ok(id.to_string())
+}
+
+/// The only search result whose name matches, or the only result at all.
+fn best_match<'a>(name: &str, candidates: &'a [Location]) -> anyhow::Result<&'a str> {
+    let wanted = normalize(name);
+    let exact: Vec<&Location> = candidates
+        .iter()
+        .filter(|l| l.name.as_deref().map(normalize).as_ref() == Some(&wanted))
+        .collect();
+    let matches = match (exact.len(), candidates) {
+        (1.., _) => exact,
+        (0, [only]) => vec![only],
+        _ => candidates.iter().collect(),
+    };
+    match matches.as_slice() {
+        [] => bail!("no stop found"),
+        [only] => Ok(only.id.as_deref().expect("stop with id")),
+        many => {
+            let list: Vec<String> = many
+                .iter()
+                .map(|l| {
+                    format!(
+                        "  {}\t{}",
+                        l.id.as_deref().unwrap_or_default(),
+                        l.name.as_deref().unwrap_or_default()
+                    )
+                })
+                .collect();
+            bail!(
+                "the name is ambiguous, use the full name or set the id of one of:\n{}",
+                list.join("\n")
+            )
+        }
+    }
+}
+
+/// Compares names without the "S+U" prefixes and "(Berlin)" suffixes the API adds.
+fn normalize(name: &str) -> String {
+    let name = name.trim().to_lowercase();
+    let name = name.strip_suffix("(berlin)").unwrap_or(&name).trim();
+    let name = name.strip_suffix("bhf").unwrap_or(name).trim();
+    ["s+u ", "s ", "u "]
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+        .unwrap_or(name)
+        .trim()
+        .to_string()
+}
+
+fn read_cache(path: &Path) -> anyhow::Result<BTreeMap<String, String>> {
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Could not read {}", path.display()))?;
+    serde_yaml::from_str(&content).with_context(|| format!("Invalid id cache {}", path.display()))
+}
+
+fn write_cache(path: &Path, cache: &BTreeMap<String, String>) -> anyhow::Result<()> {
+    let content = format!(
+        "# Stop ids resolved by bvg-departures, by name in the config\n{}",
+        serde_yaml::to_string(cache)?
+    );
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stop(id: &str, name: &str) -> Location {
+        serde_json::from_value(serde_json::json!({"type": "stop", "id": id, "name": name})).unwrap()
+    }
+
+    #[test]
+    fn picks_the_stop_matching_the_name() {
+        let candidates = [
+            stop("900100003", "S+U Alexanderplatz Bhf (Berlin)"),
+            stop("900100026", "S+U Alexanderplatz Bhf/Gontardstr. (Berlin)"),
+        ];
+        assert_eq!(
+            best_match("Alexanderplatz", &candidates).unwrap(),
+            "900100003"
+        );
+        assert_eq!(best_match("Alex", &candidates[1..]).unwrap(), "900100026");
+
+        let error = best_match("Alex", &candidates).unwrap_err().to_string();
+        assert!(error.contains("900100003") && error.contains("900100026"));
+        assert!(best_match("Alex", &[]).is_err());
+    }
+}
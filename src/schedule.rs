@@ -0,0 +1,237 @@
+use crate::board::Board;
+use anyhow::{anyhow, bail, Context};
+use chrono::{Datelike, NaiveDateTime, NaiveTime, Weekday};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Never wait longer than this between refreshes, however often we get rate limited.
+const MAX_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Time without rate limiting after which the interval shrinks again.
+const COOL_DOWN: Duration = Duration::from_secs(5 * 60);
+
+/// Refresh interval that backs off while the API rate limits us.
+///
+/// Every rate limited refresh doubles the interval (or uses `Retry-After` if that is longer).
+/// Once refreshes succeeded for a cool-down period, it halves again until it is back at normal.
+#[derive(Debug, Clone)]
+pub struct AdaptiveInterval {
+    base: Duration,
+    current: Duration,
+    last_throttled: Option<Instant>,
+}
+
+impl AdaptiveInterval {
+    pub fn new(base: Duration) -> Self {
+        Self {
+            base,
+            current: base,
+            last_throttled: None,
+        }
+    }
+
+    /// Time to wait until the next refresh.
+    pub fn current(&self) -> Duration {
+        self.current
+    }
+
+    /// Whether the interval is stretched because of rate limiting.
+    pub fn is_throttled(&self) -> bool {
+        self.current > self.base
+    }
+
+    pub fn throttled(&mut self, retry_after: Option<Duration>) {
+        let doubled = (self.current * 2).min(MAX_INTERVAL);
+        self.current = retry_after.map_or(doubled, |r| r.max(doubled));
+        self.last_throttled = Some(Instant::now());
+        warn!(
+            "Rate limited, refreshing every {}s for now",
+            self.current.as_secs()
+        );
+    }
+
+    /// Stretches the interval if the API rate limited any stop of the board, else counts as a
+    /// successful refresh.
+    pub fn after(&mut self, board: &Board) {
+        match board.rate_limited() {
+            Some(retry_after) => self.throttled(retry_after),
+            None => self.succeeded(),
+        }
+    }
+
+    pub fn succeeded(&mut self) {
+        if !self.is_throttled() {
+            return;
+        }
+        if self.last_throttled.is_some_and(|t| t.elapsed() < COOL_DOWN) {
+            return;
+        }
+        self.current = (self.current / 2).max(self.base);
+        // the next step down needs another cool-down
+        self.last_throttled = Some(Instant::now());
+        info!("Refreshing every {}s again", self.current.as_secs());
+    }
+}
+
+/// Days and hours in which something is active, e.g. "Mon-Fri 07:00-09:00".
+///
+/// Days are a comma separated list of days and ranges ("Mon,Wed,Fri-Sun") and can be left out for
+/// every day. Without hours the whole day counts. Hours may span midnight ("Fri 22:00-02:00"), the
+/// days then refer to the evening.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Schedule {
+    /// Indexed by days since Monday
+    days: [bool; 7],
+    /// None for the whole day
+    hours: Option<(NaiveTime, NaiveTime)>,
+    text: String,
+}
+
+impl Schedule {
+    pub fn is_active(&self, at: NaiveDateTime) -> bool {
+        let day = |d: Weekday| self.days[d.num_days_from_monday() as usize];
+        let (weekday, time) = (at.weekday(), at.time());
+        match self.hours {
+            None => day(weekday),
+            Some((from, to)) if from < to => day(weekday) && from <= time && time < to,
+            // spans midnight: the early hours belong to the day before
+            Some((from, to)) => {
+                (day(weekday) && time >= from) || (day(weekday.pred()) && time < to)
+            }
+        }
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let parts: Vec<&str> = s.split_whitespace().collect();
+        let (days, hours) = match parts.as_slice() {
+            [one] if one.contains(':') => (None, Some(*one)),
+            [one] => (Some(*one), None),
+            [days, hours] => (Some(*days), Some(*hours)),
+            _ => bail!(
+                "Invalid schedule {:?}, expected e.g. \"Mon-Fri 07:00-09:00\"",
+                s
+            ),
+        };
+
+        let days = match days {
+            None => [true; 7],
+            Some(days) => parse_days(days).with_context(|| format!("Invalid schedule {:?}", s))?,
+        };
+        let hours = hours
+            .map(|h| {
+                let (from, to) = h
+                    .split_once('-')
+                    .ok_or_else(|| anyhow!("expected hours like 07:00-09:00, got {:?}", h))?;
+                anyhow::Ok((parse_time(from)?, parse_time(to)?))
+            })
+            .transpose()
+            .with_context(|| format!("Invalid schedule {:?}", s))?
+            // 00:00-00:00 is the whole day
+            .filter(|(from, to)| from != to);
+
+        Ok(Self {
+            days,
+            hours,
+            text: s.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+impl TryFrom<String> for Schedule {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> anyhow::Result<Self> {
+        // serde only shows the outermost error
+        s.parse().map_err(|e: anyhow::Error| anyhow!("{:#}", e))
+    }
+}
+
+impl From<Schedule> for String {
+    fn from(schedule: Schedule) -> Self {
+        schedule.text
+    }
+}
+
+fn parse_days(s: &str) -> anyhow::Result<[bool; 7]> {
+    let mut days = [false; 7];
+    for item in s.split(',') {
+        let (first, last) = match item.split_once('-') {
+            Some((first, last)) => (parse_weekday(first)?, parse_weekday(last)?),
+            None => (parse_weekday(item)?, parse_weekday(item)?),
+        };
+        // ranges may wrap around the week, e.g. Fri-Mon
+        let mut day = first;
+        loop {
+            days[day.num_days_from_monday() as usize] = true;
+            if day == last {
+                break;
+            }
+            day = day.succ();
+        }
+    }
+    Ok(days)
+}
+
+fn parse_weekday(s: &str) -> anyhow::Result<Weekday> {
+    s.trim()
+        .parse()
+        .map_err(|_| anyhow!("unknown day {:?}, expected Mon, Tue, ...", s))
+}
+
+fn parse_time(s: &str) -> anyhow::Result<NaiveTime> {
+    NaiveTime::parse_from_str(s.trim(), "%H:%M")
+        .map_err(|_| anyhow!("invalid time {:?}, expected HH:MM", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    /// 2024-01-01 is a Monday
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 1, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn schedule_matches_days_and_hours() {
+        let commute: Schedule = "Mon-Fri 07:00-09:00".parse().unwrap();
+        assert!(commute.is_active(at(1, 7, 0)));
+        assert!(commute.is_active(at(5, 8, 59)));
+        assert!(!commute.is_active(at(5, 9, 0)));
+        assert!(!commute.is_active(at(6, 8, 0)));
+
+        let night: Schedule = "Fri,Sat 22:00-02:00".parse().unwrap();
+        assert!(night.is_active(at(5, 23, 0)));
+        assert!(night.is_active(at(7, 1, 0)));
+        assert!(!night.is_active(at(5, 1, 0)));
+
+        let weekend: Schedule = "sat-sun".parse().unwrap();
+        assert!(weekend.is_active(at(7, 12, 0)));
+        assert!(!weekend.is_active(at(1, 12, 0)));
+        assert!("07:00-09:00"
+            .parse::<Schedule>()
+            .unwrap()
+            .is_active(at(3, 8, 0)));
+
+        assert!("Mon-Fri 7-9".parse::<Schedule>().is_err());
+        assert!("Mo-Fr 07:00-09:00".parse::<Schedule>().is_err());
+    }
+}
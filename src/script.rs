@@ -0,0 +1,145 @@
+//! Departure filters written as small [Rhai](https://rhai.rs) scripts, for rules the built-in
+//! filters do not cover.
+//!
+//! The script sees a single departure as variables and evaluates to `true` to keep it:
+//!
+//! ```rhai
+//! // drop the M10 to Warschauer Str. on weekday mornings
+//! !(line == "M10" && direction.contains("Warschauer") && weekday <= 5 && hour < 9)
+//! ```
+
+use crate::board::BoardRow;
+use anyhow::{anyhow, Context};
+use chrono::{Datelike, Local, Timelike, Utc};
+use rhai::{Engine, Scope, AST};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Upper bound on the work a script may do per departure, so a loop cannot hang the board.
+const MAX_OPERATIONS: u64 = 100_000;
+
+/// A compiled filter script.
+pub struct ScriptFilter {
+    path: PathBuf,
+    engine: Engine,
+    ast: AST,
+}
+
+impl fmt::Debug for ScriptFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScriptFilter")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ScriptFilter {
+    /// Reads and compiles the script at `path`.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read script {}", path.display()))?;
+        Self::compile(path, &source)
+    }
+
+    fn compile(path: &Path, source: &str) -> anyhow::Result<Self> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        let ast = engine
+            .compile(source)
+            .map_err(|e| anyhow!("Invalid script {}: {}", path.display(), e))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            engine,
+            ast,
+        })
+    }
+
+    /// Whether to keep the departure.
+    ///
+    /// Variables: `line`, `product`, `direction`, `platform` (empty if unknown), `minutes` until
+    /// departure, `delay` in minutes, `cancelled`, and `weekday` (1 = Monday), `hour` and `minute`
+    /// of the departure in local time.
+    pub fn keep(&self, row: &BoardRow) -> anyhow::Result<bool> {
+        let mut scope = Scope::new();
+        let text = |s: &Option<String>| s.clone().unwrap_or_default();
+        scope.push_constant("line", text(&row.line));
+        scope.push_constant(
+            "product",
+            row.product
+                .as_ref()
+                .map(|p| p.to_string())
+                .unwrap_or_default(),
+        );
+        scope.push_constant("direction", text(&row.direction));
+        scope.push_constant("platform", text(&row.platform));
+        scope.push_constant("delay", row.delay.unwrap_or_default() / 60);
+        scope.push_constant("cancelled", row.cancelled);
+
+        let when = row.when.or(row.planned_when);
+        let local = when.map(|w| w.with_timezone(&Local));
+        scope.push_constant(
+            "minutes",
+            when.map(|w| (w - Utc::now()).num_minutes())
+                .unwrap_or_default(),
+        );
+        scope.push_constant(
+            "weekday",
+            local
+                .map(|l| l.weekday().number_from_monday() as i64)
+                .unwrap_or_default(),
+        );
+        scope.push_constant("hour", local.map(|l| l.hour() as i64).unwrap_or_default());
+        scope.push_constant(
+            "minute",
+            local.map(|l| l.minute() as i64).unwrap_or_default(),
+        );
+
+        self.engine
+            .eval_ast_with_scope::<bool>(&mut scope, &self.ast)
+            .map_err(|e| anyhow!("Script {} failed: {}", self.path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(line: &str, direction: &str) -> BoardRow {
+        BoardRow {
+            stop_id: "1".to_string(),
+            trip_id: None,
+            line_id: None,
+            line: Some(line.to_string()),
+            product: Some(crate::api::products::Product::Tram),
+            direction: Some(direction.to_string()),
+            when: Some(Utc::now() + chrono::Duration::minutes(5)),
+            planned_when: None,
+            delay: Some(120),
+            prognosis_type: None,
+            platform: None,
+            planned_platform: None,
+            remarks: vec![],
+            cancelled: false,
+            ghost: false,
+            implausible_delay: false,
+            #[cfg(feature = "geo")]
+            polyline: None,
+        }
+    }
+
+    #[test]
+    fn script_decides_per_departure() {
+        let script = ScriptFilter::compile(
+            Path::new("test.rhai"),
+            r#"!(line == "M10" && direction.contains("Warschauer")) && delay == 2 && minutes >= 3"#,
+        )
+        .unwrap();
+        assert!(!script.keep(&row("M10", "S+U Warschauer Str.")).unwrap());
+        assert!(script.keep(&row("M10", "Hauptbahnhof")).unwrap());
+        assert!(script.keep(&row("M4", "S+U Warschauer Str.")).unwrap());
+
+        let broken = ScriptFilter::compile(Path::new("test.rhai"), "line").unwrap();
+        assert!(broken.keep(&row("M10", "Hauptbahnhof")).is_err());
+        assert!(ScriptFilter::compile(Path::new("test.rhai"), "line ==").is_err());
+    }
+}
@@ -0,0 +1,59 @@
+//! Dumps the board a long-running view currently shows as JSON on SIGUSR1, to see what the process
+//! believes without attaching a debugger: `kill -USR1 $(pidof bvg-departures)`.
+
+use crate::board::Board;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
+
+/// Latest board of a view. Clones share it.
+#[derive(Debug, Clone, Default)]
+pub struct BoardSnapshot {
+    board: Arc<Mutex<Option<Board>>>,
+}
+
+impl BoardSnapshot {
+    pub fn update(&self, board: &Board) {
+        *self.board.lock().unwrap() = Some(board.clone());
+    }
+
+    /// Writes the board to `target`, or to stderr if None, on every SIGUSR1 until the process
+    /// exits. Needs a tokio runtime.
+    #[cfg(unix)]
+    pub fn listen(&self, target: Option<PathBuf>) -> anyhow::Result<()> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut signals = signal(SignalKind::user_defined1())?;
+        let snapshot = self.clone();
+        tokio::spawn(async move {
+            while signals.recv().await.is_some() {
+                if let Err(e) = snapshot.write(target.as_ref()) {
+                    warn!("Could not write the board snapshot: {}", e);
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Signals are only supported on unix.
+    #[cfg(not(unix))]
+    pub fn listen(&self, _target: Option<PathBuf>) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn write(&self, target: Option<&PathBuf>) -> anyhow::Result<()> {
+        let json = match &*self.board.lock().unwrap() {
+            Some(board) => serde_json::to_string_pretty(board)?,
+            None => "null".to_string(),
+        };
+        match target {
+            Some(path) => {
+                std::fs::write(path, format!("{}\n", json))?;
+                info!("Wrote the board to {}", path.display());
+            }
+            None => writeln!(std::io::stderr(), "{}", json)?,
+        }
+        Ok(())
+    }
+}
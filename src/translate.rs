@@ -0,0 +1,98 @@
+//! Translation of remark texts, which the API often only has in German.
+
+use crate::board::StationBoard;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::Mutex;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tracing::{debug, warn};
+
+/// Name of the environment variable that holds the target language for the command.
+pub const LANGUAGE_ENV: &str = "BVG_DEPARTURES_TARGET_LANGUAGE";
+
+/// Command that translates remark texts, e.g. a script calling a translation API.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct TranslateConfig {
+    /// Shell command that reads the text on stdin and prints the translation
+    pub command: String,
+    /// Target language, passed to the command as `BVG_DEPARTURES_TARGET_LANGUAGE`
+    #[serde(default = "english")]
+    pub language: String,
+}
+
+fn english() -> String {
+    "en".to_string()
+}
+
+/// Translates remarks with a user command. Each remark is only translated once per run.
+#[derive(Debug)]
+pub struct RemarkTranslator {
+    config: TranslateConfig,
+    /// Translations by remark id, or by text for remarks without id
+    cache: Mutex<HashMap<String, String>>,
+}
+
+impl RemarkTranslator {
+    pub fn new(config: TranslateConfig) -> Self {
+        Self {
+            config,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Replaces the remark texts of a station with their translations.
+    pub async fn translate_station(&self, station: &mut StationBoard) {
+        for remark in station.rows.iter_mut().flat_map(|r| &mut r.remarks) {
+            let key = remark.id.clone().unwrap_or_else(|| remark.text.clone());
+            let cached = self.cache.lock().expect("cache lock").get(&key).cloned();
+            let translated = match cached {
+                Some(translated) => translated,
+                None => {
+                    let translated = match self.run(&remark.text).await {
+                        Ok(translated) => translated,
+                        Err(e) => {
+                            // keep the original, and do not ask again for this remark
+                            warn!("Could not translate remark: {}", e);
+                            remark.text.clone()
+                        }
+                    };
+                    self.cache
+                        .lock()
+                        .expect("cache lock")
+                        .insert(key, translated.clone());
+                    translated
+                }
+            };
+            remark.text = translated;
+        }
+    }
+
+    async fn run(&self, text: &str) -> anyhow::Result<String> {
+        debug!("Translating '{}'", text);
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(&self.config.command)
+            .env(LANGUAGE_ENV, &self.config.language)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes()).await?;
+        }
+
+        let output = child.wait_with_output().await?;
+        if !output.status.success() {
+            anyhow::bail!("'{}' exited with {}", self.config.command, output.status);
+        }
+        let translated = String::from_utf8(output.stdout)?.trim().to_string();
+        if translated.is_empty() {
+            anyhow::bail!("'{}' printed no translation", self.config.command);
+        }
+        Ok(translated)
+    }
+}
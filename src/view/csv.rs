@@ -0,0 +1,120 @@
+use crate::api::departures::DeparturesApi;
+use crate::api::products::Product;
+use crate::board::{Board, BoardRow, StationBoard};
+use crate::exec::ExecHook;
+use crate::view::ResultDisplay;
+use crate::InputStops;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use derive_builder::Builder;
+use serde::Serialize;
+
+/// Prints the board once as CSV with a header row, one row per departure.
+///
+/// Like the JSON output, values are the ones reported by the API. Stops that could not be fetched
+/// are left out.
+#[derive(Builder)]
+#[builder(pattern = "owned")]
+pub struct CsvDisplay<D: DeparturesApi> {
+    api_client: D,
+    stops: InputStops,
+    /// Command to run once the departures have been fetched.
+    #[builder(default)]
+    exec: Option<ExecHook>,
+}
+
+/// Fields of [`CsvRow`], written even if there are no departures
+const HEADER: [&str; 8] = [
+    "station",
+    "line",
+    "product",
+    "direction",
+    "planned_when",
+    "when",
+    "delay_s",
+    "platform",
+];
+
+#[derive(Debug, Serialize)]
+struct CsvRow<'a> {
+    station: &'a str,
+    line: Option<&'a str>,
+    product: Option<&'a Product>,
+    direction: Option<&'a str>,
+    planned_when: Option<DateTime<Utc>>,
+    when: Option<DateTime<Utc>>,
+    delay_s: Option<i64>,
+    platform: Option<&'a str>,
+}
+
+impl<'a> CsvRow<'a> {
+    fn new(station: &'a StationBoard, row: &'a BoardRow) -> Self {
+        Self {
+            station: &station.name,
+            line: row.line.as_deref(),
+            product: row.product.as_ref(),
+            direction: row.direction.as_deref(),
+            planned_when: row.planned_when,
+            when: row.when,
+            delay_s: row.delay,
+            platform: row.platform.as_deref(),
+        }
+    }
+}
+
+#[async_trait]
+impl<D: DeparturesApi + Sync> ResultDisplay for CsvDisplay<D> {
+    async fn display(&self) -> anyhow::Result<()> {
+        let board = self.api_client.get_departures(&self.stops).await?;
+
+        if let Some(exec) = &self.exec {
+            let _ = exec.run(&board).await;
+        }
+
+        write_csv(&board, std::io::stdout().lock())
+    }
+}
+
+fn write_csv(board: &Board, out: impl std::io::Write) -> anyhow::Result<()> {
+    // serde only writes the header along with the first row
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(out);
+    writer.write_record(HEADER)?;
+    for station in &board.stations {
+        for row in &station.rows {
+            writer.serialize(CsvRow::new(station, row))?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::InputStop;
+    use chrono::Local;
+    use serde_json::json;
+
+    #[test]
+    fn writes_the_header_without_departures() {
+        let csv = |departures| {
+            let stop = InputStop::new("1".to_string(), "Alex".to_string());
+            let response = serde_json::from_value(json!({ "departures": departures })).unwrap();
+            let board = Board {
+                stations: vec![StationBoard::new(&stop, response)],
+                fetched_at: Local::now(),
+            };
+            let mut out = vec![];
+            write_csv(&board, &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let header = "station,line,product,direction,planned_when,when,delay_s,platform\n";
+        assert_eq!(csv(json!([])), header);
+        assert_eq!(
+            csv(json!([{"direction": "Pankow", "line": {"name": "U2", "product": "subway"}}])),
+            format!("{}Alex,U2,subway,Pankow,,,,\n", header)
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use crate::view::{DisplayEntry, StationHeader};
+use base64::Engine;
+use qrcode::render::unicode::Dense1x2;
+use qrcode::QrCode;
+use std::io::Write;
+use url::Url;
+
+/// Used if the config has no `deep_link`. The trip in the BVG journey planner, from the stop on,
+/// which the BVG app opens too.
+pub(crate) const DEFAULT_DEEP_LINK: &str =
+    "https://www.bvg.de/de/verbindungen/fahrtverlauf?tripId={trip_id}&stopId={stop_id}";
+
+/// Fills in the placeholders `{trip_id}`, `{stop_id}`, `{line}` and `{base_url}`, the API at
+/// `base`. None if the departure has no trip id.
+pub(crate) fn deep_link(
+    template: &str,
+    base: &Url,
+    station: &StationHeader,
+    e: &DisplayEntry,
+) -> Option<String> {
+    let trip_id = e.trip_id.as_deref()?;
+    Some(
+        template
+            .replace("{base_url}", base.as_str().trim_end_matches('/'))
+            .replace("{trip_id}", &encode(trip_id))
+            .replace("{stop_id}", &encode(&station.stop_id))
+            .replace("{line}", &encode(&e.line)),
+    )
+}
+
+/// Trip ids contain characters like `|` that must not end up in a URL as they are.
+fn encode(value: &str) -> String {
+    url::form_urlencoded::byte_serialize(value.as_bytes()).collect()
+}
+
+/// Puts text on the clipboard via the OSC 52 escape sequence. This also works over SSH, as long as
+/// the terminal supports it.
+pub(crate) fn copy_osc52(text: &str) -> std::io::Result<()> {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+    let mut out = std::io::stdout();
+    write!(out, "\x1b]52;c;{}\x07", encoded)?;
+    out.flush()
+}
+
+/// QR code of a link as lines of half blocks, two modules per line. It has to be drawn dark on
+/// light to be scannable. None if the link is too long for a QR code.
+pub(crate) fn qr_code(link: &str) -> Option<Vec<String>> {
+    let code = QrCode::new(link.as_bytes()).ok()?;
+    let image = code.render::<Dense1x2>().build();
+    Some(image.lines().map(str::to_string).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::view::tests::{entry, header};
+
+    #[test]
+    fn fills_in_and_encodes_the_placeholders() {
+        let base = Url::parse("https://v6.bvg.transport.rest/").unwrap();
+        let e = DisplayEntry {
+            trip_id: Some("1|12345|0|86|1012025".to_string()),
+            ..entry("U8", 4)
+        };
+        assert_eq!(
+            deep_link(DEFAULT_DEEP_LINK, &base, &header("900100003", "Alex"), &e).unwrap(),
+            "https://www.bvg.de/de/verbindungen/fahrtverlauf\
+             ?tripId=1%7C12345%7C0%7C86%7C1012025&stopId=900100003"
+        );
+        assert_eq!(
+            deep_link(
+                "{base_url}/trips/{trip_id}?line={line}",
+                &base,
+                &header("1", "A"),
+                &e
+            )
+            .unwrap(),
+            "https://v6.bvg.transport.rest/trips/1%7C12345%7C0%7C86%7C1012025?line=U8"
+        );
+        assert_eq!(
+            deep_link(DEFAULT_DEEP_LINK, &base, &header("1", "A"), &entry("U8", 4)),
+            None
+        );
+    }
+}
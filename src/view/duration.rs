@@ -0,0 +1,136 @@
+use chrono::{DateTime, Duration, Local};
+
+/// Countdowns above this many minutes are rendered as hours and minutes.
+const HUMANIZE_ABOVE_MINS: i64 = 90;
+
+/// Formats a countdown in minutes, e.g. "now", "4min", "1h 25m" for long look-aheads
+/// or "departed 1min ago" for negative values.
+pub(crate) fn format_countdown(mins: i64) -> String {
+    match mins {
+        m if m < 0 => format!("departed {}min ago", -m),
+        0 => "now".to_string(),
+        m if m > HUMANIZE_ABOVE_MINS => format!("{}h {:02}m", m / 60, m % 60),
+        m => format!("{}min", m),
+    }
+}
+
+/// Formats how long something takes, e.g. "0min", "29min" or "1h 05m".
+pub(crate) fn format_duration(mins: i64) -> String {
+    match mins.max(0) {
+        m if m >= 60 => format!("{}h {:02}m", m / 60, m % 60),
+        m => format!("{}min", m),
+    }
+}
+
+/// Countdown to when you have to leave for a departure, e.g. "leave in 4min" or "leave now".
+pub(crate) fn format_leave_in(mins: i64) -> String {
+    match mins {
+        m if m <= 0 => "leave now".to_string(),
+        m => format!("leave in {}", format_countdown(m)),
+    }
+}
+
+/// Number of calendar days between `now` and `when` in local time.
+pub(crate) fn day_offset(when: DateTime<Local>, now: DateTime<Local>) -> i64 {
+    (when.date_naive() - now.date_naive()).num_days()
+}
+
+/// Like [`day_offset`], but days start at `start_hour` like service days do. With a start hour of
+/// 3, a departure at 00:12 is on the same service day as 23:50.
+pub(crate) fn service_day_offset(
+    when: DateTime<Local>,
+    now: DateTime<Local>,
+    start_hour: u32,
+) -> i64 {
+    let shift = Duration::hours(start_hour.into());
+    day_offset(when - shift, now - shift)
+}
+
+/// Appends the day offset to a "HH:MM" time if it is not today, e.g. "00:12 +1d".
+pub(crate) fn clock_label(time: &str, day_offset: i64) -> String {
+    if day_offset == 0 {
+        time.to_string()
+    } else {
+        format!("{} {:+}d", time, day_offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn local(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    #[test]
+    fn countdown_is_humanized_above_90_minutes() {
+        assert_eq!(format_countdown(1), "1min");
+        assert_eq!(format_countdown(90), "90min");
+        assert_eq!(format_countdown(91), "1h 31m");
+        assert_eq!(format_countdown(125), "2h 05m");
+    }
+
+    #[test]
+    fn countdown_around_departure() {
+        assert_eq!(format_countdown(0), "now");
+        assert_eq!(format_countdown(-1), "departed 1min ago");
+        assert_eq!(format_leave_in(0), "leave now");
+        assert_eq!(format_leave_in(4), "leave in 4min");
+    }
+
+    #[test]
+    fn duration_has_no_countdown_wording() {
+        assert_eq!(format_duration(0), "0min");
+        assert_eq!(format_duration(29), "29min");
+        assert_eq!(format_duration(65), "1h 05m");
+    }
+
+    #[test]
+    fn day_offset_across_midnight() {
+        let now = local(2024, 3, 9, 23, 50);
+        assert_eq!(day_offset(local(2024, 3, 9, 23, 59), now), 0);
+        assert_eq!(day_offset(local(2024, 3, 10, 0, 12), now), 1);
+        assert_eq!(day_offset(local(2024, 3, 11, 0, 12), now), 2);
+    }
+
+    #[test]
+    fn day_offset_across_month_and_year() {
+        assert_eq!(
+            day_offset(local(2024, 3, 1, 0, 5), local(2024, 2, 29, 23, 55)),
+            1
+        );
+        assert_eq!(
+            day_offset(local(2025, 1, 1, 0, 5), local(2024, 12, 31, 23, 55)),
+            1
+        );
+    }
+
+    #[test]
+    fn day_offset_in_the_past() {
+        assert_eq!(
+            day_offset(local(2024, 3, 9, 23, 58), local(2024, 3, 10, 0, 1)),
+            -1
+        );
+    }
+
+    #[test]
+    fn service_day_offset_keeps_early_morning_on_the_previous_day() {
+        let now = local(2024, 3, 9, 23, 50);
+        assert_eq!(service_day_offset(local(2024, 3, 10, 0, 12), now, 3), 0);
+        assert_eq!(service_day_offset(local(2024, 3, 10, 2, 59), now, 3), 0);
+        assert_eq!(service_day_offset(local(2024, 3, 10, 3, 0), now, 3), 1);
+        assert_eq!(service_day_offset(local(2024, 3, 10, 0, 12), now, 0), 1);
+        // after midnight, the evening before is still today
+        let now = local(2024, 3, 10, 1, 30);
+        assert_eq!(service_day_offset(local(2024, 3, 9, 23, 58), now, 3), 0);
+    }
+
+    #[test]
+    fn clock_label_only_annotates_other_days() {
+        assert_eq!(clock_label("23:59", 0), "23:59");
+        assert_eq!(clock_label("00:12", 1), "00:12 +1d");
+        assert_eq!(clock_label("23:58", -1), "23:58 -1d");
+    }
+}
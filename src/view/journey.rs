@@ -0,0 +1,82 @@
+use crate::api::departures::Stop;
+use crate::api::journeys::{Journey, Leg};
+use crate::view::duration::format_duration;
+use chrono::{DateTime, Local, Utc};
+
+/// Connections as printed by `bvg-departures journey`, e.g.
+///
+/// ```text
+/// 08:12 → 08:41  29min, 1 transfer
+///   08:12  U8     Alexanderplatz → Hermannplatz  +2min
+///   08:29  walk   Hermannplatz → Karl-Marx-Str.
+/// ```
+pub fn format_journeys(journeys: &[Journey]) -> String {
+    journeys
+        .iter()
+        .map(format_journey)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn format_journey(journey: &Journey) -> String {
+    let duration = match (journey.departure_time(), journey.arrival_time()) {
+        (Some(dep), Some(arr)) => format_duration((arr - dep).num_minutes()),
+        _ => "?".to_string(),
+    };
+    let transfers = match journey.transfers() {
+        1 => "1 transfer".to_string(),
+        n => format!("{} transfers", n),
+    };
+    let mut out = format!(
+        "{} → {}  {}, {}",
+        clock(journey.departure_time()),
+        clock(journey.arrival_time()),
+        duration,
+        transfers
+    );
+    if journey.is_cancelled() {
+        out.push_str(", cancelled");
+    }
+    out.push('\n');
+    for leg in &journey.legs {
+        out.push_str(&format_leg(leg));
+        out.push('\n');
+    }
+    out
+}
+
+fn format_leg(leg: &Leg) -> String {
+    let line = match &leg.line {
+        Some(line) if !leg.is_walking() => line.name.as_deref().unwrap_or("?"),
+        _ => "walk",
+    };
+    let mut out = format!(
+        "  {}  {:<5}  {} → {}",
+        clock(leg.departure_time()),
+        line,
+        stop_name(&leg.origin),
+        stop_name(&leg.destination)
+    );
+    if let Some(platform) = &leg.departure_platform {
+        out.push_str(&format!(" (platform {})", platform));
+    }
+    match leg.departure_delay.map(|d| d / 60) {
+        Some(mins) if mins != 0 => out.push_str(&format!("  {:+}min", mins)),
+        _ => {}
+    }
+    if leg.cancelled == Some(true) {
+        out.push_str("  cancelled");
+    }
+    out
+}
+
+fn clock(when: Option<DateTime<Utc>>) -> String {
+    when.map(|w| w.with_timezone(&Local).format("%H:%M").to_string())
+        .unwrap_or_else(|| "--:--".to_string())
+}
+
+fn stop_name(stop: &Option<Stop>) -> &str {
+    stop.as_ref()
+        .and_then(|s| s.name.as_deref())
+        .unwrap_or_default()
+}
@@ -0,0 +1,130 @@
+use crate::api::departures::DeparturesApi;
+use crate::api::products::Product;
+use crate::board::{Board, BoardRow, StationBoard};
+use crate::exec::ExecHook;
+use crate::view::ResultDisplay;
+use crate::InputStops;
+use async_trait::async_trait;
+use chrono::{DateTime, Local, Utc};
+use derive_builder::Builder;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::io::Write;
+
+/// Prints the board once as JSON, e.g. for jq.
+///
+/// Values are passed on as the API reports them, without the aliases and labels of the other
+/// views. The field names are part of the public interface and must not change.
+#[derive(Builder)]
+#[builder(pattern = "owned")]
+pub struct JsonDisplay<D: DeparturesApi> {
+    api_client: D,
+    stops: InputStops,
+    /// Command to run once the departures have been fetched.
+    #[builder(default)]
+    exec: Option<ExecHook>,
+}
+
+/// Also what `--exec` commands get, and `/board` of `serve`.
+#[derive(Debug, Serialize, JsonSchema)]
+#[schemars(
+    rename = "Board",
+    description = "Departures of the stops, in the order of the config"
+)]
+pub(crate) struct BoardJson<'a> {
+    fetched_at: DateTime<Local>,
+    stations: Vec<StationJson<'a>>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+#[schemars(rename = "Station")]
+struct StationJson<'a> {
+    stop_id: &'a str,
+    station: &'a str,
+    /// Whether `departures` are arrivals, with `direction` being where they come from
+    arrivals: bool,
+    /// Why the departures of this stop are missing
+    error: Option<&'a str>,
+    departures: Vec<DepartureJson<'a>>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+#[schemars(rename = "Departure")]
+struct DepartureJson<'a> {
+    trip_id: Option<&'a str>,
+    line: Option<&'a str>,
+    product: Option<&'a Product>,
+    direction: Option<&'a str>,
+    planned_when: Option<DateTime<Utc>>,
+    /// Realtime departure, if known
+    when: Option<DateTime<Utc>>,
+    /// In seconds
+    delay: Option<i64>,
+    platform: Option<&'a str>,
+    planned_platform: Option<&'a str>,
+    cancelled: bool,
+    /// Only present if the delay is beyond `max_delay_minutes`
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    implausible_delay: bool,
+    /// Route of the trip as GeoJSON, with `polylines` in the config
+    #[cfg(feature = "geo")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    polyline: Option<&'a serde_json::Value>,
+}
+
+impl<'a> From<&'a Board> for BoardJson<'a> {
+    fn from(board: &'a Board) -> Self {
+        Self {
+            fetched_at: board.fetched_at,
+            stations: board.stations.iter().map(StationJson::from).collect(),
+        }
+    }
+}
+
+impl<'a> From<&'a StationBoard> for StationJson<'a> {
+    fn from(station: &'a StationBoard) -> Self {
+        Self {
+            stop_id: &station.stop_id,
+            station: &station.name,
+            arrivals: station.arrivals,
+            error: station.error.as_deref(),
+            departures: station.rows.iter().map(DepartureJson::from).collect(),
+        }
+    }
+}
+
+impl<'a> From<&'a BoardRow> for DepartureJson<'a> {
+    fn from(row: &'a BoardRow) -> Self {
+        Self {
+            trip_id: row.trip_id.as_deref(),
+            line: row.line.as_deref(),
+            product: row.product.as_ref(),
+            direction: row.direction.as_deref(),
+            planned_when: row.planned_when,
+            when: row.when,
+            delay: row.delay,
+            platform: row.platform.as_deref(),
+            planned_platform: row.planned_platform.as_deref(),
+            cancelled: row.cancelled,
+            implausible_delay: row.implausible_delay,
+            #[cfg(feature = "geo")]
+            polyline: row.polyline.as_ref(),
+        }
+    }
+}
+
+#[async_trait]
+impl<D: DeparturesApi + Sync> ResultDisplay for JsonDisplay<D> {
+    async fn display(&self) -> anyhow::Result<()> {
+        let board = self.api_client.get_departures(&self.stops).await?;
+
+        if let Some(exec) = &self.exec {
+            let _ = exec.run(&board).await;
+        }
+
+        let mut stdout = std::io::stdout().lock();
+        serde_json::to_writer_pretty(&mut stdout, &BoardJson::from(&board))?;
+        writeln!(stdout)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+use crate::board::BoardRow;
+use crate::{InputStops, RingLabels};
+use std::collections::HashMap;
+
+/// Line specific formatting of departures, e.g. to replace unhelpful directions.
+pub trait LineFormatter {
+    /// Direction shown instead of the one from the API. None keeps it.
+    fn direction(&self, row: &BoardRow) -> Option<String>;
+}
+
+/// Formatters applied by the views, in order. The first one that returns a value wins.
+pub fn line_formatters(stops: &InputStops) -> Vec<Box<dyn LineFormatter + '_>> {
+    let mut formatters: Vec<Box<dyn LineFormatter>> = vec![Box::new(RingFormatter {
+        labels: stops.ring_labels.clone().unwrap_or_default(),
+    })];
+    if !stops.direction_aliases.is_empty() {
+        formatters.push(Box::new(AliasFormatter {
+            aliases: &stops.direction_aliases,
+        }));
+    }
+    formatters
+}
+
+/// The S41 runs clockwise around the ring and the S42 counterclockwise, but both just say "Ring".
+pub struct RingFormatter {
+    labels: RingLabels,
+}
+
+impl LineFormatter for RingFormatter {
+    fn direction(&self, row: &BoardRow) -> Option<String> {
+        let line = row.line_id.as_deref().or(row.line.as_deref())?;
+        let clockwise = match line.to_ascii_lowercase().as_str() {
+            "s41" => true,
+            "s42" => false,
+            // other lines on the ring mark the direction with an arrow, if at all
+            _ => {
+                let direction = row.direction.as_deref()?;
+                if direction.contains('⟳') {
+                    true
+                } else if direction.contains('⟲') {
+                    false
+                } else {
+                    return None;
+                }
+            }
+        };
+        let label = if clockwise {
+            &self.labels.clockwise
+        } else {
+            &self.labels.counterclockwise
+        };
+        Some(label.clone())
+    }
+}
+
+/// Replaces official stop names in directions with the nicknames from the alias file.
+pub struct AliasFormatter<'a> {
+    aliases: &'a HashMap<String, String>,
+}
+
+impl LineFormatter for AliasFormatter<'_> {
+    fn direction(&self, row: &BoardRow) -> Option<String> {
+        let direction = row.direction.as_deref()?.trim();
+        self.aliases.get(direction).cloned()
+    }
+}
@@ -1,94 +1,782 @@
-use crate::api::departures::DeparturesResponse;
+use crate::api::products::Product;
+use crate::board::{Board, BoardRow};
+use crate::{is_night_line, InputStops};
 use async_trait::async_trait;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
 
-pub(crate) mod std_out;
-pub(crate) mod tui;
+pub mod csv;
+pub(crate) mod deep_link;
+pub(crate) mod duration;
+pub mod journey;
+pub mod json;
+pub mod line_format;
+#[cfg(feature = "overlay")]
+pub mod overlay;
+pub mod status_file;
+pub mod std_out;
+pub mod summary;
+pub mod tui;
+pub mod verbose;
+
+/// Output of the one-shot (non-TUI) mode.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Table for humans, or tab-separated rows with `--porcelain`
+    #[default]
+    Text,
+    /// The board as JSON
+    // see json::JsonDisplay
+    Json,
+    /// One row per departure
+    // see csv::CsvDisplay
+    Csv,
+    /// Full sentences for screen readers
+    // see verbose::VerboseDisplay
+    PlainVerbose,
+}
 
 #[async_trait]
-pub(super) trait ResultDisplay {
+pub trait ResultDisplay {
     async fn display(&self) -> anyhow::Result<()>;
 }
 
-pub(super) fn product_symbol(product: &str) -> &'static str {
+pub(super) fn product_symbol(product: Option<&Product>) -> &'static str {
     match product {
-        p if p.contains("subway") => "🚇",
-        p if p.contains("suburban") => "🚆",
-        p if p.contains("bus") => "🚌",
-        p if p.contains("tram") => "🚃",
+        Some(Product::Subway) => "🚇",
+        Some(Product::Suburban) => "🚆",
+        Some(Product::Bus) => "🚌",
+        Some(Product::Tram) => "🚃",
         _ => "🚀",
     }
 }
 
-pub(super) fn product_hex(product: &str) -> &'static str {
+pub(crate) fn product_hex(product: Option<&Product>) -> &'static str {
     match product {
-        p if p.contains("subway") => "#00539F",
-        p if p.contains("suburban") => "#00854A",
-        p if p.contains("bus") => "#95276E",
-        p if p.contains("tram") => "#BE1414",
+        Some(Product::Subway) => "#00539F",
+        Some(Product::Suburban) => "#00854A",
+        Some(Product::Bus) => "#95276E",
+        Some(Product::Tram) => "#BE1414",
         _ => "#00FFFF",
     }
 }
 
+/// Station heading, optionally styled per stop in the config.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StationHeader {
+    pub stop_id: String,
+    pub name: String,
+    pub icon: Option<String>,
+    pub hex: Option<String>,
+    /// Labeled "Arrivals" instead of "Station"
+    pub arrivals: bool,
+    /// Distance and direction from `my_location`, e.g. "350m NE"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distance: Option<String>,
+}
+
+impl StationHeader {
+    /// "Station" or "Arrivals", shown in front of the name
+    pub fn label(&self) -> &'static str {
+        if self.arrivals {
+            "Arrivals"
+        } else {
+            "Station"
+        }
+    }
+}
+
+impl fmt::Display for StationHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
 // Shared display entry and builder to avoid duplicated formatting logic between std_out and tui
-pub(super) struct DisplayEntry {
+#[derive(Serialize, PartialEq)]
+pub struct DisplayEntry {
+    pub trip_id: Option<String>,
     pub line: String,
+    #[serde(rename = "direction")]
     pub dir: String,
-    pub actual_mins: i64,
+    #[serde(rename = "minutes")]
+    pub actual_mins: i64, // negative if already departed, planned time for cancelled trips
+    #[serde(rename = "delay_minutes")]
     pub delay_mins: Option<i64>,
+    /// Delay as reported if it is beyond `max_delay_minutes`. `delay_mins` is None then.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub implausible_delay_mins: Option<i64>,
+    #[serde(skip)]
     pub symbol: &'static str,
+    #[serde(skip)]
     pub hex: &'static str,
     // Absolute departure time formatted as HH:MM in local time (None if unknown)
+    #[serde(rename = "time")]
     pub abs_time: Option<String>,
+    // Timetable departure time as HH:MM in local time (None if unknown)
+    pub planned_time: Option<String>,
+    // Calendar days between today and the departure, for boards reaching past midnight
+    pub day_offset: i64,
+    // Texts of remarks of type "warning" attached to this departure
+    pub warnings: Vec<String>,
+    pub confidence: Confidence,
+    pub platform: Option<String>,
+    /// Whether `platform` differs from the timetable
+    pub platform_changed: bool,
+    /// Cancelled trips keep their planned time and show "CANCELLED" instead of a countdown
+    pub cancelled: bool,
+    /// Likely ghost run: dropped from the realtime data without being cancelled
+    pub ghost: bool,
+    /// False if the departure leaves before you could walk to the stop
+    pub reachable: bool,
+    /// Minutes until you have to leave for the stop, with `show_leave_in` and `walk_minutes`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub leave_in_mins: Option<i64>,
+    /// Minutes until the following departures of the same line and direction in the grouped view
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub later_mins: Vec<i64>,
+    /// Typical minutes between the departures of the group in the grouped view, if there are
+    /// enough on the board to tell
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub headway_mins: Option<i64>,
+    /// Station of the departure in the merged view, where all stations share one list
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub station: Option<StationHeader>,
 }
 
-pub(super) fn build_display_lines(
-    resp: &Vec<(String, DeparturesResponse)>,
-) -> Vec<(String, Vec<DisplayEntry>)> {
+impl DisplayEntry {
+    /// Countdown for the "in" column: to the departure, or to when you have to leave.
+    pub fn countdown(&self) -> String {
+        if self.cancelled {
+            return "CANCELLED".to_string();
+        }
+        if !self.later_mins.is_empty() {
+            // e.g. "2, 9, 17 min"
+            let first = match self.actual_mins {
+                0 => "now".to_string(),
+                m => m.to_string(),
+            };
+            let later: Vec<String> = self.later_mins.iter().map(|m| m.to_string()).collect();
+            let every = self
+                .headway_mins
+                .map(|h| format!(", every ~{}min", h))
+                .unwrap_or_default();
+            return format!("{}, {} min{}", first, later.join(", "), every);
+        }
+        match self.leave_in_mins {
+            Some(leave_in) if self.reachable => duration::format_leave_in(leave_in),
+            _ => duration::format_countdown(self.actual_mins),
+        }
+    }
+
+    /// "?" for a delay beyond `max_delay_minutes`, else e.g. "+3min". Empty if on time.
+    pub fn delay_text(&self) -> String {
+        match (self.delay_mins, self.implausible_delay_mins) {
+            (_, Some(_)) => "?".to_string(),
+            (Some(d), _) if d != 0 => format!("{:+}min", d),
+            _ => String::new(),
+        }
+    }
+
+    /// e.g. "14:32 → 14:38" for `show_planned_times`, with `arrow` between the times. None if the
+    /// trip runs on time.
+    pub fn planned_and_actual(&self, arrow: &str) -> Option<String> {
+        match (&self.planned_time, &self.abs_time) {
+            (Some(planned), Some(actual)) if planned != actual && !self.cancelled => Some(format!(
+                "{} {} {}",
+                planned,
+                arrow,
+                duration::clock_label(actual, self.day_offset)
+            )),
+            _ => None,
+        }
+    }
+
+    /// Badge for a changed platform, e.g. "Gleis geändert: 2". Easier to miss than a delay, and worse.
+    pub fn platform_badge(&self) -> Option<String> {
+        match &self.platform {
+            Some(platform) if self.platform_changed => {
+                Some(format!("Gleis geändert: {}", platform))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// How trustworthy the departure time is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Confidence {
+    /// Predicted from the current position of the vehicle
+    Realtime,
+    /// Extrapolated by the API, e.g. from an earlier delay
+    Estimated,
+    /// Timetable only
+    Scheduled,
+}
+
+impl Confidence {
+    fn of(row: &BoardRow) -> Self {
+        match row.prognosis_type.as_deref() {
+            Some("prognosed") => Confidence::Realtime,
+            Some(_) => Confidence::Estimated,
+            // older deployments only report a delay for realtime data
+            None if row.delay.is_some() => Confidence::Realtime,
+            None => Confidence::Scheduled,
+        }
+    }
+
+    /// Marker shown in front of countdowns
+    pub fn marker(self) -> &'static str {
+        match self {
+            Confidence::Realtime => "●",
+            Confidence::Estimated => "◐",
+            Confidence::Scheduled => "○",
+        }
+    }
+}
+
+/// Departures that left more than `grace_minutes` ago are dropped. With night mode, day offsets
+/// are counted in service days and night lines are labeled.
+pub fn build_display_lines(
+    board: &Board,
+    stops: &InputStops,
+) -> Vec<(StationHeader, Vec<DisplayEntry>)> {
     use chrono::{Local, Utc};
-    let mut out: Vec<(String, Vec<DisplayEntry>)> = Vec::new();
-    for (station_name, departures) in resp.iter() {
+    let mut out: Vec<(StationHeader, Vec<DisplayEntry>)> = Vec::new();
+    let now = Local::now();
+    let grace_minutes = stops.grace_minutes;
+    let night_mode = stops.night_mode.as_ref();
+    let service_day_start = night_mode.map_or(0, |n| n.service_day_start_hour);
+    let formatters = line_format::line_formatters(stops);
+    // by stop rather than station: the stops of a group can be different walks away
+    let walk_minutes: HashMap<&str, i64> = stops
+        .stops
+        .iter()
+        .filter_map(|s| Some((s.id.as_str(), i64::from(s.walk_minutes?))))
+        .collect();
+    for station in &board.stations {
+        let header = StationHeader {
+            stop_id: station.stop_id.clone(),
+            name: station.name.clone(),
+            icon: station.icon.clone(),
+            hex: station.color.clone(),
+            arrivals: station.arrivals,
+            distance: stops
+                .my_location
+                .zip(station.location)
+                .map(|(me, stop)| me.describe(&stop)),
+        };
+
         let mut entries: Vec<DisplayEntry> = Vec::new();
-        for d in &departures.departures {
-            let line = d
-                .line
-                .as_ref()
-                .and_then(|l| l.name.as_ref())
-                .map(|s| s.clone())
-                .unwrap_or_else(|| "?".to_string());
-
-            let product = d
-                .line
-                .as_ref()
-                .and_then(|l| l.product.as_ref())
-                .map(|s| s.as_str())
-                .unwrap_or("");
-
-            let symbol = product_symbol(product);
+        for d in &station.rows {
+            let line = d.line.clone().unwrap_or_else(|| "?".to_string());
+
+            let product = d.product.as_ref();
+
+            let symbol = if night_mode.is_some() && is_night_line(&line) {
+                "🌙"
+            } else {
+                product_symbol(product)
+            };
             let hex = product_hex(product);
 
-            let dir = d.direction.as_deref().unwrap_or("").to_string();
-            let actual_mins = d
-                .when
+            let dir = formatters
+                .iter()
+                .find_map(|f| f.direction(d))
+                .unwrap_or_else(|| d.direction.clone().unwrap_or_default());
+            // cancelled trips have no realtime prediction, only the timetable, and the realtime
+            // time of a glitched delay is just as wrong
+            let when = if d.implausible_delay {
+                d.planned_when.or(d.when)
+            } else {
+                d.when.or(d.planned_when)
+            };
+            // negative for departed trips
+            let actual_mins = when
                 .map(|w| (w - Utc::now()).num_seconds() / 60)
-                .unwrap_or_default()
-                .max(0);
-            let delay_mins = d.delay.map(|d| d / 60);
+                .unwrap_or_default();
+            if actual_mins < -i64::from(grace_minutes) {
+                continue;
+            }
+            let walk_minutes = walk_minutes.get(d.stop_id.as_str()).copied();
+            let reachable = walk_minutes.is_none_or(|walk| actual_mins >= walk);
+            if !reachable && stops.hide_unreachable {
+                continue;
+            }
+            let leave_in_mins = walk_minutes
+                .filter(|_| stops.show_leave_in)
+                .map(|walk| actual_mins - walk);
+            let (delay_mins, implausible_delay_mins) = match d.delay.map(|d| d / 60) {
+                Some(delay) if d.implausible_delay => (None, Some(delay)),
+                delay => (delay, None),
+            };
 
-            let abs_time = d
-                .when
+            let abs_time = when.map(|w| w.with_timezone(&Local).format("%H:%M").to_string());
+            let planned_time = d
+                .planned_when
                 .map(|w| w.with_timezone(&Local).format("%H:%M").to_string());
+            let day_offset = when
+                .map(|w| {
+                    duration::service_day_offset(w.with_timezone(&Local), now, service_day_start)
+                })
+                .unwrap_or_default();
+
+            let warnings = d.warnings().map(str::to_string).collect();
 
             entries.push(DisplayEntry {
+                trip_id: d.trip_id.clone(),
                 line,
                 dir,
                 actual_mins,
                 delay_mins,
+                implausible_delay_mins,
                 symbol,
                 hex,
                 abs_time,
+                planned_time,
+                day_offset,
+                warnings,
+                confidence: Confidence::of(d),
+                platform: d.platform.clone(),
+                platform_changed: d.changed_platform().is_some(),
+                cancelled: d.cancelled,
+                ghost: d.ghost,
+                reachable,
+                leave_in_mins,
+                later_mins: Vec::new(),
+                headway_mins: None,
+                station: None,
             });
         }
-        out.push((station_name.clone(), entries));
+        out.push((header, entries));
     }
     out
 }
+
+/// Order of the departures within a station.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    /// Soonest first
+    #[default]
+    Time,
+    /// By line name, then soonest first
+    Line,
+    /// Most delayed first
+    Delay,
+}
+
+impl SortOrder {
+    pub fn sort(self, entries: &mut [DisplayEntry]) {
+        // all stable, so departures that compare equal stay soonest first
+        match self {
+            SortOrder::Time => entries.sort_by_key(|e| e.actual_mins),
+            SortOrder::Line => entries.sort_by(|a, b| a.line.cmp(&b.line)),
+            SortOrder::Delay => {
+                entries.sort_by_key(|e| std::cmp::Reverse(e.delay_mins.unwrap_or_default()))
+            }
+        }
+    }
+}
+
+impl fmt::Display for SortOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SortOrder::Time => "time",
+            SortOrder::Line => "line",
+            SortOrder::Delay => "delay",
+        })
+    }
+}
+
+/// Flattens all stations into a single list, soonest first, for stops that are all within a short
+/// walk. Each entry keeps its station.
+pub fn merge_stations(
+    display_lines: Vec<(StationHeader, Vec<DisplayEntry>)>,
+) -> Vec<(StationHeader, Vec<DisplayEntry>)> {
+    let mut entries: Vec<DisplayEntry> = display_lines
+        .into_iter()
+        .flat_map(|(header, entries)| {
+            entries.into_iter().map(move |mut e| {
+                e.station = Some(header.clone());
+                e
+            })
+        })
+        .collect();
+    // stable, so departures in the same minute stay in config order
+    SortOrder::Time.sort(&mut entries);
+
+    let header = StationHeader {
+        stop_id: String::new(),
+        name: "All stops".to_string(),
+        icon: None,
+        hex: None,
+        arrivals: false,
+        distance: None,
+    };
+    vec![(header, entries)]
+}
+
+/// Puts the arrivals of each stop next to its departures, for the composite view, as station,
+/// departures and arrivals. Stations keep their order; a stop with only one of them gets an empty
+/// list for the other.
+pub fn pair_arrivals(
+    display_lines: Vec<(StationHeader, Vec<DisplayEntry>)>,
+) -> Vec<(StationHeader, Vec<DisplayEntry>, Vec<DisplayEntry>)> {
+    // departures and arrivals, None until seen
+    type Side = Option<Vec<DisplayEntry>>;
+    let mut pairs: Vec<(StationHeader, Side, Side)> = Vec::new();
+    for (header, entries) in display_lines {
+        let pair = pairs.iter_mut().find(|(h, departures, arrivals)| {
+            h.stop_id == header.stop_id
+                && if header.arrivals {
+                    arrivals.is_none()
+                } else {
+                    departures.is_none()
+                }
+        });
+        match (pair, header.arrivals) {
+            (Some((_, _, arrivals)), true) => *arrivals = Some(entries),
+            // named like the departures, the arrivals header is labeled as such
+            (Some((h, departures, _)), false) => {
+                *h = header;
+                *departures = Some(entries);
+            }
+            (None, true) => pairs.push((header, None, Some(entries))),
+            (None, false) => pairs.push((header, Some(entries), None)),
+        }
+    }
+    pairs
+        .into_iter()
+        .map(|(header, departures, arrivals)| {
+            (
+                header,
+                departures.unwrap_or_default(),
+                arrivals.unwrap_or_default(),
+            )
+        })
+        .collect()
+}
+
+/// Departures shown per line and direction in the grouped view
+pub const GROUP_SIZE: usize = 3;
+
+/// Collapses the upcoming departures of each line and direction into their first one, with the
+/// minutes of up to [`GROUP_SIZE`] departures in total and how often the line goes, estimated
+/// from all of them. Departed, cancelled and unreachable ones keep their own rows.
+pub fn group_by_line(
+    display_lines: Vec<(StationHeader, Vec<DisplayEntry>)>,
+) -> Vec<(StationHeader, Vec<DisplayEntry>)> {
+    display_lines
+        .into_iter()
+        .map(|(header, mut entries)| {
+            SortOrder::Time.sort(&mut entries);
+            let mut grouped: Vec<DisplayEntry> = Vec::new();
+            // minutes of all departures of each group, also beyond GROUP_SIZE
+            let mut times: Vec<Vec<i64>> = Vec::new();
+            for e in entries {
+                let groupable = |e: &DisplayEntry| {
+                    e.actual_mins >= 0 && e.reachable && !e.cancelled && !e.ghost
+                };
+                let group = grouped.iter().position(|g| {
+                    groupable(g)
+                        && g.line == e.line
+                        && g.dir == e.dir
+                        && g.station.as_ref().map(|s| &s.stop_id)
+                            == e.station.as_ref().map(|s| &s.stop_id)
+                });
+                match group {
+                    Some(i) if groupable(&e) => {
+                        times[i].push(e.actual_mins);
+                        if grouped[i].later_mins.len() + 1 < GROUP_SIZE {
+                            grouped[i].later_mins.push(e.actual_mins);
+                        }
+                    }
+                    _ => {
+                        times.push(vec![e.actual_mins]);
+                        grouped.push(e);
+                    }
+                }
+            }
+            for (group, times) in grouped.iter_mut().zip(times) {
+                if !group.later_mins.is_empty() {
+                    group.headway_mins = summary::headway(times);
+                }
+            }
+            (header, grouped)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use serde_json::json;
+
+    pub(crate) fn header(stop_id: &str, name: &str) -> StationHeader {
+        StationHeader {
+            stop_id: stop_id.to_string(),
+            name: name.to_string(),
+            icon: None,
+            hex: None,
+            arrivals: false,
+            distance: None,
+        }
+    }
+
+    /// Board of a single stop with `departures` as sent by the API
+    fn board(departures: serde_json::Value) -> Board {
+        let stop = crate::InputStop::new("1".to_string(), "Alex".to_string());
+        let response = serde_json::from_value(json!({ "departures": departures })).unwrap();
+        Board {
+            stations: vec![crate::board::StationBoard::new(&stop, response)],
+            fetched_at: chrono::Local::now(),
+        }
+    }
+
+    /// On time, without trip id
+    pub(crate) fn entry(line: &str, actual_mins: i64) -> DisplayEntry {
+        DisplayEntry {
+            trip_id: None,
+            line: line.to_string(),
+            dir: "Wittenau".to_string(),
+            actual_mins,
+            delay_mins: Some(0),
+            implausible_delay_mins: None,
+            symbol: "🚇",
+            hex: "#224F86",
+            abs_time: None,
+            planned_time: None,
+            day_offset: 0,
+            warnings: vec![],
+            confidence: Confidence::Realtime,
+            platform: None,
+            platform_changed: false,
+            cancelled: false,
+            ghost: false,
+            reachable: true,
+            leave_in_mins: None,
+            later_mins: vec![],
+            headway_mins: None,
+            station: None,
+        }
+    }
+
+    #[test]
+    fn pairs_the_arrivals_of_each_stop_with_its_departures() {
+        let arrivals = |stop_id: &str| StationHeader {
+            arrivals: true,
+            ..header(stop_id, "Alex")
+        };
+        let pairs = pair_arrivals(vec![
+            (header("1", "Alex"), vec![entry("U8", 2)]),
+            (arrivals("1"), vec![entry("U2", 3), entry("U5", 4)]),
+            (arrivals("2"), vec![entry("M10", 1)]),
+            (header("3", "Hermannstr."), vec![]),
+        ]);
+        let lines = |entries: &[DisplayEntry]| -> Vec<String> {
+            entries.iter().map(|e| e.line.clone()).collect()
+        };
+        let summary: Vec<_> = pairs
+            .iter()
+            .map(|(h, d, a)| (h.stop_id.as_str(), h.arrivals, lines(d), lines(a)))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "1",
+                    false,
+                    vec!["U8".to_string()],
+                    vec!["U2".to_string(), "U5".to_string()]
+                ),
+                ("2", true, vec![], vec!["M10".to_string()]),
+                ("3", false, vec![], vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn sorts_ties_soonest_first_and_unknown_delays_as_on_time() {
+        let delayed = |line: &str, actual_mins: i64, delay_mins: Option<i64>| DisplayEntry {
+            delay_mins,
+            ..entry(line, actual_mins)
+        };
+        let board = || {
+            vec![
+                delayed("U8", 1, None),
+                delayed("M10", 2, Some(3)),
+                delayed("U2", 3, Some(0)),
+                delayed("M10", 4, Some(3)),
+                DisplayEntry {
+                    implausible_delay_mins: Some(300),
+                    ..delayed("U8", 6, None)
+                },
+                delayed("U2", 7, Some(-1)),
+            ]
+        };
+        let order = |sort: SortOrder| -> Vec<(String, i64)> {
+            // reversed, then soonest first as they come from the board
+            let mut entries = board();
+            entries.reverse();
+            SortOrder::Time.sort(&mut entries);
+            sort.sort(&mut entries);
+            entries
+                .into_iter()
+                .map(|e| (e.line, e.actual_mins))
+                .collect()
+        };
+        let expected = |order: &[(&str, i64)]| -> Vec<(String, i64)> {
+            order.iter().map(|(l, m)| (l.to_string(), *m)).collect()
+        };
+
+        assert_eq!(
+            order(SortOrder::Time),
+            expected(&[
+                ("U8", 1),
+                ("M10", 2),
+                ("U2", 3),
+                ("M10", 4),
+                ("U8", 6),
+                ("U2", 7)
+            ])
+        );
+        assert_eq!(
+            order(SortOrder::Line),
+            expected(&[
+                ("M10", 2),
+                ("M10", 4),
+                ("U2", 3),
+                ("U2", 7),
+                ("U8", 1),
+                ("U8", 6)
+            ])
+        );
+        // unknown and implausible delays rank like trips on time, early trips last
+        assert_eq!(
+            order(SortOrder::Delay),
+            expected(&[
+                ("M10", 2),
+                ("M10", 4),
+                ("U8", 1),
+                ("U2", 3),
+                ("U8", 6),
+                ("U2", 7)
+            ])
+        );
+    }
+
+    #[test]
+    fn merges_stations_soonest_first_keeping_their_headers() {
+        let merged = merge_stations(vec![
+            (header("1", "Alex"), vec![entry("U8", 2), entry("U2", 9)]),
+            (
+                header("2", "Hermannstr."),
+                vec![entry("M10", 1), entry("S41", 9)],
+            ),
+        ]);
+        assert_eq!(merged.len(), 1);
+        let (all, entries) = &merged[0];
+        assert_eq!(all.name, "All stops");
+        let order: Vec<(&str, &str)> = entries
+            .iter()
+            .map(|e| (e.line.as_str(), e.station.as_ref().unwrap().name.as_str()))
+            .collect();
+        // departures in the same minute stay in config order
+        assert_eq!(
+            order,
+            vec![
+                ("M10", "Hermannstr."),
+                ("U8", "Alex"),
+                ("U2", "Alex"),
+                ("S41", "Hermannstr."),
+            ]
+        );
+    }
+
+    #[test]
+    fn grouping_collapses_lines_and_directions() {
+        let departure = |dir: &str, actual_mins| DisplayEntry {
+            dir: dir.to_string(),
+            ..entry("U6", actual_mins)
+        };
+        let lines = group_by_line(vec![(
+            header("1", "Alex"),
+            vec![
+                departure("Alt-Tegel", 2),
+                departure("Alt-Mariendorf", 5),
+                departure("Alt-Tegel", 9),
+                departure("Alt-Tegel", 17),
+                departure("Alt-Tegel", 25),
+            ],
+        )]);
+        let entries = &lines[0].1;
+        assert_eq!(entries.len(), 2);
+        // the departure in 25min is not shown, but tells how often the line goes
+        assert_eq!(entries[0].countdown(), "2, 9, 17 min, every ~8min");
+        assert_eq!(entries[1].countdown(), "5min");
+    }
+
+    #[test]
+    fn cancelled_departures_keep_their_planned_time() {
+        let planned = chrono::Local::now() + chrono::Duration::minutes(10);
+        let board = board(json!([
+            {"tripId": "1", "when": null, "plannedWhen": planned.to_rfc3339(), "cancelled": true}
+        ]));
+        let config: InputStops = serde_yaml::from_str("stops: []").unwrap();
+        let entry = &build_display_lines(&board, &config)[0].1[0];
+        assert!(entry.cancelled);
+        assert!(entry.actual_mins >= 9);
+        assert_eq!(entry.abs_time, Some(planned.format("%H:%M").to_string()));
+        assert_eq!(entry.countdown(), "CANCELLED");
+    }
+
+    #[test]
+    fn implausible_delays_are_not_shown() {
+        let planned = chrono::Local::now() + chrono::Duration::minutes(10);
+        let glitched = planned + chrono::Duration::minutes(3000);
+        let mut board = board(json!([{
+            "tripId": "1",
+            "when": glitched.to_rfc3339(),
+            "plannedWhen": planned.to_rfc3339(),
+            "delay": 3000 * 60,
+        }]));
+        let config: InputStops = serde_yaml::from_str("stops: []").unwrap();
+        board.flag_implausible_delays(config.max_delay_minutes);
+        let entry = &build_display_lines(&board, &config)[0].1[0];
+        assert_eq!(entry.delay_mins, None);
+        assert_eq!(entry.implausible_delay_mins, Some(3000));
+        assert_eq!(entry.delay_text(), "?");
+        assert!(entry.actual_mins <= 10);
+    }
+
+    #[test]
+    fn grouped_stops_keep_their_walk() {
+        let config: InputStops = serde_yaml::from_str(
+            "stops:\n  - id: '1'\n    name: Upper\n    group: Ostkreuz\n  - id: '2'\n    name: Lower\n    group: Ostkreuz\n    walk_minutes: 10\n",
+        )
+        .unwrap();
+        let when = (chrono::Local::now() + chrono::Duration::minutes(5)).to_rfc3339();
+        let station = |stop: &crate::InputStop, trip_id: &str| {
+            let departures = json!({"departures": [{"tripId": trip_id, "when": when}]});
+            crate::board::StationBoard::new(stop, serde_json::from_value(departures).unwrap())
+        };
+        let mut board = Board {
+            stations: vec![
+                station(&config.stops[0], "upper"),
+                station(&config.stops[1], "lower"),
+            ],
+            fetched_at: chrono::Local::now(),
+        };
+        board.merge_groups(&config.stops);
+
+        let lines = build_display_lines(&board, &config);
+        let reachable: Vec<_> = lines[0]
+            .1
+            .iter()
+            .map(|e| (e.trip_id.as_deref().unwrap(), e.reachable))
+            .collect();
+        assert_eq!(reachable, [("upper", true), ("lower", false)]);
+    }
+}
@@ -0,0 +1,159 @@
+use crate::api::departures::{DeparturesApi, DeparturesError};
+use crate::board::Board;
+use crate::view::{build_display_lines, DisplayEntry, ResultDisplay};
+use crate::InputStops;
+use async_trait::async_trait;
+use derive_builder::Builder;
+use eframe::egui::{self, Color32, RichText};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::warn;
+
+/// Departures shown per station, to keep the window small
+const MAX_ROWS: usize = 5;
+
+/// Minimal board in a small undecorated window that stays on top of the others.
+///
+/// Where the window goes is up to the window manager. It is titled "bvg-departures", e.g. for a
+/// sway rule moving it to a corner.
+#[derive(Builder)]
+#[builder(pattern = "owned")]
+pub struct OverlayDisplay<D: DeparturesApi> {
+    api_client: D,
+    stops: InputStops,
+    #[builder(default = "Duration::from_secs(30)")]
+    refresh_interval: Duration,
+}
+
+/// Latest results of the refresh task
+#[derive(Default)]
+struct Latest {
+    /// Last board fetched, kept when a refresh fails
+    board: Option<Board>,
+    /// Why the last refresh failed, None once one succeeds again
+    error: Option<String>,
+}
+
+struct OverlayApp {
+    stops: InputStops,
+    latest: Arc<Mutex<Latest>>,
+}
+
+#[async_trait]
+impl<D: DeparturesApi + Clone + Send + Sync + 'static> ResultDisplay for OverlayDisplay<D> {
+    async fn display(&self) -> anyhow::Result<()> {
+        let latest = Arc::new(Mutex::new(Latest::default()));
+        let refresh = tokio::spawn({
+            let (client, stops, latest) =
+                (self.api_client.clone(), self.stops.clone(), latest.clone());
+            let interval = self.refresh_interval;
+            async move {
+                loop {
+                    let result = client.get_departures(&stops).await;
+                    if let Err(e) = &result {
+                        warn!("Could not fetch departures: {}", e);
+                    }
+                    update(&mut latest.lock().unwrap(), result);
+                    tokio::time::sleep(interval).await;
+                }
+            }
+        });
+
+        let options = eframe::NativeOptions {
+            viewport: egui::ViewportBuilder::default()
+                .with_title("bvg-departures")
+                .with_inner_size([360.0, 240.0])
+                .with_always_on_top()
+                .with_decorations(false),
+            ..Default::default()
+        };
+        let app = OverlayApp {
+            stops: self.stops.clone(),
+            latest,
+        };
+        // blocks until the window is closed. winit wants the main thread, which runs the main
+        // future, so the window stays on it while the runtime moves other tasks off it
+        let result = tokio::task::block_in_place(|| {
+            eframe::run_native("bvg-departures", options, Box::new(|_| Ok(Box::new(app))))
+        });
+        refresh.abort();
+        result.map_err(|e| anyhow::anyhow!("Could not open the overlay: {}", e))
+    }
+}
+
+/// Keeps the last board when a refresh fails, the error is shown above it.
+fn update(latest: &mut Latest, result: Result<Board, DeparturesError>) {
+    match result {
+        Ok(board) => {
+            latest.board = Some(board);
+            latest.error = None;
+        }
+        Err(e) => latest.error = Some(e.to_string()),
+    }
+}
+
+impl eframe::App for OverlayApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            let latest = self.latest.lock().unwrap();
+            if let Some(e) = &latest.error {
+                ui.colored_label(Color32::RED, format!("Refresh failed: {}", e));
+            }
+            let Some(board) = &latest.board else {
+                if latest.error.is_none() {
+                    ui.label("Loading departures…");
+                }
+                return;
+            };
+            if let Some(banner) = board.offline_banner() {
+                ui.colored_label(Color32::YELLOW, banner);
+            }
+            for (station, entries) in build_display_lines(board, &self.stops) {
+                ui.strong(station.name.as_str());
+                for e in entries.iter().take(MAX_ROWS) {
+                    entry_row(ui, e);
+                }
+                ui.add_space(4.0);
+            }
+        });
+        // countdowns change every minute, new boards every refresh
+        ctx.request_repaint_after(Duration::from_secs(1));
+    }
+}
+
+fn entry_row(ui: &mut egui::Ui, e: &DisplayEntry) {
+    let gone = e.actual_mins < 0 || !e.reachable;
+    let text = |text: String| {
+        let text = RichText::new(text).monospace();
+        if gone {
+            text.weak()
+        } else {
+            text
+        }
+    };
+    ui.horizontal(|ui| {
+        let line = text(format!("{:<5}", e.line));
+        ui.label(if gone {
+            line
+        } else {
+            line.color(hex_color(e.hex))
+        });
+        ui.label(text(e.dir.clone()));
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            ui.label(text(e.countdown()));
+            if !e.cancelled {
+                let delay = ui.label(text(e.delay_text()));
+                if let Some(reported) = e.implausible_delay_mins {
+                    delay.on_hover_text(format!(
+                        "Reported delay of {:+}min, likely a glitch in the data",
+                        reported
+                    ));
+                }
+            }
+        });
+    });
+}
+
+fn hex_color(hex: &str) -> Color32 {
+    Color32::from_hex(hex).unwrap_or(Color32::WHITE)
+}
@@ -0,0 +1,292 @@
+use crate::announce::Announcer;
+use crate::api::departures::DeparturesApi;
+use crate::exec::ExecHook;
+use crate::schedule::AdaptiveInterval;
+use crate::snapshot::BoardSnapshot;
+use crate::view::{DisplayEntry, ResultDisplay, StationHeader};
+use crate::InputStops;
+use async_trait::async_trait;
+use clap::ValueEnum;
+use derive_builder::Builder;
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::{debug, warn};
+use unicode_width::UnicodeWidthChar;
+
+/// How line names are marked up in the status line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum StatusMarkup {
+    /// No markup
+    #[default]
+    Plain,
+    /// Polybar format tags, e.g. `%{F#00539F}U8%{F-}`
+    Polybar,
+}
+
+impl StatusMarkup {
+    fn can_dim(self) -> bool {
+        self != StatusMarkup::Plain
+    }
+}
+
+/// Color of departures you cannot catch anymore, see `walk_minutes`
+const DIMMED: &str = "#808080";
+
+/// Appended while rate limited, if it fits
+const THROTTLED: &str = " (throttled)";
+
+/// Parts of each departure shown, from most to least detailed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Detail {
+    Full,
+    NoDelay,
+    NoTime,
+}
+
+/// Continuously writes a single status line to a file, for status bars that can `cat` it.
+///
+/// The template is applied to each of the next departures across all stations. Placeholders:
+/// `{station}`, `{line}`, `{direction}`, `{time}`, `{countdown}`, `{delay}`.
+#[derive(Builder)]
+#[builder(pattern = "owned")]
+pub struct StatusFileDisplay<D: DeparturesApi> {
+    api_client: D,
+    stops: InputStops,
+    path: PathBuf,
+    #[builder(default = "String::from(\"{line} {countdown}\")")]
+    template: String,
+    #[builder(default = "String::from(\" | \")")]
+    separator: String,
+    /// Number of departures in the line
+    #[builder(default = "3")]
+    count: usize,
+    #[builder(default)]
+    markup: StatusMarkup,
+    /// Max width of the line, markup not counted. Less important parts are left out to fit.
+    #[builder(default)]
+    max_width: Option<usize>,
+    #[builder(default = "Duration::from_secs(30)")]
+    interval: Duration,
+    /// Kept up to date with the board of the line
+    #[builder(default)]
+    snapshot: Option<BoardSnapshot>,
+    /// Command to run after each refresh.
+    #[builder(default)]
+    exec: Option<ExecHook>,
+}
+
+#[async_trait]
+impl<D: DeparturesApi + Sync> ResultDisplay for StatusFileDisplay<D> {
+    async fn display(&self) -> anyhow::Result<()> {
+        let mut interval = AdaptiveInterval::new(self.interval);
+        let mut last_line: Option<String> = None;
+        let mut announcer = Announcer::new(&self.stops);
+        loop {
+            let mut line = match self.api_client.get_departures(&self.stops).await {
+                Ok(board) => {
+                    interval.after(&board);
+                    if let Some(snapshot) = &self.snapshot {
+                        snapshot.update(&board);
+                    }
+                    if let Some(exec) = &self.exec {
+                        // a slow command must not hold up the refresh
+                        exec.run(&board);
+                    }
+                    let display_lines = crate::view::build_display_lines(&board, &self.stops);
+                    if let Some(announcer) = &mut announcer {
+                        announcer.announce(&display_lines);
+                    }
+                    let mut line = self.status_line(&display_lines);
+                    last_line = Some(line.clone());
+                    if let Some(since) = board.stations.iter().filter_map(|s| s.stale_since).min() {
+                        let offline = format!(" (offline, {})", since.format("%H:%M"));
+                        if self
+                            .max_width
+                            .is_none_or(|max| visible_width(&line) + offline.len() <= max)
+                        {
+                            line.push_str(&offline);
+                        }
+                    }
+                    line
+                }
+                Err(e) if e.rate_limit().is_some() => {
+                    interval.throttled(e.rate_limit().flatten());
+                    // stale departures are better than none
+                    last_line
+                        .clone()
+                        .unwrap_or_else(|| "departures".to_string())
+                }
+                Err(e) => {
+                    warn!("Could not fetch departures: {}", e);
+                    "departures unavailable".to_string()
+                }
+            };
+            if interval.is_throttled()
+                && self
+                    .max_width
+                    .is_none_or(|max| visible_width(&line) + THROTTLED.len() <= max)
+            {
+                line.push_str(THROTTLED);
+            }
+
+            if let Err(e) = self.write_atomically(&line) {
+                warn!("Could not write {}: {}", self.path.display(), e);
+            }
+            debug!("Wrote status line '{}'", line);
+
+            tokio::time::sleep(interval.current()).await;
+        }
+    }
+}
+
+impl<D: DeparturesApi> StatusFileDisplay<D> {
+    fn status_line(&self, display_lines: &[(StationHeader, Vec<DisplayEntry>)]) -> String {
+        let mut next: Vec<(&StationHeader, &DisplayEntry)> = display_lines
+            .iter()
+            .flat_map(|(station, entries)| entries.iter().map(move |e| (station, e)))
+            // without markup there is no way to grey out what you cannot catch anymore
+            .filter(|(_, e)| e.actual_mins >= 0 && (e.reachable || self.markup.can_dim()))
+            .collect();
+        next.sort_by_key(|(_, e)| e.actual_mins);
+
+        let line = |count: usize, detail: Detail| {
+            next.iter()
+                .take(count)
+                .map(|(station, e)| self.format_entry(station, e, detail))
+                .collect::<Vec<_>>()
+                .join(&self.separator)
+        };
+        let Some(max_width) = self.max_width else {
+            return line(self.count, Detail::Full);
+        };
+
+        // drop the least important parts first: the delay, then the time, then further departures
+        let count = self.count.min(next.len()).max(1);
+        let candidates = [(count, Detail::Full), (count, Detail::NoDelay)]
+            .into_iter()
+            .chain((1..=count).rev().map(|c| (c, Detail::NoTime)));
+        let mut shortest = String::new();
+        for (count, detail) in candidates {
+            shortest = line(count, detail);
+            if visible_width(&shortest) <= max_width {
+                return shortest;
+            }
+        }
+        truncate_visible(&shortest, max_width)
+    }
+
+    fn format_entry(&self, station: &StationHeader, e: &DisplayEntry, detail: Detail) -> String {
+        let line = match self.markup {
+            StatusMarkup::Polybar if e.reachable => format!("%{{F{}}}{}%{{F-}}", e.hex, e.line),
+            _ => e.line.clone(),
+        };
+        let delay = match e.delay_mins {
+            Some(d) if d != 0 && detail == Detail::Full => format!("{:+}", d),
+            _ => String::new(),
+        };
+        let time = match detail {
+            Detail::NoTime => "",
+            _ => e.abs_time.as_deref().unwrap_or("--"),
+        };
+
+        let mut entry = self
+            .template
+            .replace("{station}", &station.name)
+            .replace("{line}", &line)
+            .replace("{direction}", &e.dir)
+            .replace("{time}", time)
+            .replace("{countdown}", &e.countdown())
+            .replace("{delay}", &delay);
+        if detail != Detail::Full {
+            // no double spaces where parts were left out
+            entry = entry
+                .split(' ')
+                .filter(|w| !w.is_empty())
+                .collect::<Vec<_>>()
+                .join(" ");
+        }
+        match self.markup {
+            StatusMarkup::Polybar if !e.reachable => format!("%{{F{}}}{}%{{F-}}", DIMMED, entry),
+            _ => entry,
+        }
+    }
+
+    /// Bars may read the file at any time, so never let them see a half-written line.
+    fn write_atomically(&self, line: &str) -> std::io::Result<()> {
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        std::fs::write(&tmp, format!("{}\n", line))?;
+        std::fs::rename(&tmp, &self.path)
+    }
+}
+
+/// Width of `line` as shown by the bar, without polybar format tags like `%{F-}`.
+fn visible_width(line: &str) -> usize {
+    segments(line)
+        .filter(|(_, tag)| !tag)
+        .flat_map(|(text, _)| text.chars())
+        .filter_map(|c| c.width())
+        .sum()
+}
+
+/// Cuts `line` to `max_width` visible characters, ending in "…". Tags are kept, so colors are
+/// still reset at the end.
+fn truncate_visible(line: &str, max_width: usize) -> String {
+    let mut out = String::new();
+    let mut width = 0;
+    let mut cut = false;
+    for (text, tag) in segments(line) {
+        if tag {
+            out.push_str(text);
+            continue;
+        }
+        for c in text.chars() {
+            let w = c.width().unwrap_or(0);
+            if !cut && width + w + 1 > max_width {
+                out.push('…');
+                cut = true;
+            }
+            if !cut {
+                out.push(c);
+                width += w;
+            }
+        }
+    }
+    out
+}
+
+/// Splits `line` into text and `%{...}` tags, with true for tags.
+fn segments(line: &str) -> impl Iterator<Item = (&str, bool)> {
+    let mut rest = line;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let (segment, tag) = match rest.find("%{") {
+            Some(0) => match rest.find('}') {
+                Some(end) => (&rest[..=end], true),
+                None => (rest, false),
+            },
+            Some(start) => (&rest[..start], false),
+            None => (rest, false),
+        };
+        rest = &rest[segment.len()..];
+        Some((segment, tag))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn width_ignores_polybar_tags() {
+        let line = "%{F#00539F}U8%{F-} 4min | %{F#95276E}M41%{F-} 6min";
+        assert_eq!(visible_width(line), 18);
+        assert_eq!(
+            truncate_visible(line, 12),
+            "%{F#00539F}U8%{F-} 4min | %{F#95276E}M…%{F-}"
+        );
+        assert_eq!(truncate_visible("U8 4min", 20), "U8 4min");
+    }
+}
@@ -1,9 +1,15 @@
 use crate::api::departures::DeparturesApi;
-use crate::view::ResultDisplay;
+use crate::board::Board;
+use crate::exec::ExecHook;
+use crate::view::summary::{filter_note, station_summary, warnings};
+use crate::view::{
+    group_by_line, merge_stations, pair_arrivals, DisplayEntry, ResultDisplay, StationHeader,
+};
 use crate::InputStops;
 use async_trait::async_trait;
 use colored::{Color, ColoredString, Colorize};
 use derive_builder::Builder;
+use std::io::Write;
 use tracing::info;
 
 #[derive(Builder)]
@@ -11,29 +17,242 @@ use tracing::info;
 pub struct StdoutDisplay<D: DeparturesApi> {
     api_client: D,
     stops: InputStops,
+    /// Emit stable tab-separated rows (station, line, direction, time, minutes, delay, cancelled)
+    /// instead of the table.
+    #[builder(default)]
+    porcelain: bool,
+    /// Use colors and emoji. Should only be enabled if stdout is a terminal.
+    #[builder(default)]
+    decorate: bool,
+    /// Command to run once the departures have been fetched.
+    #[builder(default)]
+    exec: Option<ExecHook>,
+    /// One list for all stations, soonest first
+    #[builder(default)]
+    merged: bool,
+    /// One row per line and direction with the next departures
+    #[builder(default)]
+    grouped: bool,
+    /// The arrivals of each stop next to its departures. Expects a config from
+    /// [`InputStops::with_arrivals`].
+    #[builder(default)]
+    with_arrivals: bool,
 }
 
 #[async_trait]
 impl<D: DeparturesApi + Sync> ResultDisplay for StdoutDisplay<D> {
     async fn display(&self) -> anyhow::Result<()> {
-        let resp = self.api_client.get_departures(&self.stops).await?;
+        let board = self.api_client.get_departures(&self.stops).await?;
 
-        info!("Got departures for {} stations. Display now.", resp.len());
+        info!(
+            "Got departures for {} stations. Display now.",
+            board.stations.len()
+        );
+
+        let mut grouped = crate::view::build_display_lines(&board, &self.stops);
+        if self.merged {
+            grouped = merge_stations(grouped);
+        }
+        if self.grouped {
+            grouped = group_by_line(grouped);
+        }
+
+        if let Some(exec) = &self.exec {
+            let _ = exec.run(&board).await;
+        }
+
+        if self.porcelain {
+            // on stderr, so that the rows stay the same, but scripts can tell failures from
+            // stations without departures
+            for station in &board.stations {
+                if let Some(error) = &station.error {
+                    eprintln!("Could not get departures for {}: {}", station.name, error);
+                }
+            }
+            write_porcelain(&grouped, &mut std::io::stdout().lock())?;
+            return Ok(());
+        }
+
+        if self.with_arrivals {
+            write_composite(
+                &board,
+                grouped,
+                self.decorate,
+                &mut std::io::stdout().lock(),
+            )?;
+            return Ok(());
+        }
+
+        if let Some(banner) = board.offline_banner() {
+            if self.decorate {
+                println!("{}", banner.yellow().bold());
+            } else {
+                println!("{}", banner);
+            }
+            println!();
+        }
+
+        if self.merged {
+            for station in &board.stations {
+                if let Some(error) = &station.error {
+                    println!("Could not get departures for {}: {}", station.name, error);
+                }
+            }
+        }
 
-        let grouped = crate::view::build_display_lines(&resp);
         for (name, entries) in grouped {
-            println!("Station: {}", name);
-            for e in entries {
-                let line_colored = color_line(&e.line, e.hex);
-                let delay_text = match e.delay_mins {
-                    Some(d) if d != 0 => format!(" ({:+}min)", d),
+            let station = board.stations.iter().find(|s| s.stop_id == name.stop_id);
+            let distance = name
+                .distance
+                .as_ref()
+                .map(|d| format!(" · {}", d))
+                .unwrap_or_default();
+            if self.decorate {
+                println!("{}{}", station_heading(&name), distance.dimmed());
+            } else {
+                println!("{}: {}{}", name.label(), name, distance);
+            }
+            if let Some(error) = station.and_then(|s| s.error.as_ref()) {
+                println!("Could not get departures: {}", error);
+                println!();
+                continue;
+            }
+            match station.and_then(filter_note) {
+                Some(note) => println!("{} ({})", station_summary(&entries), note),
+                None => println!("{}", station_summary(&entries)),
+            }
+            let station_width = entries
+                .iter()
+                .filter_map(|e| e.station.as_ref())
+                .map(|s| s.name.chars().count())
+                .max()
+                .unwrap_or_default();
+            // room for "leave in 1h 05m"
+            let countdown_width = if self.stops.show_leave_in {
+                15
+            } else {
+                // e.g. "CANCELLED" or "2, 9, 17 min"
+                entries
+                    .iter()
+                    .map(|e| e.countdown().chars().count() + if self.decorate { 2 } else { 0 })
+                    .max()
+                    .unwrap_or_default()
+                    .max(7)
+            };
+            // platform column, only for stations that have platforms
+            let platform_width = entries
+                .iter()
+                .filter_map(|e| e.platform.as_deref())
+                .map(|p| p.chars().count().max(2))
+                .max();
+            for e in &entries {
+                let mut dir = format!("{:<35}", e.dir);
+                if let Some(width) = platform_width {
+                    let platform = format!(
+                        "{:<w$}",
+                        e.platform.as_deref().unwrap_or_default(),
+                        w = width
+                    );
+                    // dimmed and cancelled rows are styled as a whole
+                    let highlight = self.decorate
+                        && e.platform_changed
+                        && !e.cancelled
+                        && e.actual_mins >= 0
+                        && e.reachable;
+                    if highlight {
+                        dir.push_str(&format!("|{}", platform.yellow().bold()));
+                    } else {
+                        dir.push_str(&format!("|{}", platform));
+                    }
+                }
+                // station column of the merged view
+                let station = e
+                    .station
+                    .as_ref()
+                    .map(|s| format!("{:<w$}|", s.name, w = station_width))
+                    .unwrap_or_default();
+                let arrow = if self.decorate { "→" } else { "->" };
+                let mut delay_text = match (e.delay_mins, e.planned_and_actual(arrow)) {
+                    // e.g. "14:32 → 14:38 (+6)"
+                    (Some(d), Some(times)) if self.stops.show_planned_times && d != 0 => {
+                        format!(" {} ({:+})", times, d)
+                    }
+                    (_, Some(times)) if self.stops.show_planned_times => format!(" {}", times),
+                    _ if e.cancelled => String::new(),
+                    (Some(d), _) if d != 0 => format!(" ({:+}min)", d),
+                    // delay beyond max_delay_minutes
+                    (None, _) if e.implausible_delay_mins.is_some() => " (?)".to_string(),
                     _ => String::new(),
                 };
+                if !e.warnings.is_empty() {
+                    delay_text.push_str(if self.decorate { " ⚠" } else { " (!)" });
+                }
+                if let Some(badge) = e.platform_badge() {
+                    let badge = format!("[{}]", badge);
+                    if self.decorate {
+                        delay_text.push_str(&format!(" {}", badge.yellow().bold()));
+                    } else {
+                        delay_text.push_str(&format!(" {}", badge));
+                    }
+                }
 
-                println!(
-                    "{} {:<6}|{:<35}|{:02}min{}",
-                    e.symbol, line_colored, e.dir, e.actual_mins, delay_text
-                );
+                let countdown = if self.decorate && !e.cancelled {
+                    format!("{} {}", e.confidence.marker(), e.countdown())
+                } else {
+                    e.countdown()
+                };
+                if self.decorate && e.cancelled {
+                    let row = format!("{}{:<6}|{}|", station, e.line, dir);
+                    println!(
+                        "{} {}{}{}",
+                        e.symbol,
+                        row.red().strikethrough(),
+                        format!("{:>w$}", countdown, w = countdown_width)
+                            .red()
+                            .bold(),
+                        delay_text
+                    );
+                } else if self.decorate && (e.actual_mins < 0 || !e.reachable) {
+                    let row = format!(
+                        "{}{:<6}|{}|{:>w$}{}",
+                        station,
+                        e.line,
+                        dir,
+                        countdown,
+                        if e.warnings.is_empty() { "" } else { " ⚠" },
+                        w = countdown_width
+                    );
+                    println!("{} {}", e.symbol, row.dimmed());
+                } else if self.decorate {
+                    let line_colored = color_line(&e.line, e.hex);
+                    println!(
+                        "{} {}{:<6}|{}|{:>w$}{}",
+                        e.symbol,
+                        station,
+                        line_colored,
+                        dir,
+                        countdown,
+                        delay_text,
+                        w = countdown_width
+                    );
+                } else {
+                    println!(
+                        "{}{:<6}|{}|{:>w$}{}",
+                        station,
+                        e.line,
+                        dir,
+                        countdown,
+                        delay_text,
+                        w = countdown_width
+                    );
+                }
+            }
+            for warning in warnings(&entries) {
+                if self.decorate {
+                    println!("{}", format!("⚠ {}", warning).yellow());
+                } else {
+                    println!("{}", warning);
+                }
             }
             println!();
         }
@@ -42,20 +261,187 @@ impl<D: DeparturesApi + Sync> ResultDisplay for StdoutDisplay<D> {
     }
 }
 
+/// One line per departure, fields separated by tabs. Unknown values are left empty. Cancelled
+/// trips are listed at their planned time, with `true` in the last field. Tabs and line breaks in
+/// names are replaced by spaces.
+/// The column order is part of the public interface and must not change; new fields go last.
+fn write_porcelain(
+    grouped: &[(StationHeader, Vec<DisplayEntry>)],
+    out: &mut impl Write,
+) -> std::io::Result<()> {
+    for (name, entries) in grouped {
+        for e in entries {
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                porcelain_field(&e.station.as_ref().unwrap_or(name).name),
+                porcelain_field(&e.line),
+                porcelain_field(&e.dir),
+                e.abs_time.as_deref().unwrap_or_default(),
+                e.actual_mins,
+                e.delay_mins.map(|d| d.to_string()).unwrap_or_default(),
+                e.cancelled
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn porcelain_field(value: &str) -> String {
+    value.replace(['\t', '\n', '\r'], " ")
+}
+
+/// Departures and arrivals of each stop in adjacent columns, e.g. to pick someone up.
+fn write_composite(
+    board: &Board,
+    display_lines: Vec<(StationHeader, Vec<DisplayEntry>)>,
+    decorate: bool,
+    out: &mut impl Write,
+) -> std::io::Result<()> {
+    if let Some(banner) = board.offline_banner() {
+        writeln!(out, "{}\n", banner)?;
+    }
+    for (name, departures, arrivals) in pair_arrivals(display_lines) {
+        if decorate {
+            writeln!(out, "{}", station_heading(&name))?;
+        } else {
+            writeln!(out, "{}: {}", name.label(), name)?;
+        }
+        let stations = board.stations.iter().filter(|s| s.stop_id == name.stop_id);
+        for station in stations {
+            if let Some(error) = &station.error {
+                let kind = if station.arrivals {
+                    "arrivals"
+                } else {
+                    "departures"
+                };
+                writeln!(out, "Could not get {}: {}", kind, error)?;
+            }
+        }
+        let column = |title: &str, entries: &[DisplayEntry]| -> Vec<String> {
+            std::iter::once(title.to_string())
+                .chain(entries.iter().map(composite_cell))
+                .collect()
+        };
+        let left = column("Departures", &departures);
+        let right = column("Arrivals", &arrivals);
+        let width = left
+            .iter()
+            .map(|c| c.chars().count())
+            .max()
+            .unwrap_or_default();
+        for i in 0..left.len().max(right.len()) {
+            let row = format!(
+                "{:<w$} | {}",
+                left.get(i).map(String::as_str).unwrap_or_default(),
+                right.get(i).map(String::as_str).unwrap_or_default(),
+                w = width
+            );
+            writeln!(out, "{}", row.trim_end())?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// e.g. "U8    Wittenau 4 min (+2min)"
+fn composite_cell(e: &DisplayEntry) -> String {
+    let mut cell = format!("{:<6}{} {}", e.line, e.dir, e.countdown());
+    let delay = e.delay_text();
+    if !delay.is_empty() && !e.cancelled {
+        cell.push_str(&format!(" ({})", delay));
+    }
+    cell
+}
+
+fn station_heading(header: &StationHeader) -> String {
+    let icon = header
+        .icon
+        .as_ref()
+        .map(|i| format!("{} ", i))
+        .unwrap_or_default();
+    let name = format!("{}: {}", header.label(), header.name);
+    match header.hex.as_deref().and_then(hex_to_color) {
+        Some(color) => format!("{}{}", icon, name.color(color).bold()),
+        None => format!("{}{}", icon, name),
+    }
+}
+
 /// Watch out: If the terminal does not support true color, the colors may look different!
 /// This is the case with the RustRover internal terminal.
 fn color_line(line: &str, hex: &str) -> ColoredString {
     // Use the supplied hex color and convert to colored::Color
-    line.color(hex_to_color(hex)).bold()
+    match hex_to_color(hex) {
+        Some(color) => line.color(color).bold(),
+        None => line.bold(),
+    }
 }
 
-fn hex_to_color(hex: &str) -> Color {
+/// None unless `hex` is "#RRGGBB". Stop colors are only checked when the config is loaded.
+fn hex_to_color(hex: &str) -> Option<Color> {
     let hex = hex.trim_start_matches('#');
+    let channel = |range| u8::from_str_radix(hex.get(range)?, 16).ok();
+    Some(Color::TrueColor {
+        r: channel(0..2)?,
+        g: channel(2..4)?,
+        b: channel(4..6)?,
+    })
+}
 
-    // Parse the 3 pairs of hex digits
-    let r = u8::from_str_radix(&hex[0..2], 16).unwrap_or(255);
-    let g = u8::from_str_radix(&hex[2..4], 16).unwrap_or(255);
-    let b = u8::from_str_radix(&hex[4..6], 16).unwrap_or(255);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::view::tests::{entry, header};
 
-    Color::TrueColor { r, g, b }
+    #[test]
+    fn shows_arrivals_next_to_departures() {
+        let arrivals = StationHeader {
+            arrivals: true,
+            ..header("1", "Alex")
+        };
+        let late = DisplayEntry {
+            delay_mins: Some(2),
+            ..entry("U8", 4)
+        };
+        let display_lines = vec![
+            (header("1", "Alex"), vec![late]),
+            (arrivals, vec![entry("U2", 1), entry("U5", 12)]),
+        ];
+        let board = Board {
+            stations: vec![],
+            fetched_at: chrono::Local::now(),
+        };
+        let mut out = vec![];
+        write_composite(&board, display_lines, false, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Station: Alex\n\
+             Departures                  | Arrivals\n\
+             U8    Wittenau 4min (+2min) | U2    Wittenau 1min\n\
+             \x20                           | U5    Wittenau 12min\n\n"
+        );
+    }
+
+    #[test]
+    fn porcelain_rows_keep_their_seven_columns() {
+        let on_time = DisplayEntry {
+            abs_time: Some("12:04".to_string()),
+            ..entry("U8", 4)
+        };
+        let cancelled = DisplayEntry {
+            dir: "S Hermannstr.\tvia\nNeukölln".to_string(),
+            abs_time: Some("12:10".to_string()),
+            delay_mins: None,
+            cancelled: true,
+            ..entry("U8", 10)
+        };
+        let grouped = vec![(header("1", "Alex\n"), vec![on_time, cancelled])];
+        let mut out = vec![];
+        write_porcelain(&grouped, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Alex \tU8\tWittenau\t12:04\t4\t0\tfalse\n\
+             Alex \tU8\tS Hermannstr. via Neukölln\t12:10\t10\t\ttrue\n"
+        );
+    }
 }
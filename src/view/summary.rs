@@ -0,0 +1,84 @@
+use crate::board::StationBoard;
+use crate::view::duration::format_countdown;
+use crate::view::DisplayEntry;
+
+/// How many distinct lines are mentioned in a summary.
+const MAX_LINES: usize = 3;
+
+/// One-line rollup of a station, e.g. "next U8 in 4min, next M10 in 2min, 1 warning".
+///
+/// Lines are listed by their next departure you can still catch, soonest first.
+pub fn station_summary(entries: &[DisplayEntry]) -> String {
+    if entries.is_empty() {
+        return "no departures".to_string();
+    }
+
+    let mut next: Vec<&DisplayEntry> = Vec::new();
+    for e in entries
+        .iter()
+        .filter(|e| !e.cancelled && !e.ghost && e.actual_mins >= 0 && e.reachable)
+    {
+        match next.iter_mut().find(|n| n.line == e.line) {
+            Some(n) if e.actual_mins < n.actual_mins => *n = e,
+            Some(_) => {}
+            None => next.push(e),
+        }
+    }
+    next.sort_by_key(|e| e.actual_mins);
+
+    let mut parts: Vec<String> = next
+        .iter()
+        .take(MAX_LINES)
+        .map(|e| format!("next {} in {}", e.line, format_countdown(e.actual_mins)))
+        .collect();
+
+    match warnings(entries).len() {
+        0 => {}
+        1 => parts.push("1 warning".to_string()),
+        n => parts.push(format!("{} warnings", n)),
+    }
+
+    parts.join(", ")
+}
+
+/// Texts of the warnings of all `entries`, each once, e.g. "Aufzug außer Betrieb".
+pub fn warnings(entries: &[DisplayEntry]) -> Vec<&str> {
+    let mut warnings: Vec<&str> = entries
+        .iter()
+        .flat_map(|e| e.warnings.iter().map(|w| w.as_str()))
+        .collect();
+    warnings.sort_unstable();
+    warnings.dedup();
+    warnings
+}
+
+/// Typical minutes between departures at `times` (in minutes from now). None if there are
+/// fewer than three.
+pub(crate) fn headway(mut times: Vec<i64>) -> Option<i64> {
+    times.sort_unstable();
+    let mut gaps: Vec<i64> = times.windows(2).map(|w| w[1] - w[0]).collect();
+    if gaps.len() < 2 {
+        return None;
+    }
+    // the median, so a single delayed trip does not skew it
+    gaps.sort_unstable();
+    Some(gaps[gaps.len() / 2].max(1))
+}
+
+/// Subtle hint about filtered departures, e.g. "12 shown, 8 filtered". None if nothing was filtered.
+pub fn filter_note(station: &StationBoard) -> Option<String> {
+    let filtered = station.filter_stats.total();
+    (filtered > 0).then(|| format!("{} shown, {} filtered", station.rows.len(), filtered))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headway_ignores_a_single_delayed_trip() {
+        assert_eq!(headway(vec![2, 7, 12, 20, 22]), Some(5));
+        assert_eq!(headway(vec![10, 0, 5]), Some(5));
+        assert_eq!(headway(vec![3, 8]), None);
+    }
+}
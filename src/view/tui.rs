@@ -1,8 +1,24 @@
+use crate::announce::Announcer;
 use crate::api::departures::DeparturesApi;
-use crate::view::{DisplayEntry, ResultDisplay};
-use crate::InputStops;
+use crate::api::trips::{Stopover, Trip};
+use crate::api::DEFAULT_BASE_URL;
+use crate::board::Board;
+use crate::exec::ExecHook;
+use crate::notify::{Notifier, Urgency};
+use crate::request_log::RequestLog;
+use crate::schedule::AdaptiveInterval;
+use crate::snapshot::BoardSnapshot;
+use crate::view::deep_link::{copy_osc52, deep_link, qr_code, DEFAULT_DEEP_LINK};
+use crate::view::duration::clock_label;
+use crate::view::summary::{filter_note, station_summary};
+use crate::view::{
+    group_by_line, merge_stations, DisplayEntry, ResultDisplay, SortOrder, StationHeader,
+};
+use crate::watch::{GhostWatcher, LineWatcher, TripWatcher};
+use crate::{InputStop, InputStops};
 use async_trait::async_trait;
-use chrono::Local;
+use chrono::{DateTime, Local, Utc};
+
 use crossterm::event::{self, Event, KeyCode, KeyModifiers};
 use crossterm::execute;
 use crossterm::terminal::{
@@ -11,17 +27,20 @@ use crossterm::terminal::{
 use derive_builder::Builder;
 use std::collections::VecDeque;
 use std::io;
-use std::io::{stdout, Stdout};
+use std::io::stdout;
 use std::sync::{Arc, Mutex};
-use tracing::info;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
 use tracing_subscriber::fmt::writer::MakeWriter;
+use tui::backend::{Backend, CrosstermBackend};
 use tui::layout::Alignment;
-use tui::layout::{Constraint, Direction, Layout};
+use tui::layout::{Constraint, Direction, Layout, Rect};
 use tui::style::{Color as TuiColor, Modifier, Style};
 use tui::text::{Span, Spans, Text};
-use tui::widgets::{Block, Borders, Paragraph};
-use tui::{backend::CrosstermBackend, Terminal};
+use tui::widgets::{Block, Borders, Cell, Clear, Paragraph, Row, Table, TableState, Wrap};
+use tui::Terminal;
 use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+use url::Url;
 
 #[derive(Clone)]
 pub struct LogBuffer {
@@ -106,6 +125,253 @@ pub struct TuiDisplay<D: DeparturesApi> {
     api_client: D,
     stops: InputStops,
     log_buffer: LogBuffer,
+    /// Command to run after each refresh.
+    #[builder(default)]
+    exec: Option<ExecHook>,
+    /// Upper bound for redraws per second.
+    #[builder(default = "10")]
+    max_fps: u32,
+    /// Time between automatic refreshes
+    #[builder(default = "Duration::from_secs(30)")]
+    refresh_interval: Duration,
+    /// One table for all stations, soonest first
+    #[builder(default)]
+    merged: bool,
+    /// One row per line and direction with the next departures, toggled with g
+    #[builder(default)]
+    grouped: bool,
+    /// Kept up to date with the board on screen
+    #[builder(default)]
+    snapshot: Option<BoardSnapshot>,
+    /// Requests shown in the bottom pane with L
+    #[builder(default)]
+    request_log: Option<RequestLog>,
+    /// API the default deep link points at, the public one if None
+    #[builder(default)]
+    base_url: Option<Url>,
+}
+
+/// Interval in which countdowns and the log pane are checked for changes.
+const TICK: Duration = Duration::from_secs(1);
+
+/// Interval in which keys are read while departures or a trip are fetched.
+const KEY_POLL: Duration = Duration::from_millis(50);
+
+/// What the main loop has to do after a key press.
+enum Action {
+    None,
+    Refresh,
+    /// Fetch the trip with this id and show its stopovers
+    ShowTrip(String),
+    Quit,
+}
+
+/// What the pane below the board shows.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum BottomPane {
+    Logs,
+    /// Remarks of the departures, toggled with w
+    Remarks,
+    /// Recent requests to the API, toggled with L
+    Requests,
+}
+
+impl BottomPane {
+    /// Switches to `pane`, or back to the logs if it is already shown.
+    fn toggle(&mut self, pane: BottomPane) {
+        *self = if *self == pane {
+            BottomPane::Logs
+        } else {
+            pane
+        };
+    }
+}
+
+enum Status {
+    Loading,
+    Ready,
+    Failed(String),
+}
+
+/// Everything shown on screen. The terminal is only redrawn if this changes.
+struct Screen {
+    board: Option<Board>,
+    display_lines: Vec<(StationHeader, Vec<DisplayEntry>)>,
+    status: Status,
+    /// Whether the direction filters of the config are applied
+    filters: bool,
+    /// Whether all stations share one table
+    merged: bool,
+    /// One row per line and direction, see [`group_by_line`]
+    grouped: bool,
+    /// Changed with t, l and d without refetching
+    sort: SortOrder,
+    /// Index of the selected departure, counted across all stations
+    selected: Option<usize>,
+    /// Link and QR code shown on top of the board
+    qr: Option<(String, Vec<String>)>,
+    /// Stopovers of the trip opened with Enter, shown on top of the board
+    trip: Option<TripPanel>,
+    pane: BottomPane,
+    /// Trips pinned with `p`
+    watcher: TripWatcher,
+    line_watcher: LineWatcher,
+    notifier: Notifier,
+    /// Reset when the filters are toggled, as that changes the departures fetched
+    ghosts: GhostWatcher,
+    announcer: Option<Announcer>,
+    /// Time between automatic refreshes, stretched while rate limited
+    interval: AdaptiveInterval,
+    logs: Vec<String>,
+    /// Formatted records of the request log, oldest first
+    requests: Vec<String>,
+    dirty: bool,
+}
+
+impl Screen {
+    fn loading(refresh_interval: Duration, stops: &InputStops) -> Self {
+        Self {
+            board: None,
+            display_lines: Vec::new(),
+            status: Status::Loading,
+            filters: true,
+            merged: false,
+            grouped: false,
+            sort: SortOrder::Time,
+            selected: None,
+            qr: None,
+            trip: None,
+            pane: BottomPane::Logs,
+            watcher: TripWatcher::default(),
+            line_watcher: LineWatcher::default(),
+            notifier: Notifier::new(stops),
+            ghosts: GhostWatcher::default(),
+            announcer: None,
+            interval: AdaptiveInterval::new(refresh_interval),
+            logs: Vec::new(),
+            requests: Vec::new(),
+            dirty: true,
+        }
+    }
+
+    fn build_display_lines(
+        &self,
+        board: &Board,
+        stops: &InputStops,
+    ) -> Vec<(StationHeader, Vec<DisplayEntry>)> {
+        let display_lines = crate::view::build_display_lines(board, stops);
+        let mut display_lines = if self.merged {
+            merge_stations(display_lines)
+        } else {
+            display_lines
+        };
+        if self.grouped {
+            display_lines = group_by_line(display_lines);
+        }
+        for (_, entries) in &mut display_lines {
+            self.sort.sort(entries);
+        }
+        display_lines
+    }
+
+    /// Re-sorts the current board.
+    fn sort_by(&mut self, sort: SortOrder, stops: &InputStops) {
+        self.sort = sort;
+        if let Some(board) = &self.board {
+            self.display_lines = self.build_display_lines(board, stops);
+        }
+        self.dirty = true;
+    }
+
+    fn update(&mut self, board: Board, stops: &InputStops) {
+        self.display_lines = self.build_display_lines(&board, stops);
+        self.board = Some(board);
+        self.clamp_selection();
+        self.status = Status::Ready;
+        self.dirty = true;
+    }
+
+    /// Recomputes countdowns and picks up new log lines and requests. Marks the screen dirty if
+    /// anything changed.
+    fn tick(
+        &mut self,
+        stops: &InputStops,
+        log_buffer: &LogBuffer,
+        request_log: Option<&RequestLog>,
+    ) {
+        if let Some(board) = &self.board {
+            let display_lines = self.build_display_lines(board, stops);
+            if display_lines != self.display_lines {
+                self.display_lines = display_lines;
+                self.clamp_selection();
+                self.dirty = true;
+            }
+        }
+        if let Some(panel) = &mut self.trip
+            && panel.update(Utc::now())
+        {
+            self.dirty = true;
+        }
+        let logs = log_buffer.snapshot();
+        if logs != self.logs {
+            self.logs = logs;
+            self.dirty = true;
+        }
+        if let Some(request_log) = request_log {
+            let requests: Vec<String> = request_log
+                .records()
+                .iter()
+                .map(ToString::to_string)
+                .collect();
+            if requests != self.requests {
+                self.requests = requests;
+                self.dirty = true;
+            }
+        }
+    }
+
+    fn entry_count(&self) -> usize {
+        self.display_lines.iter().map(|(_, e)| e.len()).sum()
+    }
+
+    /// Moves the selection by `step` departures. Without a selection, the first departure is selected.
+    fn select(&mut self, step: isize) {
+        let count = self.entry_count();
+        self.selected = match self.selected {
+            _ if count == 0 => None,
+            Some(i) => Some(i.saturating_add_signed(step).min(count - 1)),
+            None => Some(0),
+        };
+        self.dirty = true;
+    }
+
+    /// Keeps the selection on the board when departures drop off.
+    fn clamp_selection(&mut self) {
+        let count = self.entry_count();
+        self.selected = self
+            .selected
+            .filter(|_| count > 0)
+            .map(|i| i.min(count - 1));
+    }
+
+    fn scroll_trip(&mut self, step: isize) {
+        if let Some(panel) = &mut self.trip {
+            let max = panel.lines.len().saturating_sub(1);
+            panel.scroll = panel.scroll.saturating_add_signed(step).min(max);
+            self.dirty = true;
+        }
+    }
+
+    fn selected_entry(&self) -> Option<(&StationHeader, &DisplayEntry)> {
+        let mut index = self.selected?;
+        for (station, entries) in &self.display_lines {
+            if index < entries.len() {
+                return Some((station, &entries[index]));
+            }
+            index -= entries.len();
+        }
+        None
+    }
 }
 
 #[async_trait]
@@ -118,34 +384,59 @@ impl<D: DeparturesApi + Sync> ResultDisplay for TuiDisplay<D> {
         let backend = CrosstermBackend::new(stdout);
         let mut terminal = Terminal::new(backend)?;
 
-        let resp = self.api_client.get_departures(&self.stops).await?;
-        let mut display_lines = crate::view::build_display_lines(&resp);
+        let mut screen = Screen::loading(self.refresh_interval, &self.stops);
+        screen.announcer = Announcer::new(&self.stops);
+        screen.merged = self.merged;
+        screen.grouped = self.grouped;
+        let mut action = self.refresh(&mut screen, &mut terminal).await?;
+        let mut last_refresh = Instant::now();
 
-        Self::render(&display_lines, &self.log_buffer, &mut terminal)?;
+        let frame_interval = Duration::from_secs(1) / self.max_fps.max(1);
+        let mut last_frame: Option<Instant> = None;
+        let mut last_tick = Instant::now();
 
-        // Wait for user to press 'q' to quit. Timeout every 250ms to keep responsive (no refresh behavior implemented).
         loop {
-            match event::read()? {
-                Event::Key(key) => match key.code {
-                    KeyCode::Char('q') | KeyCode::Esc => break,
-                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
-                    KeyCode::Char('l') => {
-                        info!("This is a sample log.");
-                        Self::render(&display_lines, &self.log_buffer, &mut terminal)?;
-                    }
-                    KeyCode::Char('r') => {
-                        // Refresh: re-fetch departures and re-render
-                        let resp = self.api_client.get_departures(&self.stops).await?;
-                        display_lines = crate::view::build_display_lines(&resp);
-                        Self::render(&display_lines, &self.log_buffer, &mut terminal)?;
-                    }
-                    _ => {}
-                },
-                Event::Resize(_, _) => {
-                    // Re-render using the current terminal size
-                    Self::render(&display_lines, &self.log_buffer, &mut terminal)?;
+            match action {
+                Action::Quit => break,
+                Action::Refresh => {
+                    action = self.refresh(&mut screen, &mut terminal).await?;
+                    last_refresh = Instant::now();
+                    continue;
                 }
-                _ => {}
+                Action::ShowTrip(trip_id) => {
+                    action = self.show_trip(&mut screen, &mut terminal, &trip_id).await?;
+                    continue;
+                }
+                Action::None => {}
+            }
+            if last_refresh.elapsed() >= screen.interval.current() {
+                action = Action::Refresh;
+                continue;
+            }
+
+            // Draw at most max_fps frames per second, and only if something changed
+            let frame_due = last_frame.is_none_or(|t| t.elapsed() >= frame_interval);
+            if screen.dirty && frame_due {
+                Self::render(&screen, &self.stops, &mut terminal)?;
+                screen.dirty = false;
+                last_frame = Some(Instant::now());
+            }
+
+            let timeout = if screen.dirty {
+                last_frame.map_or(Duration::ZERO, |t| {
+                    frame_interval.saturating_sub(t.elapsed())
+                })
+            } else {
+                TICK.saturating_sub(last_tick.elapsed())
+            };
+
+            if event::poll(timeout)? {
+                action = self.handle_event(&mut screen, event::read()?);
+            }
+
+            if last_tick.elapsed() >= TICK || screen.dirty {
+                screen.tick(&self.stops, &self.log_buffer, self.request_log.as_ref());
+                last_tick = Instant::now();
             }
         }
 
@@ -158,90 +449,450 @@ impl<D: DeparturesApi + Sync> ResultDisplay for TuiDisplay<D> {
     }
 }
 
-impl<D: DeparturesApi> TuiDisplay<D> {
-    fn render(
-        display_lines: &Vec<(String, Vec<DisplayEntry>)>,
-        log_buffer: &LogBuffer,
-        terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+impl<D: DeparturesApi + Sync> TuiDisplay<D> {
+    /// Fetches departures into the screen. Failures are shown on screen instead of ending the TUI.
+    ///
+    /// Keys are handled while waiting for the API. Returns what was asked for in the meantime.
+    async fn refresh<B: Backend>(
+        &self,
+        screen: &mut Screen,
+        terminal: &mut Terminal<B>,
+    ) -> anyhow::Result<Action> {
+        screen.status = Status::Loading;
+        Self::render(screen, &self.stops, terminal)?;
+        screen.dirty = false;
+
+        let unfiltered;
+        let stops = if screen.filters {
+            &self.stops
+        } else {
+            unfiltered = self.stops.without_filters();
+            &unfiltered
+        };
+        let (result, pending) = self
+            .while_handling_keys(screen, terminal, self.api_client.get_departures(stops))
+            .await?;
+        let Some(result) = result else {
+            return Ok(Action::Quit);
+        };
+
+        match result {
+            Ok(mut board) => {
+                screen.interval.after(&board);
+                screen.ghosts.check(&mut board, stops);
+                let now = Local::now().naive_local();
+                for alert in screen.watcher.check(&board, self.stops.watch_delay_minutes) {
+                    warn!("{}", alert);
+                    screen.notifier.send(
+                        alert.trip_id(),
+                        alert.rule(),
+                        Urgency::Critical,
+                        &alert.to_string(),
+                        now,
+                    );
+                }
+                let recovered = screen.line_watcher.check(
+                    &board,
+                    &self.stops.watch_lines,
+                    self.stops.watch_delay_minutes,
+                );
+                for line in recovered {
+                    info!("{}", line);
+                    screen.notifier.send(
+                        &line.line,
+                        "recovered",
+                        Urgency::Normal,
+                        &line.to_string(),
+                        now,
+                    );
+                }
+                if let Some(snapshot) = &self.snapshot {
+                    snapshot.update(&board);
+                }
+                self.run_exec(&board);
+                screen.update(board, &self.stops);
+                if let Some(announcer) = &mut screen.announcer {
+                    announcer.announce(&screen.display_lines);
+                }
+            }
+            Err(e) if e.rate_limit().is_some() => {
+                screen.interval.throttled(e.rate_limit().flatten());
+                // keep showing the last board, the header says it is throttled
+                screen.status = Status::Ready;
+                screen.dirty = true;
+            }
+            Err(e) => {
+                warn!("Could not fetch departures: {}", e);
+                screen.status = Status::Failed(e.to_string());
+                screen.dirty = true;
+            }
+        }
+        Ok(pending)
+    }
+
+    /// Awaits `fetch` while handling keys, so that the TUI does not freeze on a slow API. Returns
+    /// None if the user quit in the meantime, and what else was asked for.
+    async fn while_handling_keys<T, B: Backend>(
+        &self,
+        screen: &mut Screen,
+        terminal: &mut Terminal<B>,
+        fetch: impl Future<Output = T>,
+    ) -> anyhow::Result<(Option<T>, Action)> {
+        tokio::pin!(fetch);
+        let mut pending = Action::None;
+        loop {
+            tokio::select! {
+                result = &mut fetch => return Ok((Some(result), pending)),
+                _ = tokio::time::sleep(KEY_POLL) => {
+                    while event::poll(Duration::ZERO)? {
+                        match self.handle_event(screen, event::read()?) {
+                            Action::Quit => return Ok((None, Action::Quit)),
+                            // e.g. filters toggled, the running fetch is outdated
+                            Action::Refresh => pending = Action::Refresh,
+                            Action::ShowTrip(trip_id) => pending = Action::ShowTrip(trip_id),
+                            Action::None => {}
+                        }
+                    }
+                    if screen.dirty {
+                        Self::render(screen, &self.stops, terminal)?;
+                        screen.dirty = false;
+                    }
+                }
+            }
+        }
+    }
+
+    fn handle_event(&self, screen: &mut Screen, event: Event) -> Action {
+        match event {
+            Event::Key(key) => match key.code {
+                KeyCode::Esc | KeyCode::Enter if screen.qr.is_some() => {
+                    screen.qr = None;
+                    screen.dirty = true;
+                }
+                KeyCode::Esc | KeyCode::Enter if screen.trip.is_some() => {
+                    screen.trip = None;
+                    screen.dirty = true;
+                }
+                KeyCode::Down | KeyCode::Char('j') if screen.trip.is_some() => {
+                    screen.scroll_trip(1)
+                }
+                KeyCode::Up | KeyCode::Char('k') if screen.trip.is_some() => screen.scroll_trip(-1),
+                KeyCode::Char('q') | KeyCode::Esc => return Action::Quit,
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Action::Quit
+                }
+                KeyCode::Char('t') => screen.sort_by(SortOrder::Time, &self.stops),
+                KeyCode::Char('l') => screen.sort_by(SortOrder::Line, &self.stops),
+                KeyCode::Char('d') => screen.sort_by(SortOrder::Delay, &self.stops),
+                // Refresh: re-fetch departures and re-render, cached responses would be the same
+                KeyCode::Char('r') => {
+                    self.api_client.clear_cache();
+                    return Action::Refresh;
+                }
+                KeyCode::Down | KeyCode::Char('j') => screen.select(1),
+                KeyCode::Up | KeyCode::Char('k') => screen.select(-1),
+                KeyCode::Char('y') => self.copy_link(screen),
+                KeyCode::Enter => return Self::selected_trip(screen),
+                KeyCode::Char('c') => self.show_qr(screen),
+                KeyCode::Char('p') => Self::toggle_pin(screen),
+                KeyCode::Char('w') => {
+                    screen.pane.toggle(BottomPane::Remarks);
+                    screen.dirty = true;
+                }
+                KeyCode::Char('g') => {
+                    screen.grouped = !screen.grouped;
+                    let sort = screen.sort;
+                    screen.sort_by(sort, &self.stops);
+                    screen.clamp_selection();
+                }
+                KeyCode::Char('L') => {
+                    screen.pane.toggle(BottomPane::Requests);
+                    screen.dirty = true;
+                }
+                KeyCode::Char('f') => {
+                    // Toggle the direction filters of the config
+                    screen.filters = !screen.filters;
+                    screen.ghosts = GhostWatcher::default();
+                    return Action::Refresh;
+                }
+                _ => {}
+            },
+            Event::Resize(_, _) => {
+                // Re-render using the current terminal size
+                screen.dirty = true;
+            }
+            _ => {}
+        }
+        Action::None
+    }
+
+    /// Asks for the trip of the selected departure. Logs why if there is none.
+    fn selected_trip(screen: &Screen) -> Action {
+        let Some((_, entry)) = screen.selected_entry() else {
+            info!("Select a departure with ↑/↓ first");
+            return Action::None;
+        };
+        match &entry.trip_id {
+            Some(trip_id) => Action::ShowTrip(trip_id.clone()),
+            None => {
+                warn!("{} to {} has no trip id", entry.line, entry.dir);
+                Action::None
+            }
+        }
+    }
+
+    /// Fetches the trip into the trip panel. Keys are handled while waiting for the API.
+    async fn show_trip<B: Backend>(
+        &self,
+        screen: &mut Screen,
+        terminal: &mut Terminal<B>,
+        trip_id: &str,
+    ) -> anyhow::Result<Action> {
+        let (result, pending) = self
+            .while_handling_keys(screen, terminal, self.api_client.get_trip(trip_id))
+            .await?;
+        match result {
+            Some(Ok(trip)) => {
+                screen.trip = Some(TripPanel::new(
+                    trip,
+                    self.stops.max_delay_minutes,
+                    Utc::now(),
+                ));
+                screen.dirty = true;
+            }
+            Some(Err(e)) => warn!("Could not get trip {}: {}", trip_id, e),
+            None => return Ok(Action::Quit),
+        }
+        Ok(pending)
+    }
+
+    /// Deep link of the selected departure. Logs why if there is none.
+    fn selected_link(&self, screen: &Screen) -> Option<String> {
+        let Some((station, entry)) = screen.selected_entry() else {
+            info!("Select a departure with ↑/↓ first");
+            return None;
+        };
+        let template = self.stops.deep_link.as_deref().unwrap_or(DEFAULT_DEEP_LINK);
+        let base = match &self.base_url {
+            Some(base) => base.clone(),
+            None => Url::parse(DEFAULT_BASE_URL).unwrap(),
+        };
+        let station = entry.station.as_ref().unwrap_or(station);
+        let link = deep_link(template, &base, station, entry);
+        if link.is_none() {
+            warn!("{} to {} has no trip id to link to", entry.line, entry.dir);
+        }
+        link
+    }
+
+    /// Copies the deep link of the selected departure to the clipboard.
+    fn copy_link(&self, screen: &Screen) {
+        let Some(link) = self.selected_link(screen) else {
+            return;
+        };
+        match copy_osc52(&link) {
+            Ok(()) => info!("Copied {}", link),
+            Err(e) => warn!("Could not copy {}: {}", link, e),
+        }
+    }
+
+    /// Starts or stops watching the selected trip for cancellations and delays.
+    fn toggle_pin(screen: &mut Screen) {
+        let Some((_, entry)) = screen.selected_entry() else {
+            info!("Select a departure with ↑/↓ first");
+            return;
+        };
+        let Some(trip_id) = entry.trip_id.clone() else {
+            warn!("{} to {} has no trip id to watch", entry.line, entry.dir);
+            return;
+        };
+        let label = format!("{} to {}", entry.line, entry.dir);
+        if screen.watcher.toggle(&trip_id, label.clone()) {
+            info!("Watching {}", label);
+        } else {
+            info!("Stopped watching {}", label);
+        }
+        screen.dirty = true;
+    }
+
+    /// Shows the deep link of the selected departure as QR code, e.g. for passers-by at a kiosk.
+    fn show_qr(&self, screen: &mut Screen) {
+        let Some(link) = self.selected_link(screen) else {
+            return;
+        };
+        match qr_code(&link) {
+            Some(lines) => {
+                screen.qr = Some((link, lines));
+                screen.dirty = true;
+            }
+            None => warn!("{} is too long for a QR code", link),
+        }
+    }
+
+    fn run_exec(&self, board: &Board) {
+        if let Some(exec) = &self.exec {
+            // a slow command must not hold up the refresh
+            exec.run(board);
+        }
+    }
+
+    fn render<B: Backend>(
+        screen: &Screen,
+        stops: &InputStops,
+        terminal: &mut Terminal<B>,
     ) -> Result<(), anyhow::Error> {
-        // Render once and wait for 'q' to quit
+        let display_lines = &screen.display_lines;
+        // tui only writes the cells that differ from the previous frame
         terminal.draw(|f| {
             let size = f.size();
+            let remarks = remark_lines(display_lines);
             let log_height = if size.height > 10 { 5 } else { 3 };
+            let pane_lines = match screen.pane {
+                BottomPane::Logs => 0,
+                BottomPane::Remarks => remarks.len(),
+                BottomPane::Requests => screen.requests.len(),
+            };
+            let log_height =
+                (pane_lines as u16 + 2).clamp(log_height, (size.height / 3).max(log_height));
             let chunks = Layout::default()
                 .direction(Direction::Vertical)
-                .constraints([Constraint::Min(5), Constraint::Length(log_height)].as_ref())
+                .constraints(
+                    [
+                        Constraint::Length(1),
+                        Constraint::Min(5),
+                        Constraint::Length(log_height),
+                    ]
+                    .as_ref(),
+                )
                 .split(size);
 
-            // Build header with current time right-aligned within the content area
-            let now = Local::now();
-            let now_str = now.format("%H:%M:%S").to_string();
-            let header_line = format!("Request time: {}", now_str);
+            let header = Paragraph::new(header_spans(screen));
+            f.render_widget(header, chunks[0]);
 
-            // Build the lines for the entries
-            let mut spans: Vec<Spans> = Vec::new();
-            spans.push(Spans::from(Span::styled(
-                header_line,
-                Style::default().add_modifier(Modifier::BOLD),
-            )));
+            if display_lines.is_empty() {
+                let message = Paragraph::new(state_message(screen, stops))
+                    .block(Block::default().borders(Borders::ALL).title("Departures"))
+                    .alignment(Alignment::Center)
+                    .wrap(Wrap { trim: true });
+                f.render_widget(message, chunks[1]);
+            }
 
-            spans.push(Spans::from(Span::raw("")));
+            // One table per station, stacked. Each needs its rows plus header row and borders,
+            // empty stations only a single line for the hint.
+            let station_constraints: Vec<Constraint> = display_lines
+                .iter()
+                .map(|(_, entries)| match entries.len() {
+                    0 => Constraint::Length(3),
+                    n => Constraint::Length(n as u16 + 3),
+                })
+                .chain(std::iter::once(Constraint::Min(0)))
+                .collect();
+            let station_chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints(station_constraints)
+                .split(chunks[1]);
 
-            let (line_width, dir_width) = max_column_widths(display_lines);
-            let line_width = line_width.max(5);
-            let dir_width = dir_width.max(1);
+            let columns = Columns::new(display_lines, stops);
+            let widths = column_widths(display_lines, &columns);
+            let mut offset = 0;
+            for ((station, entries), area) in display_lines.iter().zip(station_chunks.iter()) {
+                let mut state = TableState::default();
+                state.select(
+                    screen
+                        .selected
+                        .and_then(|s| s.checked_sub(offset))
+                        .filter(|s| *s < entries.len()),
+                );
+                offset += entries.len();
 
-            for (name, entries) in display_lines {
-                spans.push(Spans::from(Span::styled(
-                    format!("Station: {}", name),
-                    Style::default()
-                        .add_modifier(Modifier::BOLD)
-                        .add_modifier(Modifier::UNDERLINED),
-                )));
-
-                for e in entries {
-                    let (r, g, b) = hex_to_rgb(e.hex);
-                    let tui_color = TuiColor::Rgb(r, g, b);
-                    let delay_text = match e.delay_mins {
-                        Some(d) if d != 0 => format!(" ({:+}min)", d),
-                        _ => String::new(),
-                    };
-
-                    let abs_text = e
-                        .abs_time
-                        .as_ref()
-                        .map(|t| format!("{}", t))
-                        .unwrap_or_else(|| String::from("--"));
-
-                    // Compose spans: symbol, styled line, absolute time, and the rest as raw text
-                    let span_vec = vec![
-                        Span::raw(format!("{} ", e.symbol)),
-                        Span::styled(
-                            format!("{:<width$}", e.line, width = line_width),
-                            Style::default().bg(tui_color).add_modifier(Modifier::BOLD),
-                        ),
-                        Span::raw(format!(
-                            "| {:<width$} | {:>5} | {:2}min{}",
-                            e.dir,
-                            abs_text,
-                            e.actual_mins,
-                            delay_text,
-                            width = dir_width
-                        )),
-                    ];
-
-                    spans.push(Spans::from(span_vec));
+                let board_station = screen
+                    .board
+                    .as_ref()
+                    .and_then(|b| b.stations.iter().find(|s| s.stop_id == station.stop_id));
+                let note = match board_station {
+                    Some(board_station) => filter_note(board_station),
+                    // merged view: mention the stations missing from the list
+                    None => screen.board.as_ref().and_then(failed_note),
+                };
+                if let Some(error) = board_station.and_then(|s| s.error.as_ref()) {
+                    let paragraph = Paragraph::new(Span::styled(
+                        format!("⚠ Could not get departures: {}", error),
+                        Style::default().fg(TuiColor::Red),
+                    ))
+                    .block(station_block(
+                        station,
+                        "unavailable".to_string(),
+                        None,
+                    ));
+                    f.render_widget(paragraph, *area);
+                    continue;
+                }
+                if entries.is_empty() {
+                    let filtered = board_station.map_or(0, |s| s.filter_stats.total());
+                    let hint = stops
+                        .stops
+                        .iter()
+                        .find(|s| s.id == station.stop_id)
+                        .map(|s| empty_station_hint(s, screen.filters, filtered))
+                        .unwrap_or_default();
+                    let paragraph = Paragraph::new(Span::styled(
+                        hint,
+                        Style::default().add_modifier(Modifier::ITALIC),
+                    ))
+                    .block(station_block(
+                        station,
+                        station_summary(entries),
+                        note,
+                    ));
+                    f.render_widget(paragraph, *area);
+                    continue;
                 }
-                spans.push(Spans::from(Span::raw("")));
+                let table =
+                    station_table(station, entries, &widths, note, &screen.watcher, &columns);
+                f.render_stateful_widget(table, *area, &mut state);
             }
 
-            let paragraph = Paragraph::new(Text::from(spans))
-                .block(Block::default().borders(Borders::ALL).title("Departures"))
-                .alignment(Alignment::Left);
+            if screen.pane == BottomPane::Remarks {
+                let text = if remarks.is_empty() {
+                    "No remarks".to_string()
+                } else {
+                    remarks.join("\n")
+                };
+                let paragraph = Paragraph::new(text)
+                    .block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .title("Remarks (w for logs)"),
+                    )
+                    .wrap(Wrap { trim: true });
+                f.render_widget(paragraph, chunks[2]);
+            }
+
+            if screen.pane == BottomPane::Requests {
+                let inner_height = chunks[2].height.saturating_sub(2) as usize;
+                let inner_width = chunks[2].width.saturating_sub(2) as usize;
+                let text = if screen.requests.is_empty() {
+                    "No requests yet".to_string()
+                } else {
+                    // newest at the bottom, like the logs
+                    let skip = screen.requests.len().saturating_sub(inner_height);
+                    screen.requests[skip..]
+                        .iter()
+                        .map(|r| truncate_line(r, inner_width))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                };
+                let paragraph = Paragraph::new(text).block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("Requests (L for logs)"),
+                );
+                f.render_widget(paragraph, chunks[2]);
+            }
 
-            let log_lines = log_buffer.snapshot();
-            let log_inner_height = chunks[1].height.saturating_sub(2) as usize;
-            let log_inner_width = chunks[1].width.saturating_sub(2) as usize;
+            let log_lines = screen.logs.clone();
+            let log_inner_height = chunks[2].height.saturating_sub(2) as usize;
+            let log_inner_width = chunks[2].width.saturating_sub(2) as usize;
             let mut visible_logs: Vec<String> = if log_lines.is_empty() {
                 vec!["No logs yet".to_string()]
             } else if log_inner_height == 0 {
@@ -263,23 +914,221 @@ impl<D: DeparturesApi> TuiDisplay<D> {
                 .into_iter()
                 .map(|line| Spans::from(Span::raw(line)))
                 .collect();
+            let log_title = match remarks.len() {
+                0 => "Logs".to_string(),
+                1 => "Logs (w shows 1 remark)".to_string(),
+                n => format!("Logs (w shows {} remarks)", n),
+            };
             let log_paragraph = Paragraph::new(Text::from(log_spans))
-                .block(Block::default().borders(Borders::ALL).title("Logs"))
+                .block(Block::default().borders(Borders::ALL).title(log_title))
                 .alignment(Alignment::Left);
 
-            f.render_widget(paragraph, chunks[0]);
-            f.render_widget(log_paragraph, chunks[1]);
+            if screen.pane == BottomPane::Logs {
+                f.render_widget(log_paragraph, chunks[2]);
+            }
+
+            if let Some(panel) = &screen.trip {
+                let width = panel
+                    .lines
+                    .iter()
+                    .map(|l| l.width())
+                    .max()
+                    .unwrap_or_default()
+                    .max(panel.title.width()) as u16
+                    + 2;
+                let height = panel.lines.len() as u16 + 2;
+                let area = centered(
+                    size,
+                    width.min(size.width.saturating_sub(4)),
+                    height.min(size.height.saturating_sub(2)),
+                );
+                let block = Block::default().borders(Borders::ALL).title(truncate_line(
+                    &panel.title,
+                    area.width.saturating_sub(2) as usize,
+                ));
+                let stopovers = Paragraph::new(panel.lines.join("\n"))
+                    .scroll((panel.scroll as u16, 0))
+                    .block(block);
+                f.render_widget(Clear, area);
+                f.render_widget(stopovers, area);
+            }
+
+            if let Some((link, lines)) = &screen.qr {
+                let width = lines.first().map_or(0, |l| l.width()) as u16 + 2;
+                let height = lines.len() as u16 + 2;
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .title(truncate_line(link, width.saturating_sub(2) as usize));
+                if width <= size.width && height <= size.height {
+                    let area = centered(size, width, height);
+                    // dark on light, as scanners expect
+                    let code = Paragraph::new(lines.join("\n"))
+                        .style(Style::default().fg(TuiColor::Black).bg(TuiColor::White))
+                        .block(block);
+                    f.render_widget(Clear, area);
+                    f.render_widget(code, area);
+                } else {
+                    let area = centered(size, size.width.min(40), 3);
+                    let message = Paragraph::new("Terminal too small for the QR code")
+                        .alignment(Alignment::Center)
+                        .block(block);
+                    f.render_widget(Clear, area);
+                    f.render_widget(message, area);
+                }
+            }
         })?;
         Ok(())
     }
 }
 
-fn hex_to_rgb(hex: &str) -> (u8, u8, u8) {
+/// Stopovers of a trip as text, one line per stop, below a bar showing how far the vehicle got.
+struct TripPanel {
+    title: String,
+    trip: Trip,
+    max_delay_minutes: u32,
+    lines: Vec<String>,
+    /// Number of lines scrolled past
+    scroll: usize,
+}
+
+/// Width of the progress bar in the trip panel
+const PROGRESS_WIDTH: usize = 20;
+
+impl TripPanel {
+    /// Delays beyond `max_delay_minutes` are shown as "?" and explained below the stops.
+    fn new(trip: Trip, max_delay_minutes: u32, now: DateTime<Utc>) -> Self {
+        let line = trip
+            .line
+            .as_ref()
+            .and_then(|l| l.name.as_deref())
+            .unwrap_or("?");
+        let title = format!(
+            "{} → {} (Esc to close)",
+            line,
+            trip.direction.as_deref().unwrap_or_default()
+        );
+        let mut panel = Self {
+            title,
+            trip,
+            max_delay_minutes,
+            lines: Vec::new(),
+            scroll: 0,
+        };
+        panel.update(now);
+        panel
+    }
+
+    /// Moves the vehicle along the stops. Returns whether anything changed.
+    fn update(&mut self, now: DateTime<Utc>) -> bool {
+        let stopovers = &self.trip.stopovers;
+        let passed = stops_passed(stopovers, now);
+        let filled = (passed * PROGRESS_WIDTH)
+            .checked_div(stopovers.len())
+            .unwrap_or_default();
+        let mut lines = vec![
+            format!(
+                "{}{} {}/{} stops",
+                "█".repeat(filled),
+                "░".repeat(PROGRESS_WIDTH - filled),
+                passed,
+                stopovers.len()
+            ),
+            String::new(),
+            "  plan   now    stop".to_string(),
+        ];
+        let mut implausible = Vec::new();
+        for (i, s) in stopovers.iter().enumerate() {
+            // the vehicle is at or on its way to the first stop it has not left
+            let marker = match i.cmp(&passed) {
+                std::cmp::Ordering::Less => "·",
+                std::cmp::Ordering::Equal => "▶",
+                std::cmp::Ordering::Greater => " ",
+            };
+            let delay = s.arrival_delay.or(s.departure_delay);
+            let realtime = match delay.filter(|d| d.abs() > i64::from(self.max_delay_minutes) * 60)
+            {
+                Some(delay) => {
+                    implausible.push(delay / 60);
+                    false
+                }
+                None => true,
+            };
+            lines.push(format!("{} {}", marker, stopover_line(s, realtime)));
+        }
+        if let Some(delay) = implausible.first() {
+            lines.push(String::new());
+            lines.push(format!(
+                "? reported delay of {:+}min, likely a glitch (max_delay_minutes: {})",
+                delay, self.max_delay_minutes
+            ));
+        }
+        let changed = lines != self.lines;
+        self.lines = lines;
+        changed
+    }
+}
+
+/// Number of stops the vehicle has left by `now`, by their current times, or planned ones if
+/// there are none. The last stop counts once the vehicle arrived there.
+fn stops_passed(stopovers: &[Stopover], now: DateTime<Utc>) -> usize {
+    stopovers
+        .iter()
+        .rposition(|s| {
+            s.departure
+                .or(s.planned_departure)
+                .or(s.arrival)
+                .or(s.planned_arrival)
+                .is_some_and(|t| t <= now)
+        })
+        .map_or(0, |i| i + 1)
+}
+
+/// e.g. "08:12  08:14  Alexanderplatz". Arrival times, except for the first stop. Without
+/// `realtime`, the current time is shown as "?".
+fn stopover_line(s: &Stopover, realtime: bool) -> String {
+    let clock = |t: Option<DateTime<Utc>>| {
+        t.map(|t| t.with_timezone(&Local).format("%H:%M").to_string())
+            .unwrap_or_else(|| "--:--".to_string())
+    };
+    let name = s
+        .stop
+        .as_ref()
+        .and_then(|s| s.name.as_deref())
+        .unwrap_or("?");
+    let cancelled = if s.cancelled == Some(true) {
+        "  (cancelled)"
+    } else {
+        ""
+    };
+    format!(
+        "{}  {}  {}{}",
+        clock(s.planned_arrival.or(s.planned_departure)),
+        if realtime {
+            clock(s.arrival.or(s.departure))
+        } else {
+            format!("{:<5}", "?")
+        },
+        name,
+        cancelled
+    )
+}
+
+/// Rect of the given size in the middle of `area`.
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    Rect {
+        x: area.x + area.width.saturating_sub(width) / 2,
+        y: area.y + area.height.saturating_sub(height) / 2,
+        width: width.min(area.width),
+        height: height.min(area.height),
+    }
+}
+
+/// None for anything but "#RRGGBB", e.g. the color of a stop built in code without
+/// [`InputStops::validate`].
+fn hex_to_rgb(hex: &str) -> Option<(u8, u8, u8)> {
     let hex = hex.trim_start_matches('#');
-    let r = u8::from_str_radix(&hex[0..2], 16).unwrap_or(255);
-    let g = u8::from_str_radix(&hex[2..4], 16).unwrap_or(255);
-    let b = u8::from_str_radix(&hex[4..6], 16).unwrap_or(255);
-    (r, g, b)
+    let channel = |range| u8::from_str_radix(hex.get(range)?, 16).ok();
+    Some((channel(0..2)?, channel(2..4)?, channel(4..6)?))
 }
 
 fn truncate_line(line: &str, max_width: usize) -> String {
@@ -302,14 +1151,414 @@ fn truncate_line(line: &str, max_width: usize) -> String {
     out
 }
 
-fn max_column_widths(display_lines: &Vec<(String, Vec<DisplayEntry>)>) -> (usize, usize) {
-    let mut max_line = 0usize;
-    let mut max_dir = 0usize;
+/// Optional columns, the same for all station tables.
+struct Columns {
+    /// Planned next to actual times, see `show_planned_times`
+    planned_times: bool,
+    /// Only if any departure has a platform, buses and trams usually do not
+    platform: bool,
+}
+
+impl Columns {
+    fn new(display_lines: &[(StationHeader, Vec<DisplayEntry>)], stops: &InputStops) -> Self {
+        Self {
+            planned_times: stops.show_planned_times,
+            platform: display_lines
+                .iter()
+                .flat_map(|(_, entries)| entries)
+                .any(|e| e.platform.is_some()),
+        }
+    }
+}
+
+/// Column widths shared by all station tables, so the columns line up across stations.
+fn column_widths(
+    display_lines: &[(StationHeader, Vec<DisplayEntry>)],
+    columns: &Columns,
+) -> Vec<Constraint> {
+    let mut max_station = 0usize;
+    let mut max_line = 5usize;
+    let mut max_dir = 1usize;
+    let mut max_time = 5usize;
+    let mut max_countdown = 5usize;
+    let mut max_platform = 3usize;
     for (_, entries) in display_lines {
         for entry in entries {
+            if let Some(station) = &entry.station {
+                max_station = max_station.max(station.name.width());
+            }
             max_line = max_line.max(UnicodeWidthStr::width(entry.line.as_str()));
-            max_dir = max_dir.max(UnicodeWidthStr::width(entry.dir.as_str()));
+            max_dir = max_dir.max(direction_spans(entry).width());
+            max_time = max_time.max(time_text(entry, columns.planned_times).width());
+            max_platform = max_platform.max(entry.platform.as_deref().unwrap_or_default().width());
+            max_countdown = max_countdown.max(countdown_text(entry).width());
         }
     }
-    (max_line, max_dir)
+    let mut widths = vec![
+        Constraint::Length(2),
+        Constraint::Length(max_line as u16),
+        Constraint::Min(max_dir.min(u16::MAX as usize) as u16),
+        Constraint::Length(max_time as u16),
+        Constraint::Length(max_countdown as u16),
+        Constraint::Length(8),
+    ];
+    if columns.platform {
+        widths.insert(3, Constraint::Length(max_platform as u16));
+    }
+    // the merged view has a station column after the symbol
+    if max_station > 0 {
+        widths.insert(1, Constraint::Length(max_station.min(24) as u16));
+    }
+    widths
+}
+
+/// "⚠ Station: text" for each distinct warning on the board
+fn remark_lines(display_lines: &[(StationHeader, Vec<DisplayEntry>)]) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for (station, entries) in display_lines {
+        for e in entries {
+            // in the merged view each departure knows its station
+            let name = &e.station.as_ref().unwrap_or(station).name;
+            for warning in &e.warnings {
+                let line = format!("⚠ {}: {}", name, warning);
+                if !lines.contains(&line) {
+                    lines.push(line);
+                }
+            }
+        }
+    }
+    lines
+}
+
+/// Direction, followed by a badge if the platform changed.
+fn direction_spans(e: &DisplayEntry) -> Spans<'_> {
+    let mut spans = Vec::new();
+    if !e.warnings.is_empty() {
+        spans.push(Span::styled("⚠ ", Style::default().fg(TuiColor::Yellow)));
+    }
+    spans.push(Span::raw(e.dir.as_str()));
+    if let Some(badge) = e.platform_badge() {
+        spans.push(Span::raw(" "));
+        spans.push(Span::styled(
+            badge,
+            Style::default()
+                .fg(TuiColor::Black)
+                .bg(TuiColor::Yellow)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    Spans::from(spans)
+}
+
+fn countdown_text(e: &DisplayEntry) -> String {
+    if e.cancelled {
+        return e.countdown();
+    }
+    format!("{} {}", e.confidence.marker(), e.countdown())
+}
+
+/// With `planned`, the timetable time comes first if the trip is off schedule.
+fn time_text(e: &DisplayEntry, planned: bool) -> String {
+    if planned && let Some(text) = e.planned_and_actual("→") {
+        return text;
+    }
+    e.abs_time
+        .as_ref()
+        .map(|t| clock_label(t, e.day_offset))
+        .unwrap_or_else(|| String::from("--"))
+}
+
+fn header_spans(screen: &Screen) -> Spans<'static> {
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let fetched = screen
+        .board
+        .as_ref()
+        .map(|b| format!("Last updated {}", b.fetched_at.format("%H:%M:%S")))
+        .unwrap_or_default();
+    let throttled = if screen.interval.is_throttled() {
+        format!(
+            "  [rate limited, refreshing every {}s]",
+            screen.interval.current().as_secs()
+        )
+    } else {
+        String::new()
+    };
+    let filters = if screen.filters {
+        ""
+    } else {
+        "  [direction filters off, f to enable]"
+    };
+
+    let sort = format!("  [sorted by {}]", screen.sort);
+    let offline = screen
+        .board
+        .as_ref()
+        .and_then(|b| b.offline_banner())
+        .map(|banner| format!("  {}", banner))
+        .unwrap_or_default();
+
+    match &screen.status {
+        Status::Loading => Spans::from(vec![Span::styled(fetched, bold), Span::raw("  Loading…")]),
+        Status::Ready => Spans::from(vec![
+            Span::styled(fetched, bold),
+            Span::raw(sort),
+            Span::raw(filters),
+            Span::styled(throttled, Style::default().fg(TuiColor::Yellow)),
+            Span::styled(offline, bold.fg(TuiColor::Yellow)),
+        ]),
+        Status::Failed(e) => Spans::from(vec![
+            Span::styled(fetched, bold),
+            Span::styled(
+                format!("  Refresh failed: {} (r to retry)", e),
+                Style::default().fg(TuiColor::Red),
+            ),
+        ]),
+    }
+}
+
+/// Shown instead of the station tables while there is nothing to show.
+fn state_message(screen: &Screen, stops: &InputStops) -> Text<'static> {
+    let lines = match &screen.status {
+        Status::Loading => vec!["Loading departures…".to_string()],
+        Status::Failed(e) => vec![
+            "API unreachable".to_string(),
+            String::new(),
+            e.clone(),
+            String::new(),
+            "Check the network connection and the API base URL, then press r to retry.".to_string(),
+        ],
+        Status::Ready if stops.stops.is_empty() => vec![
+            "No stops configured".to_string(),
+            String::new(),
+            "Add stops to the config file, see `bvg-departures schema config`.".to_string(),
+        ],
+        Status::Ready => vec!["No departures".to_string()],
+    };
+    Text::from(
+        lines
+            .into_iter()
+            .map(|l| Spans::from(Span::raw(l)))
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// e.g. "Ostkreuz unavailable". None if all stations could be fetched.
+fn failed_note(board: &Board) -> Option<String> {
+    let failed: Vec<&str> = board
+        .stations
+        .iter()
+        .filter(|s| s.error.is_some())
+        .map(|s| s.name.as_str())
+        .collect();
+    (!failed.is_empty()).then(|| format!("{} unavailable", failed.join(", ")))
+}
+
+/// Explains an empty station table and how to get departures to show up.
+fn empty_station_hint(stop: &InputStop, filters: bool, filtered: usize) -> String {
+    if filters && !(stop.directions.is_empty() && stop.exclude_directions.is_empty()) {
+        // the API filters by stop ids without saying how many it left out
+        if stop.direction_ids().next().is_some() {
+            "No departures matched your direction filters (f to show all)".to_string()
+        } else {
+            format!(
+                "No departures matched your direction filters ({} filtered out, f to show all)",
+                filtered
+            )
+        }
+    } else {
+        format!("No departures in the next {}min", stop.look_ahead)
+    }
+}
+
+fn station_block<'a>(header: &StationHeader, summary: String, note: Option<String>) -> Block<'a> {
+    let mut name_style = Style::default().add_modifier(Modifier::BOLD);
+    if let Some((r, g, b)) = header.hex.as_deref().and_then(hex_to_rgb) {
+        name_style = name_style.fg(TuiColor::Rgb(r, g, b));
+    }
+    let icon = header
+        .icon
+        .as_ref()
+        .map(|i| format!("{} ", i))
+        .unwrap_or_default();
+
+    let mut title = Spans::from(vec![Span::styled(
+        format!("{}{}: {}", icon, header.label(), header.name),
+        name_style,
+    )]);
+    if let Some(distance) = &header.distance {
+        title.0.push(Span::styled(
+            format!(" · {}", distance),
+            Style::default().fg(TuiColor::DarkGray),
+        ));
+    }
+    title.0.push(Span::styled(
+        format!(" · {}", summary),
+        Style::default().add_modifier(Modifier::ITALIC),
+    ));
+    if let Some(note) = note {
+        title.0.push(Span::styled(
+            format!(" ({})", note),
+            Style::default().fg(TuiColor::DarkGray),
+        ));
+    }
+    Block::default().borders(Borders::ALL).title(title)
+}
+
+fn station_table<'a>(
+    station: &StationHeader,
+    entries: &'a [DisplayEntry],
+    widths: &'a [Constraint],
+    note: Option<String>,
+    watcher: &TripWatcher,
+    columns: &Columns,
+) -> Table<'a> {
+    let merged = entries.iter().any(|e| e.station.is_some());
+    let mut titles = vec!["", "Line", "Direction", "Time", "In", "Delay"];
+    if columns.platform {
+        titles.insert(3, "Pl.");
+    }
+    if merged {
+        titles.insert(1, "Station");
+    }
+    let header = Row::new(titles).style(Style::default().add_modifier(Modifier::BOLD));
+
+    let rows: Vec<Row> = entries
+        .iter()
+        .map(|e| {
+            let line_color =
+                hex_to_rgb(e.hex).map_or(TuiColor::Reset, |(r, g, b)| TuiColor::Rgb(r, g, b));
+            // departed trips stay on the board greyed out until the grace period ends, like the
+            // ones you cannot walk to in time
+            let (tui_color, row_style) = if e.cancelled {
+                (
+                    TuiColor::Red,
+                    Style::default()
+                        .fg(TuiColor::Red)
+                        .add_modifier(Modifier::CROSSED_OUT),
+                )
+            } else if e.ghost {
+                (
+                    TuiColor::DarkGray,
+                    Style::default()
+                        .fg(TuiColor::DarkGray)
+                        .add_modifier(Modifier::ITALIC),
+                )
+            } else if e.actual_mins < 0 || !e.reachable {
+                (TuiColor::DarkGray, Style::default().fg(TuiColor::DarkGray))
+            } else {
+                (line_color, Style::default())
+            };
+            let delay_text = if e.cancelled {
+                String::new()
+            } else {
+                e.delay_text()
+            };
+
+            let pinned = e
+                .trip_id
+                .as_deref()
+                .is_some_and(|id| watcher.is_watched(id));
+            let symbol = if pinned {
+                "📌"
+            } else if e.ghost {
+                "👻"
+            } else {
+                e.symbol
+            };
+
+            let mut cells = vec![
+                Cell::from(symbol),
+                Cell::from(e.line.as_str())
+                    .style(Style::default().bg(tui_color).add_modifier(Modifier::BOLD)),
+                Cell::from(direction_spans(e)),
+                Cell::from(time_text(e, columns.planned_times)),
+                Cell::from(countdown_text(e)).style(if e.cancelled {
+                    // struck-through "CANCELLED" is hard to read
+                    Style::default()
+                        .add_modifier(Modifier::BOLD)
+                        .remove_modifier(Modifier::CROSSED_OUT)
+                } else {
+                    Style::default()
+                }),
+                Cell::from(delay_text),
+            ];
+            if columns.platform {
+                let platform = e.platform.as_deref().unwrap_or_default();
+                // a changed platform is easy to miss at big stations
+                let style = if e.platform_changed {
+                    Style::default()
+                        .fg(TuiColor::Black)
+                        .bg(TuiColor::Yellow)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+                cells.insert(3, Cell::from(platform).style(style));
+            }
+            if merged {
+                let name = e.station.as_ref().map_or("", |s| s.name.as_str());
+                cells.insert(1, Cell::from(name));
+            }
+            Row::new(cells).style(row_style)
+        })
+        .collect();
+
+    Table::new(rows)
+        .header(header)
+        .block(station_block(station, station_summary(entries), note))
+        .widths(widths)
+        .column_spacing(1)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn ignores_malformed_colors() {
+        assert_eq!(hex_to_rgb("#FF8800"), Some((255, 136, 0)));
+        assert_eq!(hex_to_rgb("#F"), None);
+        assert_eq!(hex_to_rgb("#FF88ZZ"), None);
+        // not a char boundary at 2
+        assert_eq!(hex_to_rgb("#Fü00"), None);
+    }
+
+    #[test]
+    fn marks_how_far_the_trip_got() {
+        let now = Utc::now();
+        let at = |mins: i64| (now + chrono::Duration::minutes(mins)).to_rfc3339();
+        let trip: Trip = serde_json::from_value(json!({
+            "stopovers": [
+                { "stop": { "name": "A" }, "plannedDeparture": at(-6), "departure": at(-4) },
+                // planned to have left, but late
+                { "stop": { "name": "B" }, "plannedDeparture": at(-1), "departure": at(1) },
+                { "stop": { "name": "C" }, "plannedArrival": at(5) },
+            ]
+        }))
+        .unwrap();
+        assert_eq!(stops_passed(&trip.stopovers, now), 1);
+        assert_eq!(
+            stops_passed(&trip.stopovers, now - chrono::Duration::minutes(10)),
+            0
+        );
+        assert_eq!(
+            stops_passed(&trip.stopovers, now + chrono::Duration::minutes(5)),
+            3
+        );
+
+        let mut panel = TripPanel::new(trip, 60, now);
+        let markers: Vec<&str> = panel.lines[3..]
+            .iter()
+            .map(|l| &l[..l.find(' ').unwrap()])
+            .collect();
+        assert_eq!(
+            panel.lines[0],
+            format!("{}{} 1/3 stops", "█".repeat(6), "░".repeat(14))
+        );
+        assert_eq!(markers, vec!["·", "▶", ""]);
+        assert!(!panel.update(now));
+        assert!(panel.update(now + chrono::Duration::minutes(2)));
+    }
 }
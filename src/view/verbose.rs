@@ -0,0 +1,216 @@
+use crate::api::departures::DeparturesApi;
+use crate::exec::ExecHook;
+use crate::view::summary::warnings;
+use crate::view::{build_display_lines, DisplayEntry, ResultDisplay, StationHeader};
+use crate::InputStops;
+use async_trait::async_trait;
+use derive_builder::Builder;
+
+/// Prints the board once in full sentences, one per departure, e.g. "U8 toward Wittenau departs
+/// in 4 minutes from platform 2, 2 minutes late." No tables, colors or symbols, for screen readers
+/// and text to speech.
+#[derive(Builder)]
+#[builder(pattern = "owned")]
+pub struct VerboseDisplay<D: DeparturesApi> {
+    api_client: D,
+    stops: InputStops,
+    /// Command to run once the departures have been fetched.
+    #[builder(default)]
+    exec: Option<ExecHook>,
+}
+
+#[async_trait]
+impl<D: DeparturesApi + Sync> ResultDisplay for VerboseDisplay<D> {
+    async fn display(&self) -> anyhow::Result<()> {
+        let board = self.api_client.get_departures(&self.stops).await?;
+        let grouped = build_display_lines(&board, &self.stops);
+
+        if let Some(exec) = &self.exec {
+            let _ = exec.run(&board).await;
+        }
+
+        if let Some(since) = board.stations.iter().filter_map(|s| s.stale_since).min() {
+            println!(
+                "The API is offline, showing the departures as of {}.",
+                since.format("%H:%M")
+            );
+            println!();
+        }
+
+        for (header, entries) in &grouped {
+            let station = board.stations.iter().find(|s| s.stop_id == header.stop_id);
+            if let Some(error) = station.and_then(|s| s.error.as_ref()) {
+                println!(
+                    "Departures from {} are unavailable: {}.",
+                    header.name, error
+                );
+                println!();
+                continue;
+            }
+            let kind = if header.arrivals {
+                "Arrivals at"
+            } else {
+                "Departures from"
+            };
+            if entries.is_empty() {
+                println!("No {} {}.", kind.to_lowercase(), header.name);
+                println!();
+                continue;
+            }
+            println!("{} {}:", kind, header.name);
+            for e in entries {
+                println!("{}", sentence(header, e));
+            }
+            for warning in warnings(entries) {
+                println!("Warning: {}", end_sentence(warning));
+            }
+            println!();
+        }
+        Ok(())
+    }
+}
+
+/// e.g. "U8 toward Wittenau departs in 4 minutes from platform 2, 2 minutes late."
+pub(crate) fn sentence(header: &StationHeader, e: &DisplayEntry) -> String {
+    let line = if e.line == "?" {
+        "Unknown line"
+    } else {
+        e.line.as_str()
+    };
+    let (from, verb, past) = if header.arrivals {
+        ("from", "arrives", "arrived")
+    } else {
+        ("toward", "departs", "departed")
+    };
+    let mut out = line.to_string();
+    if !e.dir.is_empty() {
+        out.push_str(&format!(" {} {}", from, e.dir));
+    }
+
+    if e.cancelled {
+        match &e.abs_time {
+            Some(time) => out.push_str(&format!(" at {} is cancelled.", time)),
+            None => out.push_str(" is cancelled."),
+        }
+        return out;
+    }
+    match e.actual_mins {
+        m if m < 0 => out.push_str(&format!(" {} {} ago", past, minutes(-m))),
+        0 => out.push_str(&format!(" {} now", verb)),
+        m => out.push_str(&format!(" {} in {}", verb, minutes(m))),
+    }
+    // departed ones already say when, the rest is of no use
+    if e.actual_mins < 0 {
+        out.push('.');
+        return out;
+    }
+    if let Some(platform) = &e.platform {
+        if e.platform_changed {
+            out.push_str(&format!(" from changed platform {}", platform));
+        } else {
+            out.push_str(&format!(" from platform {}", platform));
+        }
+    }
+    match e.delay_mins {
+        Some(d) if d > 0 => out.push_str(&format!(", {} late", minutes(d))),
+        Some(d) if d < 0 => out.push_str(&format!(", {} early", minutes(-d))),
+        _ => {}
+    }
+    if !e.reachable {
+        out.push_str(", too soon to walk there");
+    } else if let Some(leave_in) = e.leave_in_mins {
+        match leave_in {
+            m if m <= 0 => out.push_str(", leave now"),
+            m => out.push_str(&format!(", leave in {}", minutes(m))),
+        }
+    }
+    out.push('.');
+    out
+}
+
+/// e.g. "1 minute" or "1 hour and 5 minutes"
+fn minutes(m: i64) -> String {
+    let unit = |n: i64, name: &str| {
+        if n == 1 {
+            format!("1 {}", name)
+        } else {
+            format!("{} {}s", n, name)
+        }
+    };
+    match (m / 60, m % 60) {
+        (0, m) => unit(m, "minute"),
+        (h, 0) => unit(h, "hour"),
+        (h, m) => format!("{} and {}", unit(h, "hour"), unit(m, "minute")),
+    }
+}
+
+/// Remark texts do not always end with a full stop.
+fn end_sentence(text: &str) -> String {
+    let text = text.trim();
+    if text.ends_with(['.', '!', '?']) {
+        text.to_string()
+    } else {
+        format!("{}.", text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::view::Confidence;
+
+    fn entry(actual_mins: i64) -> DisplayEntry {
+        DisplayEntry {
+            trip_id: None,
+            line: "U8".to_string(),
+            dir: "Wittenau".to_string(),
+            actual_mins,
+            delay_mins: Some(2),
+            implausible_delay_mins: None,
+            symbol: "🚇",
+            hex: "#224F86",
+            abs_time: Some("12:04".to_string()),
+            planned_time: Some("12:02".to_string()),
+            day_offset: 0,
+            warnings: vec![],
+            confidence: Confidence::Realtime,
+            platform: Some("2".to_string()),
+            platform_changed: false,
+            cancelled: false,
+            ghost: false,
+            reachable: true,
+            leave_in_mins: None,
+            later_mins: vec![],
+            headway_mins: None,
+            station: None,
+        }
+    }
+
+    #[test]
+    fn describes_departures_in_full_sentences() {
+        let header = StationHeader {
+            stop_id: "900100003".to_string(),
+            name: "Alexanderplatz".to_string(),
+            icon: None,
+            hex: None,
+            arrivals: false,
+            distance: None,
+        };
+        assert_eq!(
+            sentence(&header, &entry(4)),
+            "U8 toward Wittenau departs in 4 minutes from platform 2, 2 minutes late."
+        );
+        assert_eq!(
+            sentence(&header, &entry(65)),
+            "U8 toward Wittenau departs in 1 hour and 5 minutes from platform 2, 2 minutes late."
+        );
+        let cancelled = DisplayEntry {
+            cancelled: true,
+            ..entry(4)
+        };
+        assert_eq!(
+            sentence(&header, &cancelled),
+            "U8 toward Wittenau at 12:04 is cancelled."
+        );
+    }
+}
@@ -0,0 +1,426 @@
+use crate::board::{Board, BoardRow};
+use crate::InputStops;
+use chrono::{Duration, Utc};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Tracks pinned trips across refreshes and reports when they get cancelled, late or moved to
+/// another platform.
+///
+/// Each change is only reported once, when it first shows up on the board.
+#[derive(Debug, Default)]
+pub struct TripWatcher {
+    trips: HashMap<String, WatchedTrip>,
+}
+
+#[derive(Debug)]
+struct WatchedTrip {
+    label: String,
+    cancelled: bool,
+    late: bool,
+    /// Platform the trip was last reported moved to
+    platform: Option<String>,
+}
+
+/// Change of a watched trip worth a notification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TripAlert {
+    Cancelled {
+        trip_id: String,
+        label: String,
+    },
+    Late {
+        trip_id: String,
+        label: String,
+        minutes: i64,
+    },
+    PlatformChanged {
+        trip_id: String,
+        label: String,
+        platform: String,
+    },
+}
+
+impl TripAlert {
+    pub fn trip_id(&self) -> &str {
+        match self {
+            TripAlert::Cancelled { trip_id, .. }
+            | TripAlert::Late { trip_id, .. }
+            | TripAlert::PlatformChanged { trip_id, .. } => trip_id,
+        }
+    }
+
+    /// Kind of change, e.g. to send each kind of a trip only once in a while
+    pub fn rule(&self) -> &'static str {
+        match self {
+            TripAlert::Cancelled { .. } => "cancelled",
+            TripAlert::Late { .. } => "late",
+            TripAlert::PlatformChanged { .. } => "platform",
+        }
+    }
+}
+
+impl fmt::Display for TripAlert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TripAlert::Cancelled { label, .. } => write!(f, "{} is cancelled", label),
+            TripAlert::Late { label, minutes, .. } => {
+                write!(f, "{} is {}min late", label, minutes)
+            }
+            TripAlert::PlatformChanged {
+                label, platform, ..
+            } => {
+                write!(f, "{} now leaves from platform {}", label, platform)
+            }
+        }
+    }
+}
+
+/// Tracks whether watched lines are disrupted across refreshes, to report when they recover.
+#[derive(Debug, Default)]
+pub struct LineWatcher {
+    /// Lines disrupted on the last board that showed them
+    disrupted: HashSet<String>,
+}
+
+/// A watched line that was disrupted runs normally again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineRecovered {
+    pub line: String,
+}
+
+impl fmt::Display for LineRecovered {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: service recovered", self.line)
+    }
+}
+
+impl LineWatcher {
+    /// Compares `lines` with a fresh board. A line is disrupted while one of its departures is
+    /// cancelled or `delay_threshold_minutes` late, and recovers once all of them are on time again.
+    /// Lines missing from the board keep their state.
+    pub fn check(
+        &mut self,
+        board: &Board,
+        lines: &[String],
+        delay_threshold_minutes: u32,
+    ) -> Vec<LineRecovered> {
+        let mut recovered = Vec::new();
+        for line in lines {
+            let rows: Vec<&BoardRow> = board
+                .stations
+                .iter()
+                .flat_map(|s| &s.rows)
+                .filter(|r| {
+                    r.line
+                        .as_deref()
+                        .is_some_and(|l| l.eq_ignore_ascii_case(line))
+                })
+                .collect();
+            // named as on the board, which may differ in case from the config
+            let Some(name) = rows.first().and_then(|r| r.line.clone()) else {
+                continue;
+            };
+            let disrupted = rows.iter().any(|r| {
+                r.cancelled
+                    || (!r.implausible_delay
+                        && r.delay.unwrap_or_default() / 60 >= i64::from(delay_threshold_minutes))
+            });
+            if disrupted {
+                self.disrupted.insert(line.clone());
+            } else if self.disrupted.remove(line) {
+                recovered.push(LineRecovered { line: name });
+            }
+        }
+        recovered
+    }
+}
+
+impl TripWatcher {
+    /// Starts or stops watching a trip. Returns whether it is watched now.
+    pub fn toggle(&mut self, trip_id: &str, label: String) -> bool {
+        if self.trips.remove(trip_id).is_some() {
+            return false;
+        }
+        self.trips.insert(
+            trip_id.to_string(),
+            WatchedTrip {
+                label,
+                cancelled: false,
+                late: false,
+                platform: None,
+            },
+        );
+        true
+    }
+
+    pub fn is_watched(&self, trip_id: &str) -> bool {
+        self.trips.contains_key(trip_id)
+    }
+
+    /// Compares the watched trips with a fresh board. A trip counts as late once its delay
+    /// reaches `delay_threshold_minutes`.
+    pub fn check(&mut self, board: &Board, delay_threshold_minutes: u32) -> Vec<TripAlert> {
+        let mut alerts = Vec::new();
+        // the same trip can show up at several stops
+        let mut seen = HashSet::new();
+        let rows = board.stations.iter().flat_map(|s| &s.rows);
+        for row in rows {
+            let Some(id) = row.trip_id.as_deref() else {
+                continue;
+            };
+            let Some(trip) = self.trips.get_mut(id) else {
+                continue;
+            };
+            if !seen.insert(id) {
+                continue;
+            }
+
+            if row.cancelled && !trip.cancelled {
+                alerts.push(TripAlert::Cancelled {
+                    trip_id: id.to_string(),
+                    label: trip.label.clone(),
+                });
+            }
+            trip.cancelled = row.cancelled;
+
+            let minutes = row
+                .delay
+                .filter(|_| !row.implausible_delay)
+                .unwrap_or_default()
+                / 60;
+            let late = minutes >= i64::from(delay_threshold_minutes);
+            if late && !trip.late && !row.cancelled {
+                alerts.push(TripAlert::Late {
+                    trip_id: id.to_string(),
+                    label: trip.label.clone(),
+                    minutes,
+                });
+            }
+            trip.late = late;
+
+            let platform = row.changed_platform();
+            if let Some(platform) = platform.filter(|p| trip.platform.as_deref() != Some(p)) {
+                alerts.push(TripAlert::PlatformChanged {
+                    trip_id: id.to_string(),
+                    label: trip.label.clone(),
+                    platform: platform.to_string(),
+                });
+            }
+            trip.platform = platform.map(str::to_string);
+        }
+        alerts
+    }
+}
+
+/// Remembers the departures of each stop across refreshes to spot ghost runs: trips that drop
+/// out of the realtime data before they leave, without being cancelled.
+#[derive(Debug, Default)]
+pub struct GhostWatcher {
+    /// Departures of the last board by stop id and trip id, ghosts included
+    seen: HashMap<String, HashMap<String, BoardRow>>,
+}
+
+/// Trips leaving this soon may just have left early or been dropped as departed.
+const GHOST_MARGIN_MINUTES: i64 = 2;
+
+impl GhostWatcher {
+    /// Adds the departures that went missing since the last board back to it, marked as ghosts,
+    /// until their time has passed. Stops that failed or only have stale data are skipped.
+    pub fn check(&mut self, board: &mut Board, stops: &InputStops) {
+        let earliest = Utc::now() + Duration::minutes(GHOST_MARGIN_MINUTES);
+        for station in &mut board.stations {
+            if station.error.is_some() || station.stale_since.is_some() {
+                continue;
+            }
+            let Some(stop) = stops.stops.iter().find(|s| s.id == station.stop_id) else {
+                continue;
+            };
+            // trips past the window would be missing anyway
+            let latest =
+                board.fetched_at.with_timezone(&Utc) + Duration::minutes(stop.look_ahead.into());
+            let previous = self.seen.remove(&station.stop_id).unwrap_or_default();
+            let current: HashSet<&str> = station
+                .rows
+                .iter()
+                .filter_map(|r| r.trip_id.as_deref())
+                .collect();
+            let ghosts: Vec<BoardRow> = previous
+                .into_iter()
+                .filter(|(id, _)| !current.contains(id.as_str()))
+                .map(|(_, row)| row)
+                .filter(|row| {
+                    !row.cancelled
+                        && row
+                            .when
+                            .or(row.planned_when)
+                            .is_some_and(|t| t > earliest && t < latest)
+                })
+                .map(|row| BoardRow { ghost: true, ..row })
+                .collect();
+            if !ghosts.is_empty() {
+                station.rows.extend(ghosts);
+                station.rows.sort_by_key(|r| r.when.or(r.planned_when));
+            }
+
+            let seen = station
+                .rows
+                .iter()
+                .filter_map(|r| Some((r.trip_id.clone()?, r.clone())))
+                .collect();
+            self.seen.insert(station.stop_id.clone(), seen);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::StationBoard;
+    use crate::DeparturesError;
+    use chrono::Local;
+    use serde_json::json;
+
+    /// Board of the first stop with departures of the U8 as (trip id, minutes until they leave,
+    /// cancelled, delay in seconds, platform). The planned platform is "1".
+    fn board(stops: &InputStops, departures: &[(&str, i64, bool, i64, &str)]) -> Board {
+        let departures: Vec<_> = departures
+            .iter()
+            .map(|(id, mins, cancelled, delay_s, platform)| {
+                json!({
+                    "tripId": id,
+                    "direction": "Pankow",
+                    "line": { "name": "U8" },
+                    "when": (Utc::now() + Duration::minutes(*mins)).to_rfc3339(),
+                    "cancelled": cancelled,
+                    "delay": delay_s,
+                    "platform": platform,
+                    "plannedPlatform": "1",
+                })
+            })
+            .collect();
+        let response = serde_json::from_value(json!({ "departures": departures })).unwrap();
+        Board {
+            stations: vec![StationBoard::new(&stops.stops[0], response)],
+            fetched_at: Local::now(),
+        }
+    }
+
+    fn trip_ids(board: &Board) -> Vec<(&str, bool)> {
+        board.stations[0]
+            .rows
+            .iter()
+            .map(|r| (r.trip_id.as_deref().unwrap(), r.ghost))
+            .collect()
+    }
+
+    #[test]
+    fn missing_trips_come_back_as_ghosts() {
+        let stops: InputStops =
+            serde_yaml::from_str("stops:\n  - id: '1'\n    name: A\n    look_ahead: 30\n").unwrap();
+        let mut watcher = GhostWatcher::default();
+        let mut first = board(
+            &stops,
+            &[
+                ("soon", 1, false, 0, "1"),
+                ("ghost", 10, false, 0, "1"),
+                ("later", 40, false, 0, "1"),
+            ],
+        );
+        watcher.check(&mut first, &stops);
+
+        // within the margin and past the look-ahead they are likely gone for good
+        let mut second = board(&stops, &[]);
+        watcher.check(&mut second, &stops);
+        assert_eq!(trip_ids(&second), vec![("ghost", true)]);
+
+        // stale or failed stops say nothing about which trips still run
+        let mut stale = board(&stops, &[]);
+        stale.stations[0].stale_since = Some(Local::now());
+        watcher.check(&mut stale, &stops);
+        assert!(trip_ids(&stale).is_empty());
+        let mut failed = board(&stops, &[]);
+        failed.stations[0] =
+            StationBoard::failed(&stops.stops[0], &DeparturesError::Unsupported("Departures"));
+        watcher.check(&mut failed, &stops);
+        assert!(failed.stations[0].rows.is_empty());
+
+        // ghosts are remembered like any other departure
+        let mut third = board(&stops, &[]);
+        watcher.check(&mut third, &stops);
+        assert_eq!(trip_ids(&third), vec![("ghost", true)]);
+    }
+
+    #[test]
+    fn alerts_each_change_of_a_pinned_trip_once() {
+        let stops: InputStops = serde_yaml::from_str("stops:\n  - id: '1'\n    name: A\n").unwrap();
+        let mut watcher = TripWatcher::default();
+        watcher.toggle("t1", "U2 to Pankow".to_string());
+        let label = "U2 to Pankow".to_string();
+
+        assert!(watcher
+            .check(&board(&stops, &[("t1", 10, false, -30, "1")]), 5)
+            .is_empty());
+        assert!(watcher
+            .check(&board(&stops, &[("t1", 10, false, 120, "1")]), 5)
+            .is_empty());
+
+        let late = board(&stops, &[("t1", 10, false, 360, "1")]);
+        assert_eq!(
+            watcher.check(&late, 5),
+            vec![TripAlert::Late {
+                trip_id: "t1".to_string(),
+                label: label.clone(),
+                minutes: 6
+            }]
+        );
+        assert!(watcher.check(&late, 5).is_empty());
+
+        let moved = board(&stops, &[("t1", 10, false, 360, "2")]);
+        assert_eq!(
+            watcher.check(&moved, 5),
+            vec![TripAlert::PlatformChanged {
+                trip_id: "t1".to_string(),
+                label: label.clone(),
+                platform: "2".to_string()
+            }]
+        );
+        assert!(watcher.check(&moved, 5).is_empty());
+
+        let cancelled = board(&stops, &[("t1", 10, true, 360, "2")]);
+        assert_eq!(
+            watcher.check(&cancelled, 5),
+            vec![TripAlert::Cancelled {
+                trip_id: "t1".to_string(),
+                label
+            }]
+        );
+        assert!(watcher.check(&cancelled, 5).is_empty());
+    }
+
+    #[test]
+    fn reports_a_recovered_line_once() {
+        let stops: InputStops = serde_yaml::from_str("stops:\n  - id: '1'\n    name: A\n").unwrap();
+        let lines = vec!["u8".to_string()];
+        let mut watcher = LineWatcher::default();
+        let recovered = vec![LineRecovered {
+            line: "U8".to_string(),
+        }];
+
+        assert!(watcher
+            .check(&board(&stops, &[("t1", 10, true, 0, "1")]), &lines, 5)
+            .is_empty());
+        assert!(watcher
+            .check(&board(&stops, &[("t1", 10, false, 600, "1")]), &lines, 5)
+            .is_empty());
+        // missing from the board: still disrupted
+        assert!(watcher.check(&board(&stops, &[]), &lines, 5).is_empty());
+        assert_eq!(
+            watcher.check(&board(&stops, &[("t1", 10, false, 60, "1")]), &lines, 5),
+            recovered
+        );
+        assert!(watcher
+            .check(&board(&stops, &[("t1", 10, false, 60, "1")]), &lines, 5)
+            .is_empty());
+    }
+}
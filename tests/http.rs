@@ -0,0 +1,424 @@
+//! `BvgClient` against a mock of the API: URLs, parsing of odd payloads and filtering.
+
+use bvg_departures::api::retry::RetryPolicy;
+use bvg_departures::schedule::AdaptiveInterval;
+use bvg_departures::{BvgClient, DeparturesApi, DeparturesError, InputStops};
+use serde_json::json;
+use std::time::Duration;
+use url::Url;
+use wiremock::matchers::{method, path, query_param, query_param_is_missing};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn client(server: &MockServer) -> BvgClient {
+    BvgClient::new(Url::parse(&server.uri()).unwrap()).with_retry(RetryPolicy {
+        base_delay: Duration::from_millis(10),
+        ..RetryPolicy::default()
+    })
+}
+
+fn stops(yaml: &str) -> InputStops {
+    serde_yaml::from_str(yaml).unwrap()
+}
+
+fn departure(trip_id: &str, line: Option<&str>, direction: &str) -> serde_json::Value {
+    json!({
+        "tripId": trip_id,
+        "direction": direction,
+        "line": line.map(|name| json!({"id": name.to_lowercase(), "name": name, "product": "subway"})),
+        "when": "2030-01-01T12:05:00+01:00",
+        "plannedWhen": "2030-01-01T12:04:00+01:00",
+        "delay": 60,
+        "platform": "2",
+        "plannedPlatform": "1",
+    })
+}
+
+#[tokio::test]
+async fn requests_the_departures_of_each_stop() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/stops/900100003/departures"))
+        .and(query_param("duration", "22"))
+        .and(query_param("linesOfStops", "false"))
+        .and(query_param("remarks", "true"))
+        .and(query_param("language", "en"))
+        .and(query_param("subway", "true"))
+        .and(query_param("bus", "false"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({"departures": []})))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let config = stops(
+        "grace_minutes: 2\nstops:\n  - id: '900100003'\n    name: Alex\n    look_ahead: 20\n    products: [subway]\n",
+    );
+    let board = client(&server)
+        .with_language("en")
+        .get_departures(&config)
+        .await
+        .unwrap();
+    assert_eq!(board.stations[0].name, "Alex");
+    assert!(board.stations[0].rows.is_empty());
+}
+
+#[tokio::test]
+async fn parses_odd_departures() {
+    let server = MockServer::start().await;
+    let mut no_when = departure("1", Some("U8"), "Wittenau");
+    no_when["when"] = json!(null);
+    let mut cancelled = departure("2", Some("U8"), "Hermannstr.");
+    cancelled["cancelled"] = json!(true);
+    let no_line = departure("3", None, "Wittenau");
+    Mock::given(path("/stops/900100003/departures"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "departures": [no_when, cancelled, no_line],
+            "realtimeDataUpdatedAt": 1893495600,
+        })))
+        .mount(&server)
+        .await;
+
+    let config = stops("stops:\n  - id: '900100003'\n    name: Alex\n");
+    let board = client(&server).get_departures(&config).await.unwrap();
+    let station = &board.stations[0];
+    assert_eq!(station.realtime_data_updated_at, Some(1893495600));
+    let rows = &station.rows;
+    assert_eq!(rows.len(), 3);
+    assert_eq!(rows[0].when, None);
+    assert!(rows[0].planned_when.is_some());
+    assert_eq!(rows[0].changed_platform(), Some("2"));
+    assert!(rows[1].cancelled);
+    assert_eq!(rows[1].line.as_deref(), Some("U8"));
+    assert_eq!(rows[2].line, None);
+    assert_eq!(rows[2].product, None);
+}
+
+#[tokio::test]
+async fn parses_v5_arrays_and_null_departures() {
+    let server = MockServer::start().await;
+    Mock::given(path("/stops/1/departures"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([departure(
+            "1",
+            Some("S41"),
+            "Ring"
+        )])))
+        .mount(&server)
+        .await;
+    Mock::given(path("/stops/2/departures"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({"departures": null})))
+        .mount(&server)
+        .await;
+
+    let config = stops("stops:\n  - id: '1'\n    name: A\n  - id: '2'\n    name: B\n");
+    let board = client(&server).get_departures(&config).await.unwrap();
+    assert_eq!(board.stations[0].rows[0].line.as_deref(), Some("S41"));
+    assert!(board.stations[1].rows.is_empty());
+}
+
+#[tokio::test]
+async fn filters_by_direction() {
+    let server = MockServer::start().await;
+    Mock::given(path("/stops/900100003/departures"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "departures": [
+                departure("1", Some("U8"), "S+U Wittenau (Berlin)"),
+                departure("2", Some("U8"), "S+U Hermannstr. (Berlin)"),
+            ],
+        })))
+        .mount(&server)
+        .await;
+
+    let config = stops("stops:\n  - id: '900100003'\n    name: Alex\n    directions: [Wittenau]\n");
+    let board = client(&server).get_departures(&config).await.unwrap();
+    let station = &board.stations[0];
+    assert_eq!(station.rows.len(), 1);
+    assert_eq!(station.rows[0].trip_id.as_deref(), Some("1"));
+    assert_eq!(station.filter_stats.total(), 1);
+}
+
+#[tokio::test]
+async fn passes_direction_stop_ids_to_the_api() {
+    let server = MockServer::start().await;
+    Mock::given(path("/stops/900100003/departures"))
+        .and(query_param("direction", "900009104"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "departures": [departure("1", Some("U8"), "S+U Wittenau (Berlin)")],
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(path("/stops/900100003/departures"))
+        .and(query_param_is_missing("direction"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "departures": [
+                departure("1", Some("U8"), "S+U Wittenau (Berlin)"),
+                departure("2", Some("U8"), "S+U Hermannstr. (Berlin)"),
+                departure("3", Some("U2"), "S+U Pankow (Berlin)"),
+            ],
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let config = stops(
+        "stops:\n  - id: '900100003'\n    name: Alex\n    directions: ['900009104', Hermannstr.]\n",
+    );
+    let board = client(&server).get_departures(&config).await.unwrap();
+    let mut trips: Vec<_> = board.stations[0]
+        .rows
+        .iter()
+        .filter_map(|r| r.trip_id.as_deref())
+        .collect();
+    // both leave at the same time
+    trips.sort();
+    assert_eq!(trips, vec!["1", "2"]);
+}
+
+#[tokio::test]
+async fn concurrency_bounds_the_requests_of_all_directions() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({"departures": []}))
+                .set_delay(Duration::from_millis(200)),
+        )
+        .expect(3)
+        .mount(&server)
+        .await;
+
+    let config = stops(
+        "stops:\n  - id: '1'\n    name: A\n    directions: ['900009104', '900009105']\n  - id: '2'\n    name: B\n",
+    );
+    let started = std::time::Instant::now();
+    client(&server)
+        .with_concurrency(1)
+        .get_departures(&config)
+        .await
+        .unwrap();
+    // one after the other, not both directions of A at once
+    assert!(started.elapsed() >= Duration::from_millis(600));
+}
+
+#[tokio::test]
+async fn concurrency_bounds_reading_the_bodies_too() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    // wiremock delays the whole response, this one only the body
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            tokio::spawn(async move {
+                let mut request = [0; 1024];
+                let _ = socket.read(&mut request).await;
+                let body = br#"{"departures": []}"#;
+                let head = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+                    body.len()
+                );
+                socket.write_all(head.as_bytes()).await.unwrap();
+                socket.flush().await.unwrap();
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                socket.write_all(body).await.unwrap();
+            });
+        }
+    });
+
+    let config =
+        stops("stops:\n  - id: '1'\n    name: A\n    directions: ['900009104', '900009105']\n");
+    let started = std::time::Instant::now();
+    BvgClient::new(base)
+        .with_concurrency(1)
+        .get_departures(&config)
+        .await
+        .unwrap();
+    // the directions of A one after the other, also while the body is still arriving
+    assert!(started.elapsed() >= Duration::from_millis(400));
+}
+
+#[tokio::test]
+async fn retries_server_errors() {
+    let server = MockServer::start().await;
+    Mock::given(path("/stops/1/departures"))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(path("/stops/1/departures"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({"departures": [departure("1", Some("U2"), "Pankow")]})),
+        )
+        .mount(&server)
+        .await;
+
+    let config = stops("stops:\n  - id: '1'\n    name: A\n");
+    let board = client(&server).get_departures(&config).await.unwrap();
+    assert_eq!(board.stations[0].rows.len(), 1);
+}
+
+#[tokio::test]
+async fn one_failing_stop_does_not_hide_the_others() {
+    let server = MockServer::start().await;
+    Mock::given(path("/stops/1/departures"))
+        .respond_with(ResponseTemplate::new(400).set_body_string("unknown stop"))
+        .mount(&server)
+        .await;
+    Mock::given(path("/stops/2/departures"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({"departures": []})))
+        .mount(&server)
+        .await;
+
+    let both = stops("stops:\n  - id: '1'\n    name: A\n  - id: '2'\n    name: B\n");
+    let board = client(&server).get_departures(&both).await.unwrap();
+    assert!(board.stations[0]
+        .error
+        .as_deref()
+        .unwrap()
+        .contains("unknown stop"));
+    assert!(board.stations[1].error.is_none());
+
+    let only_failing = stops("stops:\n  - id: '1'\n    name: A\n");
+    let error = client(&server)
+        .get_departures(&only_failing)
+        .await
+        .unwrap_err();
+    assert!(matches!(error, DeparturesError::Status { status, .. } if status == 400));
+}
+
+#[tokio::test]
+async fn slow_stops_time_out_without_holding_up_the_others() {
+    let server = MockServer::start().await;
+    Mock::given(path("/stops/1/departures"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({"departures": []}))
+                .set_delay(Duration::from_secs(5)),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(path("/stops/2/departures"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({"departures": []})))
+        .mount(&server)
+        .await;
+
+    let config =
+        stops("stops:\n  - id: '1'\n    name: A\n    timeout_ms: 100\n  - id: '2'\n    name: B\n");
+    let started = std::time::Instant::now();
+    let board = client(&server).get_departures(&config).await.unwrap();
+    assert!(started.elapsed() < Duration::from_secs(2));
+    assert_eq!(
+        board.stations[0].error.as_deref(),
+        Some("Timed out after 100ms")
+    );
+    assert!(board.stations[1].error.is_none());
+}
+
+#[tokio::test]
+async fn a_slow_stop_shows_its_last_departures() {
+    let server = MockServer::start().await;
+    Mock::given(path("/stops/1/departures"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({"departures": [departure("1", Some("U2"), "Pankow")]})),
+        )
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(path("/stops/1/departures"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({"departures": []}))
+                .set_delay(Duration::from_secs(5)),
+        )
+        .mount(&server)
+        .await;
+
+    let config = stops("stops:\n  - id: '1'\n    name: A\n    timeout_ms: 100\n");
+    let client = client(&server);
+    let fresh = client.get_departures(&config).await.unwrap();
+    assert!(fresh.stations[0].stale_since.is_none());
+    let stale = client.get_departures(&config).await.unwrap();
+    assert!(stale.stations[0].error.is_none());
+    assert!(stale.stations[0].stale_since.is_some());
+    assert_eq!(stale.stations[0].rows.len(), 1);
+}
+
+#[tokio::test]
+async fn shows_the_last_departures_while_the_api_fails() {
+    let server = MockServer::start().await;
+    Mock::given(path("/stops/1/departures"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({"departures": [departure("1", Some("U2"), "Pankow")]})),
+        )
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(path("/stops/1/departures"))
+        .respond_with(ResponseTemplate::new(503))
+        .mount(&server)
+        .await;
+
+    let config = stops("stops:\n  - id: '1'\n    name: A\n");
+    let client = client(&server);
+    let fresh = client.get_departures(&config).await.unwrap();
+    assert!(fresh.stations[0].stale_since.is_none());
+    let stale = client.get_departures(&config).await.unwrap();
+    assert!(stale.stations[0].stale_since.is_some());
+    assert_eq!(stale.stations[0].rows.len(), 1);
+}
+
+#[tokio::test]
+async fn a_rate_limited_stop_stretches_the_refresh_interval() {
+    let server = MockServer::start().await;
+    Mock::given(path("/stops/1/departures"))
+        .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "120"))
+        .mount(&server)
+        .await;
+    Mock::given(path("/stops/2/departures"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({"departures": []})))
+        .mount(&server)
+        .await;
+
+    let both = stops("stops:\n  - id: '1'\n    name: A\n  - id: '2'\n    name: B\n");
+    let client = client(&server);
+    let mut interval = AdaptiveInterval::new(Duration::from_secs(30));
+    // the breaker opens after a few of them, which still counts as rate limited
+    for _ in 0..4 {
+        let board = client.get_departures(&both).await.unwrap();
+        assert!(board.stations[1].error.is_none());
+        interval.after(&board);
+    }
+    assert_eq!(interval.current(), Duration::from_secs(900));
+}
+
+#[tokio::test]
+async fn saved_departures_shown_for_a_rate_limit_still_stretch_the_interval() {
+    let server = MockServer::start().await;
+    Mock::given(path("/stops/1/departures"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({"departures": [departure("1", Some("U2"), "Pankow")]})),
+        )
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(path("/stops/1/departures"))
+        .respond_with(ResponseTemplate::new(429))
+        .mount(&server)
+        .await;
+
+    let config = stops("stops:\n  - id: '1'\n    name: A\n");
+    let client = client(&server);
+    let mut interval = AdaptiveInterval::new(Duration::from_secs(30));
+    interval.after(&client.get_departures(&config).await.unwrap());
+    assert!(!interval.is_throttled());
+
+    let stale = client.get_departures(&config).await.unwrap();
+    assert!(stale.stations[0].stale_since.is_some());
+    assert_eq!(stale.stations[0].rate_limited, Some(None));
+    interval.after(&stale);
+    assert_eq!(interval.current(), Duration::from_secs(60));
+}